@@ -1 +1,5 @@
 pub const INTERPOLATION_DELAY: f32 = 1.0;
+// seconds
+pub const HANDSHAKE_TIMEOUT: f32 = 5.0;
+// seconds without hearing from the server before trying to resume the session
+pub const RECONNECT_AFTER: f32 = 3.0;
@@ -0,0 +1,289 @@
+use anyhow::{Result, anyhow};
+use ggez::event::{Axis, Button, GamepadId};
+use ggez::input::keyboard::KeyCode;
+use simulation::{Action, PlayerInput};
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::FRAC_PI_8;
+
+/// Which actions each key and gamepad button triggers, from the
+/// `[controls]` and `[gamepad]` config sections
+#[derive(Clone, Default)]
+pub struct Bindings {
+    keys: HashMap<KeyCode, Vec<Action>>,
+    buttons: HashMap<Button, Vec<Action>>,
+    // how far a stick has to be pushed, from 0 to 1
+    deadzone: f32,
+    // otherwise only the device used last counts
+    keyboard_with_gamepad: bool,
+}
+
+/// The last kind of device the player touched
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum Device {
+    #[default]
+    Keyboard,
+    Gamepad,
+}
+
+/// Everything the player is holding down, on any device
+#[derive(Default)]
+pub struct HeldInput {
+    keys: HashSet<KeyCode>,
+    buttons: HashSet<(GamepadId, Button)>,
+    // left stick of each gamepad, y points up
+    sticks: HashMap<GamepadId, [f32; 2]>,
+    last_device: Device,
+}
+
+impl Bindings {
+    /// `keys` and `buttons` list names for each action, unknown names are an error
+    pub fn new(
+        keys: &HashMap<Action, Vec<String>>,
+        buttons: &HashMap<Action, Vec<String>>,
+        deadzone: f32,
+        keyboard_with_gamepad: bool,
+    ) -> Result<Self> {
+        let keys = parse_bindings(keys, key_code, "key")?;
+        let buttons = parse_bindings(buttons, button, "button")?;
+
+        for action in Action::ALL {
+            if !keys.values().flatten().any(|a| *a == action) {
+                eprintln!("No key is bound to {action}");
+            }
+        }
+
+        Ok(Self {
+            keys,
+            buttons,
+            deadzone: deadzone.clamp(0.0, 1.0),
+            keyboard_with_gamepad,
+        })
+    }
+
+    /// What the player is doing with everything held down
+    #[must_use]
+    pub fn input(&self, held: &HeldInput) -> PlayerInput {
+        let mut input = PlayerInput::default();
+        let keyboard = self.keyboard_with_gamepad || held.last_device == Device::Keyboard;
+        let gamepad = self.keyboard_with_gamepad || held.last_device == Device::Gamepad;
+
+        if keyboard {
+            for action in held
+                .keys
+                .iter()
+                .filter_map(|key| self.keys.get(key))
+                .flatten()
+            {
+                input.set(*action, true);
+            }
+        }
+
+        if gamepad {
+            for action in held
+                .buttons
+                .iter()
+                .filter_map(|(_, button)| self.buttons.get(button))
+                .flatten()
+            {
+                input.set(*action, true);
+            }
+
+            for [x, y] in held.sticks.values() {
+                let length = x.hypot(*y);
+                if length < self.deadzone {
+                    continue;
+                }
+
+                // eight equal directions, diagonals included, like the keys give
+                let threshold = length * FRAC_PI_8.sin();
+                if *x < -threshold {
+                    input.set(Action::Left, true);
+                }
+                if *x > threshold {
+                    input.set(Action::Right, true);
+                }
+                if *y > threshold {
+                    input.set(Action::Up, true);
+                }
+                if *y < -threshold {
+                    input.set(Action::Slam, true);
+                }
+            }
+        }
+
+        input
+    }
+
+    #[must_use]
+    pub fn deadzone(&self) -> f32 {
+        self.deadzone
+    }
+}
+
+impl HeldInput {
+    pub fn press_key(&mut self, key: KeyCode) {
+        self.keys.insert(key);
+        self.last_device = Device::Keyboard;
+    }
+
+    pub fn release_key(&mut self, key: &KeyCode) {
+        self.keys.remove(key);
+    }
+
+    pub fn press_button(&mut self, id: GamepadId, button: Button) {
+        self.buttons.insert((id, button));
+        self.last_device = Device::Gamepad;
+    }
+
+    pub fn release_button(&mut self, id: GamepadId, button: Button) {
+        self.buttons.remove(&(id, button));
+    }
+
+    /// Only the left stick moves and aims, other axes are ignored. Pushing
+    /// it past `deadzone` counts as using the gamepad.
+    pub fn move_stick(&mut self, id: GamepadId, axis: Axis, value: f32, deadzone: f32) {
+        let stick = self.sticks.entry(id).or_default();
+        match axis {
+            Axis::LeftStickX => stick[0] = value,
+            Axis::LeftStickY => stick[1] = value,
+            _ => return,
+        }
+
+        if stick[0].hypot(stick[1]) >= deadzone {
+            self.last_device = Device::Gamepad;
+        }
+    }
+}
+
+fn parse_bindings<T: Eq + std::hash::Hash>(
+    names: &HashMap<Action, Vec<String>>,
+    parse: fn(&str) -> Option<T>,
+    what: &str,
+) -> Result<HashMap<T, Vec<Action>>> {
+    let mut bindings: HashMap<T, Vec<Action>> = HashMap::new();
+    for (action, names) in names {
+        for name in names {
+            let input =
+                parse(name).ok_or_else(|| anyhow!("Unknown {what} '{name}' bound to {action}"))?;
+            bindings.entry(input).or_default().push(*action);
+        }
+    }
+    Ok(bindings)
+}
+
+/// Gamepad button by its name in gilrs, ignoring case
+fn button(name: &str) -> Option<Button> {
+    let button = match name.to_ascii_lowercase().as_str() {
+        "south" => Button::South,
+        "east" => Button::East,
+        "north" => Button::North,
+        "west" => Button::West,
+        "lefttrigger" => Button::LeftTrigger,
+        "lefttrigger2" => Button::LeftTrigger2,
+        "righttrigger" => Button::RightTrigger,
+        "righttrigger2" => Button::RightTrigger2,
+        "select" => Button::Select,
+        "start" => Button::Start,
+        "leftthumb" => Button::LeftThumb,
+        "rightthumb" => Button::RightThumb,
+        "dpadup" => Button::DPadUp,
+        "dpaddown" => Button::DPadDown,
+        "dpadleft" => Button::DPadLeft,
+        "dpadright" => Button::DPadRight,
+        _ => return None,
+    };
+    Some(button)
+}
+
+/// Key by the name of its `KeyCode`, ignoring case. Digits may leave out
+/// the `Key` prefix.
+fn key_code(name: &str) -> Option<KeyCode> {
+    let key = match name.to_ascii_lowercase().as_str() {
+        "a" => KeyCode::A,
+        "b" => KeyCode::B,
+        "c" => KeyCode::C,
+        "d" => KeyCode::D,
+        "e" => KeyCode::E,
+        "f" => KeyCode::F,
+        "g" => KeyCode::G,
+        "h" => KeyCode::H,
+        "i" => KeyCode::I,
+        "j" => KeyCode::J,
+        "k" => KeyCode::K,
+        "l" => KeyCode::L,
+        "m" => KeyCode::M,
+        "n" => KeyCode::N,
+        "o" => KeyCode::O,
+        "p" => KeyCode::P,
+        "q" => KeyCode::Q,
+        "r" => KeyCode::R,
+        "s" => KeyCode::S,
+        "t" => KeyCode::T,
+        "u" => KeyCode::U,
+        "v" => KeyCode::V,
+        "w" => KeyCode::W,
+        "x" => KeyCode::X,
+        "y" => KeyCode::Y,
+        "z" => KeyCode::Z,
+        "0" | "key0" => KeyCode::Key0,
+        "1" | "key1" => KeyCode::Key1,
+        "2" | "key2" => KeyCode::Key2,
+        "3" | "key3" => KeyCode::Key3,
+        "4" | "key4" => KeyCode::Key4,
+        "5" | "key5" => KeyCode::Key5,
+        "6" | "key6" => KeyCode::Key6,
+        "7" | "key7" => KeyCode::Key7,
+        "8" | "key8" => KeyCode::Key8,
+        "9" | "key9" => KeyCode::Key9,
+        "numpad0" => KeyCode::Numpad0,
+        "numpad1" => KeyCode::Numpad1,
+        "numpad2" => KeyCode::Numpad2,
+        "numpad3" => KeyCode::Numpad3,
+        "numpad4" => KeyCode::Numpad4,
+        "numpad5" => KeyCode::Numpad5,
+        "numpad6" => KeyCode::Numpad6,
+        "numpad7" => KeyCode::Numpad7,
+        "numpad8" => KeyCode::Numpad8,
+        "numpad9" => KeyCode::Numpad9,
+        "f1" => KeyCode::F1,
+        "f2" => KeyCode::F2,
+        "f3" => KeyCode::F3,
+        "f4" => KeyCode::F4,
+        "f5" => KeyCode::F5,
+        "f6" => KeyCode::F6,
+        "f7" => KeyCode::F7,
+        "f8" => KeyCode::F8,
+        "f9" => KeyCode::F9,
+        "f10" => KeyCode::F10,
+        "f11" => KeyCode::F11,
+        "f12" => KeyCode::F12,
+        "space" => KeyCode::Space,
+        "return" | "enter" => KeyCode::Return,
+        "tab" => KeyCode::Tab,
+        "back" | "backspace" => KeyCode::Back,
+        "escape" => KeyCode::Escape,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "lshift" => KeyCode::LShift,
+        "rshift" => KeyCode::RShift,
+        "lcontrol" => KeyCode::LControl,
+        "rcontrol" => KeyCode::RControl,
+        "lalt" => KeyCode::LAlt,
+        "ralt" => KeyCode::RAlt,
+        "comma" => KeyCode::Comma,
+        "period" => KeyCode::Period,
+        "slash" => KeyCode::Slash,
+        "backslash" => KeyCode::Backslash,
+        "semicolon" => KeyCode::Semicolon,
+        "apostrophe" => KeyCode::Apostrophe,
+        "lbracket" => KeyCode::LBracket,
+        "rbracket" => KeyCode::RBracket,
+        "minus" => KeyCode::Minus,
+        "equals" => KeyCode::Equals,
+        "grave" => KeyCode::Grave,
+        _ => return None,
+    };
+    Some(key)
+}
@@ -1,6 +1,6 @@
 use simulation::{
-    Player, PlayerCombat, PlayerCooldowns, PlayerPhysics, PlayerStatus, attack::Attack,
-    game_state::GameState,
+    Player, PlayerCombat, PlayerCooldowns, PlayerPhysics, PlayerStatus, Projectile, attack::Attack,
+    game_state::GameState, map::Map,
 };
 use std::collections::{HashMap, VecDeque};
 use uuid::Uuid;
@@ -71,14 +71,22 @@ impl SnapshotHistory {
         Some((&floor.snapshot, &ceil.snapshot, alpha))
     }
 
-    pub fn get_interpolated(&self, render_tick: f32, c_player: Uuid) -> Option<GameState> {
+    pub fn get_interpolated(
+        &self,
+        render_tick: f32,
+        c_player: Uuid,
+        predicted: Option<Player>,
+    ) -> Option<GameState> {
         let (a, b, alpha) = self.surrounding(render_tick)?;
         let mut gs = interpolate(a, b, alpha);
 
-        // overwrite local player with the latest state
-        let last = &self.buffer.back()?.snapshot;
-        gs.players
-            .insert(c_player, last.players.get(&c_player).unwrap().clone());
+        // overwrite local player with the predicted state, or the latest
+        // state if there is no prediction; spectators have no local player
+        let local =
+            predicted.or_else(|| self.buffer.back()?.snapshot.players.get(&c_player).cloned());
+        if let Some(local) = local {
+            gs.players.insert(c_player, local);
+        }
 
         Some(gs)
     }
@@ -101,21 +109,36 @@ pub fn interpolate(a: &GameState, b: &GameState, alpha: f32) -> GameState {
         );
     }
     GameState {
-        winner: a.winner,
-        map: a.map.clone(),
+        outcome: a.outcome,
+        map: interpolate_map(&a.map, &b.map, alpha),
         players,
         teams: a.teams.clone(),
+        balance: a.balance.clone(),
+        projectiles: interpolate_projectiles(&a.projectiles, &b.projectiles, alpha),
+        next_projectile_id: a.next_projectile_id,
         post_game_timer: a.post_game_timer,
+        events: Vec::new(),
     }
 }
 
+fn interpolate_map(a: &Map, b: &Map, alpha: f32) -> Map {
+    let mut map = a.clone();
+    for (pa, pb) in map.platforms_mut().iter_mut().zip(b.platforms()) {
+        pa.rect.x = lerp(pa.rect.x, pb.rect.x, alpha);
+        pa.rect.y = lerp(pa.rect.y, pb.rect.y, alpha);
+    }
+    map
+}
+
 fn interpolate_player(a: &Player, b: &Player, alpha: f32) -> Player {
     Player {
+        character: a.character,
         combat: interpolate_combat(&a.combat, &b.combat, alpha),
         cooldowns: interpolate_cooldowns(&a.cooldowns, &b.cooldowns, alpha),
         physics: interpolate_physics(&a.physics, &b.physics, alpha),
         status: interpolate_status(&a.status, &b.status, alpha),
         input: a.input.clone(),
+        stats: a.stats,
     }
 }
 
@@ -126,6 +149,7 @@ fn interpolate_combat(a: &PlayerCombat, b: &PlayerCombat, alpha: f32) -> PlayerC
         combo_timer: lerp(a.combo_timer, b.combo_timer, alpha),
         knockback_multiplier: a.knockback_multiplier,
         attacks: interpolate_attacks(&a.attacks, &b.attacks, alpha),
+        fired: Vec::new(),
     }
 }
 
@@ -145,8 +169,12 @@ fn interpolate_physics(a: &PlayerPhysics, b: &PlayerPhysics, alpha: f32) -> Play
         vel: a.vel,
         facing: a.facing,
         team_idx: a.team_idx,
+        movement: a.movement,
         double_jumps: a.double_jumps,
         has_jumped: a.has_jumped,
+        has_pressed_down: a.has_pressed_down,
+        drop_through: a.drop_through,
+        ledge: a.ledge,
     }
 }
 
@@ -157,6 +185,10 @@ fn interpolate_status(a: &PlayerStatus, b: &PlayerStatus, alpha: f32) -> PlayerS
         invulnerable_timer: lerp(a.invulnerable_timer, b.invulnerable_timer, alpha),
         parry: lerp(a.parry, b.parry, alpha),
         can_slam: a.can_slam,
+        disconnected: b.disconnected,
+        hang: lerp(a.hang, b.hang, alpha),
+        ledge_cooldown: lerp(a.ledge_cooldown, b.ledge_cooldown, alpha),
+        ledge_intangibility: a.ledge_intangibility,
     }
 }
 
@@ -171,3 +203,17 @@ fn interpolate_attacks(a: &[Attack], b: &[Attack], alpha: f32) -> Vec<Attack> {
         })
         .collect()
 }
+
+fn interpolate_projectiles(a: &[Projectile], b: &[Projectile], alpha: f32) -> Vec<Projectile> {
+    a.iter()
+        .map(|pa| match b.iter().find(|pb| pb.id == pa.id) {
+            Some(pb) => Projectile {
+                pos: pa.pos.lerp(pb.pos, alpha),
+                timer: lerp(pa.timer, pb.timer, alpha),
+                ..pa.clone()
+            },
+            // gone by the next snapshot, keep it where it was last seen
+            None => pa.clone(),
+        })
+        .collect()
+}
@@ -1,10 +1,14 @@
 mod constants;
+pub mod input;
 pub mod interpolation;
 mod network_client;
+pub mod prediction;
 pub mod render_clock;
+pub mod replay;
 mod runtime;
 mod session;
 pub use network_client::NetworkClient;
+pub use network_client::load_init_data;
 pub use runtime::ClientEvent;
 pub use runtime::ClientState;
 pub use session::GameSession;
@@ -1,19 +1,63 @@
-use crate::{ClientState, runtime::ClientEvent};
-use anyhow::Result;
+use crate::{
+    ClientState,
+    constants::{HANDSHAKE_TIMEOUT, RECONNECT_AFTER},
+    runtime::ClientEvent,
+};
+use anyhow::{Result, anyhow};
 use foundation::GameMode;
-use protocol::{net_client::ClientMessage, net_game_state, net_server::ServerMessage};
-use simulation::PlayerInput;
+use game_config::read::load_map;
+use protocol::{
+    PROTOCOL_VERSION,
+    delta::SnapshotBaselines,
+    init::{InitData, NetInitData},
+    net_client::{ClientMessage, HEARTBEAT_INTERVAL, Hello},
+    net_game_state,
+    net_server::ServerMessage,
+    reliable::{RESEND_INTERVAL, ReliableChannel},
+};
+use simulation::Character;
+use simulation::constants::{FIXED_DT, TICK_RATE};
 use std::{
+    collections::VecDeque,
     net::SocketAddr,
     sync::{Arc, atomic::Ordering},
+    time::Duration,
 };
-use tokio::net::UdpSocket;
+use tokio::{net::UdpSocket, sync::Mutex};
 use wincode::{deserialize, serialize};
 
 #[derive(Clone)]
 pub struct NetworkClient {
     socket: Arc<UdpSocket>,
-    server_addr: SocketAddr,
+    channel: Arc<Mutex<ReliableChannel>>,
+    // messages received but not handled yet
+    inbox: Arc<Mutex<VecDeque<ServerMessage>>>,
+    session: Arc<Mutex<Session>>,
+}
+
+/// What is needed to resume the session after losing connection
+#[derive(Default)]
+struct Session {
+    player_name: String,
+    character: Character,
+    token: Option<String>,
+    account_token: Option<String>,
+}
+
+pub struct Welcome {
+    pub session_token: String,
+    pub account_token: String,
+    // the server resends the match right after
+    pub in_game: bool,
+    pub server_name: String,
+    pub modes: Vec<GameMode>,
+}
+
+/// Sets up a match the server sent with the map from our own assets
+pub fn load_init_data(init_data: NetInitData) -> Result<InitData> {
+    let map = load_map(&init_data.map_file, init_data.mode())?;
+
+    init_data.into_init_data(map)
 }
 
 impl NetworkClient {
@@ -36,133 +80,405 @@ impl NetworkClient {
             .await
             .expect("Fatal: Unable to connect to server");
 
-        Self {
+        let network = Self {
             socket,
-            server_addr,
-        }
+            channel: Arc::new(Mutex::new(ReliableChannel::open())),
+            inbox: Arc::new(Mutex::new(VecDeque::new())),
+            session: Arc::new(Mutex::new(Session::default())),
+        };
+        network.spawn_resend_task();
+        network.spawn_heartbeat_task();
+
+        network
     }
 
-    pub async fn handshake(&self, player_name: &str) -> Result<()> {
-        let packet = serialize(&ClientMessage::Hello {
+    /// Introduces us to the server, resuming the session
+    /// `session_token` belongs to if the server still has it
+    /// and logging into the account of `account_token`.
+    /// Fails with the reason if the server turns us away.
+    pub async fn handshake(
+        &self,
+        player_name: &str,
+        character: Character,
+        session_token: Option<String>,
+        account_token: Option<String>,
+    ) -> Result<Welcome> {
+        *self.session.lock().await = Session {
             player_name: player_name.to_string(),
-        })?;
-        self.socket.send(&packet).await?;
+            character,
+            token: session_token,
+            account_token,
+        };
+        self.send_hello().await?;
 
-        Ok(())
+        let welcome = async {
+            loop {
+                match self.recv().await? {
+                    ServerMessage::Welcome {
+                        session_token,
+                        account_token,
+                        in_game,
+                        server_name,
+                        tick_rate,
+                        modes,
+                        ..
+                    } => {
+                        if tick_rate as usize != TICK_RATE {
+                            return Err(anyhow!(
+                                "The server runs at {tick_rate} ticks per second, expected {TICK_RATE}"
+                            ));
+                        }
+
+                        return Ok(Welcome {
+                            session_token,
+                            account_token,
+                            in_game,
+                            server_name,
+                            modes,
+                        });
+                    }
+                    ServerMessage::Rejected(reason) => return Err(anyhow!("{reason}")),
+                    _ => {}
+                }
+            }
+        };
+
+        tokio::time::timeout(Duration::from_secs_f32(HANDSHAKE_TIMEOUT), welcome)
+            .await
+            .map_err(|_| anyhow!("No response from server"))?
     }
 
-    pub async fn leave_queue(&self) -> Result<()> {
-        self.socket
-            .send(&serialize(&ClientMessage::QueueLeave)?)
-            .await?;
+    /// Starts over on a new connection and asks to resume our session
+    pub async fn reconnect(&self) -> Result<()> {
+        *self.channel.lock().await = ReliableChannel::open();
+        self.send_hello().await
+    }
 
-        Ok(())
+    async fn send_hello(&self) -> Result<()> {
+        let hello = {
+            let session = self.session.lock().await;
+            ClientMessage::Hello(Hello {
+                protocol_version: PROTOCOL_VERSION,
+                build: env!("CARGO_PKG_VERSION").to_string(),
+                player_name: session.player_name.clone(),
+                character: session.character,
+                session_token: session.token.clone(),
+                account_token: session.account_token.clone(),
+            })
+        };
+
+        self.send_reliable(&hello).await
+    }
+
+    pub async fn leave_queue(&self) -> Result<()> {
+        self.send_reliable(&ClientMessage::QueueLeave).await
     }
 
     pub async fn enter_queue(&self, mode: GameMode) -> Result<()> {
-        self.socket
-            .send(&serialize(&ClientMessage::QueueJoin(mode))?)
-            .await?;
+        self.send_reliable(&ClientMessage::QueueJoin(mode)).await
+    }
+
+    pub async fn invite(&self, player_name: &str) -> Result<()> {
+        self.send_reliable(&ClientMessage::PartyInvite(player_name.to_string()))
+            .await
+    }
+
+    pub async fn accept_invite(&self) -> Result<()> {
+        self.send_reliable(&ClientMessage::PartyAccept).await
+    }
+
+    pub async fn decline_invite(&self) -> Result<()> {
+        self.send_reliable(&ClientMessage::PartyDecline).await
+    }
+
+    pub async fn leave_party(&self) -> Result<()> {
+        self.send_reliable(&ClientMessage::PartyLeave).await
+    }
+
+    pub async fn create_lobby(&self, mode: GameMode) -> Result<()> {
+        self.send_reliable(&ClientMessage::LobbyCreate(mode)).await
+    }
+
+    pub async fn join_lobby(&self, code: &str) -> Result<()> {
+        self.send_reliable(&ClientMessage::LobbyJoin(code.to_string()))
+            .await
+    }
+
+    /// Also kept for the hello when reconnecting
+    pub async fn pick_character(&self, character: Character) -> Result<()> {
+        self.session.lock().await.character = character;
+        self.send_reliable(&ClientMessage::PickCharacter(character))
+            .await
+    }
+
+    pub async fn pick_team(&self, team: u8) -> Result<()> {
+        self.send_reliable(&ClientMessage::LobbyPickTeam(team))
+            .await
+    }
+
+    pub async fn leave_lobby(&self) -> Result<()> {
+        self.send_reliable(&ClientMessage::LobbyLeave).await
+    }
+
+    pub async fn start_lobby(&self) -> Result<()> {
+        self.send_reliable(&ClientMessage::LobbyStart).await
+    }
+
+    pub async fn request_history(&self) -> Result<()> {
+        self.send_reliable(&ClientMessage::MatchHistory).await
+    }
+
+    pub async fn list_games(&self) -> Result<()> {
+        self.send_reliable(&ClientMessage::ListGames).await
+    }
+
+    pub async fn spectate(&self, game_id: &str) -> Result<()> {
+        self.send_reliable(&ClientMessage::Spectate(game_id.to_string()))
+            .await
+    }
+
+    pub async fn stop_spectating(&self) -> Result<()> {
+        self.send_reliable(&ClientMessage::StopSpectating).await
+    }
+
+    /// Tells the server we are leaving. Sent once and unreliably since there
+    /// is nobody left to resend it, the server times us out otherwise.
+    pub fn disconnect(&self) -> Result<()> {
+        let channel = self.channel.try_lock()?;
+        let packet = channel.unreliable(serialize(&ClientMessage::Disconnect)?)?;
+        self.socket.try_send(&packet)?;
 
         Ok(())
     }
 
     pub async fn poll_queue(&self) -> Result<ServerMessage> {
+        self.recv().await
+    }
+
+    async fn send_reliable(&self, msg: &ClientMessage) -> Result<()> {
+        let packet = self.channel.lock().await.send(serialize(msg)?)?;
+        self.socket.send(&packet).await?;
+
+        Ok(())
+    }
+
+    async fn send_unreliable(&self, msg: &ClientMessage) -> Result<()> {
+        let packet = self.channel.lock().await.unreliable(serialize(msg)?)?;
+        self.socket.send(&packet).await?;
+
+        Ok(())
+    }
+
+    /// Next message from the server, acking whatever needs it.
+    /// Delivered messages wait in the inbox, so none are lost
+    /// when this is cancelled, e.g. by a timeout.
+    async fn recv(&self) -> Result<ServerMessage> {
         let mut buf = [0u8; 2048];
-        match self.socket.recv(&mut buf).await {
-            Ok(n) => Ok(deserialize(&buf[..n])?),
-            Err(e) => Err(anyhow::Error::from(e)),
+
+        loop {
+            let msg = self.inbox.lock().await.pop_front();
+            if let Some(msg) = msg {
+                // the server may have started a new session when we reconnected
+                if let ServerMessage::Welcome {
+                    session_token,
+                    account_token,
+                    ..
+                } = &msg
+                {
+                    let mut session = self.session.lock().await;
+                    session.token = Some(session_token.clone());
+                    session.account_token = Some(account_token.clone());
+                }
+
+                return Ok(msg);
+            }
+
+            let n = self.socket.recv(&mut buf).await?;
+            let ack = {
+                let mut channel = self.channel.lock().await;
+                let mut inbox = self.inbox.lock().await;
+                let received = match channel.receive(&buf[..n]) {
+                    Ok(received) => received,
+                    Err(e) => {
+                        eprintln!("Skipping malformed datagram: {e}");
+                        continue;
+                    }
+                };
+
+                for payload in received.payloads {
+                    match deserialize(&payload) {
+                        Ok(msg) => inbox.push_back(msg),
+                        Err(e) => eprintln!("Skipping malformed message: {e}"),
+                    }
+                }
+                received.ack
+            };
+
+            // a lost ack only means the server resends
+            if let Some(ack) = ack {
+                self.socket.send(&ack).await?;
+            }
         }
     }
 
+    fn spawn_resend_task(&self) {
+        let network = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(RESEND_INTERVAL / 2).await;
+
+                let resends = network.channel.lock().await.resends();
+                match resends {
+                    Ok(packets) => {
+                        for packet in packets {
+                            let _ = network.socket.send(&packet).await;
+                        }
+                    }
+                    // the server stopped answering, start over on a new connection
+                    Err(e) => {
+                        eprintln!("Lost connection: {e}");
+                        if let Err(e) = network.reconnect().await {
+                            eprintln!("Failed to reconnect: {e}");
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    fn spawn_heartbeat_task(&self) {
+        let network = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(HEARTBEAT_INTERVAL).await;
+
+                let _ = network.send_unreliable(&ClientMessage::Heartbeat).await;
+            }
+        });
+    }
+
     pub fn spawn_receive_task(&self, client: Arc<ClientState>) {
-        let socket = Arc::clone(&self.socket);
+        let network = self.clone();
         tokio::spawn(async move {
-            let mut buf = [0u8; 2048];
+            let mut baselines = SnapshotBaselines::default();
+            let reconnect_after = Duration::from_secs_f32(RECONNECT_AFTER);
 
             loop {
-                match socket.recv_from(&mut buf).await {
-                    Ok((len, _)) => {
-                        match deserialize::<ServerMessage>(&buf[..len]) {
-                            Ok(ServerMessage::Snapshot {
-                                server_tick,
-                                server_state,
-                            }) => {
-                                let mut snapshot_history = client.snapshot_history.lock().await;
-
-                                {
-                                    // update render clock
-                                    let mut render_clock_locked = client.render_clock.lock().await;
-                                    render_clock_locked.update(server_tick);
-
-                                    let mut render_tick = client.render_tick.lock().await;
-                                    *render_tick = render_clock_locked.render_tick();
-                                }
-
-                                // apply server snapshot
-                                let mut core = client.core.lock().await;
-                                net_game_state::apply_snapshot(
-                                    core.game_state_mut(),
-                                    &server_state,
-                                );
-
-                                snapshot_history.push(server_tick, core.game_state().clone());
-                            }
-                            Ok(ServerMessage::EndGame) => {
-                                let _ = client.event_tx.send(Some(ClientEvent::EndGame));
-                                client.shutdown.store(true, Ordering::Relaxed);
-                                return;
-                            }
-                            Ok(_) => {
-                                // ignore other message types
-                            }
-                            Err(e) => {
-                                eprintln!("Decode error: {e}");
-                            }
+                let msg = match tokio::time::timeout(reconnect_after, network.recv()).await {
+                    Ok(Ok(msg)) => msg,
+                    Ok(Err(e)) => {
+                        eprintln!("Receive error: {e}");
+                        continue;
+                    }
+                    Err(_) => {
+                        // nothing from the server for a while
+                        if let Err(e) = network.reconnect().await {
+                            eprintln!("Failed to reconnect: {e}");
                         }
+                        continue;
                     }
+                };
+
+                match msg {
+                    ServerMessage::Snapshot {
+                        server_tick,
+                        ack_tick,
+                        server_state,
+                    } => {
+                        // a delta on a baseline we no longer have cannot be used
+                        let Some(server_state) = baselines.decode(server_tick, &server_state)
+                        else {
+                            continue;
+                        };
+                        baselines.push(server_state.clone());
+                        *client.snapshot_ack.lock().await = Some(server_tick);
+
+                        let mut snapshot_history = client.snapshot_history.lock().await;
+
+                        {
+                            // update render clock
+                            let mut render_clock_locked = client.render_clock.lock().await;
+                            render_clock_locked.update(server_tick);
 
-                    Err(e) => eprintln!("Receive error: {e}"),
+                            let mut render_tick = client.render_tick.lock().await;
+                            *render_tick = render_clock_locked.render_tick();
+                        }
+
+                        // apply server snapshot
+                        let mut core = client.core.lock().await;
+                        net_game_state::apply_snapshot(
+                            core.game_state_mut(),
+                            &server_state,
+                            &client.slots,
+                        );
+
+                        snapshot_history.push(server_tick, core.game_state().clone());
+
+                        // replay inputs the server has not seen yet
+                        let mut input_buffer = client.input_buffer.lock().await;
+                        for input in input_buffer.acknowledge(ack_tick) {
+                            core.predict(&client.player_id, input.clone(), FIXED_DT);
+                        }
+                    }
+                    // we rejoined the match after reconnecting
+                    ServerMessage::StartGame { init_data, .. } => {
+                        baselines = SnapshotBaselines::default();
+                        let restarted = match load_init_data(init_data) {
+                            Ok(init_data) => client.restart(&init_data).await,
+                            Err(e) => Err(e),
+                        };
+                        if let Err(e) = restarted {
+                            eprintln!("Unable to rejoin the match: {e}");
+                        }
+                    }
+                    ServerMessage::EndGame => {
+                        let _ = client.event_tx.send(Some(ClientEvent::EndGame));
+                        client.shutdown.store(true, Ordering::Relaxed);
+                        return;
+                    }
+                    _ => {
+                        // ignore other message types
+                    }
                 }
             }
         });
     }
 
     pub fn spawn_send_task(&self, client: Arc<ClientState>) {
-        let socket = Arc::clone(&self.socket);
-        let server_addr = self.server_addr;
+        let network = self.clone();
         tokio::spawn(async move {
-            let tick_duration =
-                std::time::Duration::from_millis(1000 / simulation::constants::TICK_RATE as u64);
+            // deadline based, so inputs are made exactly as fast as the server simulates them
+            let mut ticks = tokio::time::interval(Duration::from_secs_f32(FIXED_DT));
 
             loop {
+                ticks.tick().await;
                 if client.shutdown.load(Ordering::Relaxed) {
                     return;
                 }
 
-                let start = std::time::Instant::now();
-                let tick = client.tick.load(Ordering::Relaxed);
+                let tick = client.tick.fetch_add(1, Ordering::Relaxed);
+                let view_tick = client.render_tick.lock().await.max(0.0) as u64;
+                let snapshot_ack = *client.snapshot_ack.lock().await;
 
                 // collect input
-                let pressed = client.current_input.lock().await.clone();
-                let mut input = PlayerInput::default();
-                input.update(&pressed);
+                let input = client.current_input.lock().await.clone();
+
+                // predict local player
+                {
+                    let mut core = client.core.lock().await;
+                    core.predict(&client.player_id, input.clone(), FIXED_DT);
+                    client.input_buffer.lock().await.push(tick, input.clone());
+                }
 
                 let msg = ClientMessage::Input {
                     client_tick: tick,
+                    view_tick,
+                    snapshot_ack,
                     input,
                 };
 
-                match serialize(&msg) {
-                    Ok(data) => {
-                        let _ = socket.send_to(&data, server_addr).await;
-                    }
-                    Err(e) => eprintln!("Encoding error: {e}"),
-                }
-
-                let elapsed = start.elapsed();
-                if elapsed < tick_duration {
-                    tokio::time::sleep(tick_duration - elapsed).await;
+                if let Err(e) = network.send_unreliable(&msg).await {
+                    eprintln!("Send error: {e}");
                 }
             }
         });
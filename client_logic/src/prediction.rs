@@ -0,0 +1,43 @@
+use simulation::PlayerInput;
+use std::collections::VecDeque;
+
+const INPUT_BUFFER_SIZE: usize = 256;
+
+/// Inputs sent to the server that are not yet
+/// reflected in an authoritative snapshot
+pub struct InputBuffer {
+    inputs: VecDeque<(u64, PlayerInput)>,
+    capacity: usize,
+}
+
+impl Default for InputBuffer {
+    fn default() -> Self {
+        Self {
+            inputs: VecDeque::new(),
+            capacity: INPUT_BUFFER_SIZE,
+        }
+    }
+}
+
+impl InputBuffer {
+    pub fn push(&mut self, client_tick: u64, input: PlayerInput) {
+        if self.inputs.len() == self.capacity {
+            self.inputs.pop_front();
+        }
+        self.inputs.push_back((client_tick, input));
+    }
+
+    /// Drops every input the server has processed and
+    /// returns the remaining ones in order
+    pub fn acknowledge(&mut self, ack_tick: u64) -> impl Iterator<Item = &PlayerInput> {
+        while self
+            .inputs
+            .front()
+            .is_some_and(|(tick, _)| *tick <= ack_tick)
+        {
+            self.inputs.pop_front();
+        }
+
+        self.inputs.iter().map(|(_, input)| input)
+    }
+}
@@ -0,0 +1,173 @@
+use anyhow::Result;
+use protocol::init::PlayerSlots;
+use protocol::replay::{Replay, ReplayEvent};
+use simulation::constants::FIXED_DT;
+use simulation::game_state::GameState;
+use simulation::lag_compensation::StateHistory;
+use simulation::simulation::SimulationCore;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Ticks between the states kept for seeking backwards
+const KEYFRAME_INTERVAL: u64 = 300;
+
+/// Plays a replay back by re-simulating the recorded inputs
+pub struct ReplayPlayer {
+    replay: Replay,
+    slots: PlayerSlots,
+    core: SimulationCore,
+    history: StateHistory,
+    // next tick to step
+    tick: u64,
+    keyframes: Vec<Keyframe>,
+    // first tick that did not match its recorded checksum
+    desync: Option<u64>,
+}
+
+struct Keyframe {
+    tick: u64,
+    game_state: GameState,
+    history: StateHistory,
+}
+
+impl ReplayPlayer {
+    /// Fails when the recorded match cannot be set up again
+    pub fn new(replay: Replay) -> Result<Self> {
+        let game_state = replay.init_data.to_game_state()?;
+        let history = StateHistory::new(replay.max_rewind_ticks);
+
+        Ok(Self {
+            slots: replay.init_data.player_slots()?,
+            keyframes: vec![Keyframe {
+                tick: 0,
+                game_state: game_state.clone(),
+                history: history.clone(),
+            }],
+            core: SimulationCore::new(game_state),
+            history,
+            tick: 0,
+            desync: None,
+            replay,
+        })
+    }
+
+    /// Steps one tick, false once the replay is over
+    pub fn step(&mut self) -> bool {
+        let Some(recorded) = self.replay.ticks.get(self.tick as usize) else {
+            return false;
+        };
+
+        let gs = self.core.game_state_mut();
+        for event in &recorded.events {
+            match *event {
+                ReplayEvent::Disconnected(slot) => {
+                    if let Some(id) = self.slots.id(slot) {
+                        gs.set_disconnected(&id, true);
+                    }
+                }
+                ReplayEvent::Reconnected(slot) => {
+                    if let Some(id) = self.slots.id(slot) {
+                        gs.set_disconnected(&id, false);
+                    }
+                }
+                ReplayEvent::Forfeited(slot) => {
+                    if let Some(id) = self.slots.id(slot) {
+                        gs.forfeit_player(&id);
+                    }
+                }
+            }
+        }
+        for (slot, input) in &recorded.inputs {
+            if let Some(id) = self.slots.id(*slot) {
+                gs.apply_input(&id, input.clone());
+            }
+        }
+
+        let views: HashMap<Uuid, &GameState> = recorded
+            .views
+            .iter()
+            .filter_map(|(slot, view_tick)| {
+                Some((
+                    self.slots.id(*slot)?,
+                    self.history.view(*view_tick, self.tick)?,
+                ))
+            })
+            .collect();
+        self.core.step_with_views(FIXED_DT, &views);
+        self.history.push(self.tick, self.core.game_state());
+
+        if self.desync.is_none()
+            && self
+                .replay
+                .checksum(self.tick)
+                .is_some_and(|checksum| checksum != self.core.game_state().checksum())
+        {
+            self.desync = Some(self.tick);
+        }
+
+        self.tick += 1;
+        if self.tick.is_multiple_of(KEYFRAME_INTERVAL)
+            && self.keyframes.last().is_some_and(|k| k.tick < self.tick)
+        {
+            self.keyframes.push(Keyframe {
+                tick: self.tick,
+                game_state: self.core.game_state().clone(),
+                history: self.history.clone(),
+            });
+        }
+
+        true
+    }
+
+    /// Jumps to `tick`, going back to the closest keyframe
+    /// and simulating forward from there
+    pub fn seek(&mut self, tick: u64) {
+        let tick = tick.min(self.len());
+
+        if tick < self.tick {
+            let keyframe = self
+                .keyframes
+                .iter()
+                .rev()
+                .find(|k| k.tick <= tick)
+                .unwrap_or(&self.keyframes[0]);
+            self.tick = keyframe.tick;
+            self.history = keyframe.history.clone();
+            *self.core.game_state_mut() = keyframe.game_state.clone();
+        }
+
+        while self.tick < tick && self.step() {}
+    }
+
+    #[must_use]
+    pub fn game_state(&self) -> &GameState {
+        self.core.game_state()
+    }
+
+    #[must_use]
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// Ticks stepped so far
+    #[must_use]
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Number of ticks in the replay
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        self.replay.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.replay.is_empty()
+    }
+
+    #[must_use]
+    pub fn desync(&self) -> Option<u64> {
+        self.desync
+    }
+}
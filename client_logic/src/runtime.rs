@@ -1,14 +1,14 @@
 use anyhow::Result;
-use ggez::input::keyboard::KeyCode;
-use protocol::init::InitData;
-use std::collections::HashSet;
+use protocol::init::{InitData, PlayerSlots};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, AtomicU64};
 use tokio::sync::{Mutex, watch};
 use uuid::Uuid;
 
 use crate::interpolation::SnapshotHistory;
+use crate::prediction::InputBuffer;
 use crate::render_clock::RenderClock;
+use simulation::PlayerInput;
 use simulation::simulation::SimulationCore;
 
 #[derive(Clone)]
@@ -18,33 +18,57 @@ pub enum ClientEvent {
 
 pub struct ClientState {
     pub player_id: Uuid,
+    pub slots: PlayerSlots,
     pub event_tx: watch::Sender<Option<ClientEvent>>,
     pub event_rx: watch::Receiver<Option<ClientEvent>>,
-    pub current_input: Arc<Mutex<HashSet<KeyCode>>>,
+    pub current_input: Arc<Mutex<PlayerInput>>,
     pub snapshot_history: Arc<Mutex<SnapshotHistory>>,
     pub render_clock: Arc<Mutex<RenderClock>>,
     pub render_tick: Arc<Mutex<f32>>,
+    pub snapshot_ack: Arc<Mutex<Option<u64>>>,
+    // predicted state; always lock before `input_buffer`
     pub core: Arc<Mutex<SimulationCore>>,
+    pub input_buffer: Arc<Mutex<InputBuffer>>,
     pub tick: Arc<AtomicU64>,
     pub shutdown: Arc<AtomicBool>,
 }
 
 impl ClientState {
     pub fn new(player_id: Uuid, init_data: InitData) -> Result<Self> {
-        let gs = init_data.to_game_state();
+        let gs = init_data.to_game_state()?;
         let (event_tx, event_rx) = watch::channel(None);
 
         Ok(Self {
             player_id,
+            slots: init_data.player_slots()?,
             event_tx,
             event_rx,
-            current_input: Arc::new(Mutex::new(HashSet::new())),
+            current_input: Arc::new(Mutex::new(PlayerInput::default())),
             snapshot_history: Arc::new(Mutex::new(SnapshotHistory::default())),
             render_clock: Arc::new(Mutex::new(RenderClock::default())),
             render_tick: Arc::new(Mutex::new(0.0)),
+            snapshot_ack: Arc::new(Mutex::new(None)),
             core: Arc::new(Mutex::new(SimulationCore::new(gs))),
+            input_buffer: Arc::new(Mutex::new(InputBuffer::default())),
             tick: Arc::new(AtomicU64::new(0)),
             shutdown: Arc::new(AtomicBool::new(false)),
         })
     }
+
+    /// Starts over from the match the server resent after we rejoined,
+    /// its snapshots bring us up to date
+    pub async fn restart(&self, init_data: &InitData) -> Result<()> {
+        let gs = init_data.to_game_state()?;
+        {
+            let mut core = self.core.lock().await;
+            *core = SimulationCore::new(gs);
+            *self.input_buffer.lock().await = InputBuffer::default();
+        }
+        *self.snapshot_history.lock().await = SnapshotHistory::default();
+        *self.render_clock.lock().await = RenderClock::default();
+        *self.render_tick.lock().await = 0.0;
+        *self.snapshot_ack.lock().await = None;
+
+        Ok(())
+    }
 }
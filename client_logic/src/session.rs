@@ -1,44 +1,74 @@
+use crate::input::{Bindings, HeldInput};
 use crate::interpolation::SnapshotHistory;
 use display::render::RenderState;
+use ggez::event::{Axis, Button, GamepadId};
 use ggez::input::keyboard::KeyCode;
-use std::collections::HashSet;
+use simulation::PlayerInput;
+use simulation::simulation::SimulationCore;
 use std::sync::Arc;
 use tokio::sync::{Mutex, mpsc::UnboundedSender};
 use uuid::Uuid;
 
 pub struct GameSession {
     pub c_player: Uuid,
-    pub input_tx: UnboundedSender<HashSet<KeyCode>>,
-    pub input_state: HashSet<KeyCode>,
+    pub input_tx: UnboundedSender<PlayerInput>,
+    pub bindings: Bindings,
+    // keys and buttons held down, turned into actions by `bindings`
+    pub held: HeldInput,
     pub snapshot_history: Arc<Mutex<SnapshotHistory>>,
     pub render_tick: Arc<Mutex<f32>>,
+    pub core: Arc<Mutex<SimulationCore>>,
     pub render_state: RenderState,
 }
 
 impl GameSession {
     pub fn new(
         c_player: Uuid,
-        input_tx: UnboundedSender<HashSet<KeyCode>>,
+        input_tx: UnboundedSender<PlayerInput>,
+        bindings: Bindings,
         snapshot_history: Arc<Mutex<SnapshotHistory>>,
         render_tick: Arc<Mutex<f32>>,
+        core: Arc<Mutex<SimulationCore>>,
         render_state: RenderState,
     ) -> Self {
         Self {
             c_player,
             input_tx,
-            input_state: HashSet::new(),
+            bindings,
+            held: HeldInput::default(),
             snapshot_history,
             render_tick,
+            core,
             render_state,
         }
     }
     pub fn press(&mut self, keycode: KeyCode) {
-        self.input_state.insert(keycode);
-        let _ = self.input_tx.send(self.input_state.clone());
+        self.held.press_key(keycode);
+        self.send_input();
     }
 
     pub fn release(&mut self, keycode: &KeyCode) {
-        self.input_state.remove(keycode);
-        let _ = self.input_tx.send(self.input_state.clone());
+        self.held.release_key(keycode);
+        self.send_input();
+    }
+
+    pub fn press_button(&mut self, id: GamepadId, button: Button) {
+        self.held.press_button(id, button);
+        self.send_input();
+    }
+
+    pub fn release_button(&mut self, id: GamepadId, button: Button) {
+        self.held.release_button(id, button);
+        self.send_input();
+    }
+
+    pub fn move_stick(&mut self, id: GamepadId, axis: Axis, value: f32) {
+        self.held
+            .move_stick(id, axis, value, self.bindings.deadzone());
+        self.send_input();
+    }
+
+    fn send_input(&self) {
+        let _ = self.input_tx.send(self.bindings.input(&self.held));
     }
 }
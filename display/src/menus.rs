@@ -1,8 +1,12 @@
+use foundation::GameMode;
 use ggez::graphics::{
     Canvas, Color as GgezColor, DrawParam, Drawable, PxScale, Text, TextFragment,
 };
 use ggez::{Context, GameResult};
 use glam::Vec2;
+use protocol::history::MatchRecord;
+use protocol::net_server::{NetGame, NetLobby, NetParty};
+use simulation::Character;
 
 fn draw_centered_text(
     game_canvas: &mut Canvas,
@@ -30,20 +34,375 @@ fn draw_centered_text(
     Ok(())
 }
 
-pub fn draw_menu(ctx: &mut Context) -> GameResult {
+pub fn draw_menu(
+    ctx: &mut Context,
+    server_name: &str,
+    modes: &[GameMode],
+    character: Character,
+    party: Option<&NetParty>,
+    invite: Option<&str>,
+    notice: Option<&str>,
+) -> GameResult {
     let mut canvas = Canvas::from_frame(&ctx.gfx, GgezColor::BLACK);
 
-    draw_centered_text(&mut canvas, ctx, "Main Menu", 64.0, -40.0)?;
-    draw_centered_text(&mut canvas, ctx, "Press R to queue", 28.0, 40.0)?;
+    draw_centered_text(&mut canvas, ctx, server_name, 64.0, -160.0)?;
+
+    let mut y = -80.0;
+    for (i, mode) in modes.iter().enumerate() {
+        let text = format!(
+            "Press {} to queue {mode}, Shift+{} for a private lobby",
+            i + 1,
+            i + 1
+        );
+        draw_centered_text(&mut canvas, ctx, &text, 28.0, y)?;
+        y += 36.0;
+    }
+    draw_centered_text(
+        &mut canvas,
+        ctx,
+        "Press Enter to join a private lobby",
+        28.0,
+        y,
+    )?;
+    y += 36.0;
+    draw_centered_text(
+        &mut canvas,
+        ctx,
+        "Press Tab to invite a player to your party",
+        28.0,
+        y,
+    )?;
+    y += 36.0;
+    draw_centered_text(
+        &mut canvas,
+        ctx,
+        "Press H to see your recent matches",
+        28.0,
+        y,
+    )?;
+    y += 36.0;
+    draw_centered_text(&mut canvas, ctx, "Press G to watch a match", 28.0, y)?;
+    y += 36.0;
+    let text = format!("Playing as {character}, press C to change");
+    draw_centered_text(&mut canvas, ctx, &text, 28.0, y)?;
+    y += 72.0;
+
+    if let Some(party) = party {
+        let text = format!(
+            "Party: {} (led by {})",
+            party.members.join(", "),
+            party.leader
+        );
+        draw_centered_text(&mut canvas, ctx, &text, 24.0, y)?;
+        y += 32.0;
+        draw_centered_text(&mut canvas, ctx, "Press X to leave the party", 24.0, y)?;
+        y += 48.0;
+    }
+    if let Some(from) = invite {
+        let text = format!("{from} invited you to their party, press Y to accept or N to decline");
+        draw_centered_text(&mut canvas, ctx, &text, 24.0, y)?;
+        y += 48.0;
+    }
+    if let Some(notice) = notice {
+        draw_centered_text(&mut canvas, ctx, notice, 24.0, y)?;
+    }
 
     canvas.finish(&mut ctx.gfx)
 }
 
-pub fn draw_queue(ctx: &mut Context) -> GameResult {
+pub fn draw_rejected(ctx: &mut Context, reason: &str) -> GameResult {
+    let mut canvas = Canvas::from_frame(&ctx.gfx, GgezColor::BLACK);
+
+    draw_centered_text(&mut canvas, ctx, "Unable to join server", 48.0, -40.0)?;
+    draw_centered_text(&mut canvas, ctx, reason, 28.0, 20.0)?;
+    draw_centered_text(&mut canvas, ctx, "Press Q to quit", 24.0, 80.0)?;
+
+    canvas.finish(&mut ctx.gfx)
+}
+
+pub fn draw_queue(ctx: &mut Context, character: Character) -> GameResult {
     let mut canvas = Canvas::from_frame(&ctx.gfx, GgezColor::BLACK);
 
     draw_centered_text(&mut canvas, ctx, "Queuing...", 48.0, -20.0)?;
-    draw_centered_text(&mut canvas, ctx, "Press Esc to cancel", 24.0, 40.0)?;
+    let text = format!("Playing as {character}, press C to change");
+    draw_centered_text(&mut canvas, ctx, &text, 24.0, 40.0)?;
+    draw_centered_text(&mut canvas, ctx, "Press Esc to cancel", 24.0, 72.0)?;
+
+    canvas.finish(&mut ctx.gfx)
+}
+
+/// Asks for a line of text, e.g. a lobby code
+pub fn draw_prompt(ctx: &mut Context, title: &str, text: &str, error: Option<&str>) -> GameResult {
+    let mut canvas = Canvas::from_frame(&ctx.gfx, GgezColor::BLACK);
+
+    draw_centered_text(&mut canvas, ctx, title, 48.0, -80.0)?;
+    draw_centered_text(&mut canvas, ctx, &format!("{text}_"), 48.0, 0.0)?;
+    if let Some(error) = error {
+        draw_centered_text(&mut canvas, ctx, error, 24.0, 60.0)?;
+    }
+    draw_centered_text(
+        &mut canvas,
+        ctx,
+        "Press Enter to confirm, Esc to cancel",
+        24.0,
+        110.0,
+    )?;
 
     canvas.finish(&mut ctx.gfx)
 }
+
+pub fn draw_lobby(
+    ctx: &mut Context,
+    lobby: Option<&NetLobby>,
+    error: Option<&str>,
+    player_name: &str,
+) -> GameResult {
+    let mut canvas = Canvas::from_frame(&ctx.gfx, GgezColor::BLACK);
+
+    let Some(lobby) = lobby else {
+        draw_centered_text(&mut canvas, ctx, "Joining lobby...", 48.0, -20.0)?;
+        if let Some(error) = error {
+            draw_centered_text(&mut canvas, ctx, error, 24.0, 40.0)?;
+        }
+        draw_centered_text(&mut canvas, ctx, "Press Esc to go back", 24.0, 90.0)?;
+        return canvas.finish(&mut ctx.gfx);
+    };
+
+    let mut y = -240.0;
+    draw_centered_text(&mut canvas, ctx, &format!("Lobby {}", lobby.code), 48.0, y)?;
+    y += 50.0;
+    draw_centered_text(&mut canvas, ctx, &lobby.mode.to_string(), 28.0, y)?;
+    y += 60.0;
+
+    // members are listed with the character they play
+    let members = |names: &[String]| {
+        names
+            .iter()
+            .map(|name| match lobby.characters.get(name) {
+                Some(character) => format!("{name} ({character})"),
+                None => name.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    for (i, team) in lobby.teams.iter().enumerate() {
+        let text = format!(
+            "Team {} ({}/{}): {}",
+            i + 1,
+            team.len(),
+            lobby.mode.team_size,
+            members(team)
+        );
+        draw_centered_text(&mut canvas, ctx, &text, 28.0, y)?;
+        y += 36.0;
+    }
+    if !lobby.unassigned.is_empty() {
+        let text = format!("No team: {}", members(&lobby.unassigned));
+        draw_centered_text(&mut canvas, ctx, &text, 28.0, y)?;
+        y += 36.0;
+    }
+
+    y += 30.0;
+    let text = format!(
+        "Press 1-{} to pick a team, C to change character",
+        lobby.teams.len()
+    );
+    draw_centered_text(&mut canvas, ctx, &text, 24.0, y)?;
+    y += 32.0;
+    let text = if lobby.host == player_name {
+        String::from("Press Enter to start the match")
+    } else {
+        format!("Waiting for {} to start the match", lobby.host)
+    };
+    draw_centered_text(&mut canvas, ctx, &text, 24.0, y)?;
+    y += 32.0;
+    draw_centered_text(&mut canvas, ctx, "Press Esc to leave", 24.0, y)?;
+
+    if let Some(error) = error {
+        draw_centered_text(&mut canvas, ctx, error, 24.0, y + 50.0)?;
+    }
+
+    canvas.finish(&mut ctx.gfx)
+}
+
+/// Recent matches of our account, `account_id` is none until the server answers
+pub fn draw_history(
+    ctx: &mut Context,
+    account_id: Option<&str>,
+    matches: &[MatchRecord],
+    expected: usize,
+) -> GameResult {
+    let mut canvas = Canvas::from_frame(&ctx.gfx, GgezColor::BLACK);
+
+    let mut y = -320.0;
+    draw_centered_text(&mut canvas, ctx, "Recent matches", 48.0, y)?;
+    y += 70.0;
+
+    match account_id {
+        None => {
+            draw_centered_text(&mut canvas, ctx, "Loading...", 28.0, y)?;
+            y += 36.0;
+        }
+        Some(_) if expected == 0 => {
+            draw_centered_text(&mut canvas, ctx, "No matches played yet", 28.0, y)?;
+            y += 36.0;
+        }
+        Some(account_id) => {
+            for record in matches {
+                let Some(player) = record.player(account_id) else {
+                    continue;
+                };
+
+                let result = match record.winner {
+                    Some(team) if team == player.team => "Won",
+                    Some(_) => "Lost",
+                    None => "Draw",
+                };
+                let rating = match player.rating {
+                    Some(rating) => format!("rating {rating:.0}"),
+                    None => String::from("private"),
+                };
+                let text = format!(
+                    "{result} {} as {} on {} in {}  -  {} hits, {} parries, {} falls, best combo {}  -  {rating}",
+                    record.mode,
+                    player.character,
+                    record.map,
+                    format_time(record.duration),
+                    player.stats.hits_landed,
+                    player.stats.parries,
+                    player.stats.falls,
+                    player.stats.best_combo,
+                );
+                draw_centered_text(&mut canvas, ctx, &text, 22.0, y)?;
+                y += 28.0;
+            }
+            if matches.len() < expected {
+                draw_centered_text(&mut canvas, ctx, "Loading...", 22.0, y)?;
+                y += 28.0;
+            }
+        }
+    }
+
+    draw_centered_text(&mut canvas, ctx, "Press Esc to go back", 24.0, y + 30.0)?;
+
+    canvas.finish(&mut ctx.gfx)
+}
+
+/// Playback state drawn over a replay, along the bottom of the window
+/// Running matches to spectate, `games` is none until the server answers
+pub fn draw_games(ctx: &mut Context, games: Option<&[NetGame]>) -> GameResult {
+    let mut canvas = Canvas::from_frame(&ctx.gfx, GgezColor::BLACK);
+
+    let mut y = -320.0;
+    draw_centered_text(&mut canvas, ctx, "Running matches", 48.0, y)?;
+    y += 70.0;
+
+    match games {
+        None => {
+            draw_centered_text(&mut canvas, ctx, "Loading...", 28.0, y)?;
+            y += 36.0;
+        }
+        Some([]) => {
+            draw_centered_text(&mut canvas, ctx, "Nobody is playing right now", 28.0, y)?;
+            y += 36.0;
+        }
+        Some(games) => {
+            for (i, game) in games.iter().enumerate() {
+                let teams: Vec<String> = game.teams.iter().map(|team| team.join(", ")).collect();
+                let text = format!(
+                    "{}: {} on {}  -  {}  -  {} watching",
+                    i + 1,
+                    game.mode,
+                    game.map,
+                    teams.join(" vs "),
+                    game.spectators,
+                );
+                draw_centered_text(&mut canvas, ctx, &text, 24.0, y)?;
+                y += 32.0;
+            }
+        }
+    }
+
+    draw_centered_text(
+        &mut canvas,
+        ctx,
+        "Press a number to watch, R to refresh, Esc to go back",
+        24.0,
+        y + 30.0,
+    )?;
+
+    canvas.finish(&mut ctx.gfx)
+}
+
+/// Controls shown over a match we are watching, `following` names the
+/// player the camera follows, if any
+pub fn draw_spectator_overlay(
+    ctx: &mut Context,
+    following: Option<&str>,
+    free_camera: bool,
+) -> GameResult {
+    // drawn over the rendered match, so the frame is not cleared
+    let mut canvas = Canvas::from_frame(&ctx.gfx, None);
+    let (_, h) = ctx.gfx.drawable_size();
+    let bottom = h / 2.0;
+
+    let text = match (following, free_camera) {
+        (_, true) => String::from("Spectating  -  free camera"),
+        (Some(name), false) => format!("Spectating  -  following {name}"),
+        (None, false) => String::from("Spectating"),
+    };
+    draw_centered_text(&mut canvas, ctx, &text, 28.0, bottom - 80.0)?;
+    draw_centered_text(
+        &mut canvas,
+        ctx,
+        "Tab follow next player, F free camera, arrows move it, Esc leave",
+        20.0,
+        bottom - 45.0,
+    )?;
+
+    canvas.finish(&mut ctx.gfx)
+}
+
+pub fn draw_replay_overlay(
+    ctx: &mut Context,
+    time: f32,
+    length: f32,
+    speed: f32,
+    paused: bool,
+    desync: Option<f32>,
+) -> GameResult {
+    // drawn over the rendered match, so the frame is not cleared
+    let mut canvas = Canvas::from_frame(&ctx.gfx, None);
+    let (_, h) = ctx.gfx.drawable_size();
+    let bottom = h / 2.0;
+
+    let state = if paused { "  paused" } else { "" };
+    let text = format!(
+        "{} / {}  x{speed}{state}",
+        format_time(time),
+        format_time(length)
+    );
+    draw_centered_text(&mut canvas, ctx, &text, 28.0, bottom - 80.0)?;
+    draw_centered_text(
+        &mut canvas,
+        ctx,
+        "Space pause, Left/Right seek, Up/Down speed, Home restart, Q quit",
+        20.0,
+        bottom - 45.0,
+    )?;
+    if let Some(desync) = desync {
+        let text = format!(
+            "Replay no longer matches the recorded match since {}",
+            format_time(desync)
+        );
+        draw_centered_text(&mut canvas, ctx, &text, 24.0, bottom - 115.0)?;
+    }
+
+    canvas.finish(&mut ctx.gfx)
+}
+
+fn format_time(seconds: f32) -> String {
+    let seconds = seconds as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
@@ -16,8 +16,8 @@ use glam::Vec2;
 use protocol::init::InitData;
 use simulation::{
     attack::{Attack, AttackKind},
-    constants::{NAME_COLOR, PLAYER_SIZE, VIRTUAL_HEIGHT, VIRTUAL_WIDTH},
-    game_state::GameState,
+    constants::{NAME_COLOR, VIRTUAL_HEIGHT, VIRTUAL_WIDTH},
+    game_state::{GameState, Outcome},
 };
 use uuid::Uuid;
 
@@ -90,10 +90,28 @@ impl TrailRenderer {
     }
 }
 
+// world units per second the free camera moves at
+const FREE_CAMERA_SPEED: f32 = 900.0;
+
+/// What the camera keeps in view
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CameraMode {
+    /// Every living player
+    Players,
+    /// One player, for spectators
+    Follow(Uuid),
+    /// Moved by hand, for spectators
+    Free,
+}
+
 pub struct RenderState {
     c_player: Uuid,
-    team_one_color: Color,
-    team_two_color: Color,
+    camera_mode: CameraMode,
+    // where the free camera is heading
+    free_target: Vec2,
+    // players in team order, the order spectators cycle through them
+    player_order: Vec<Uuid>,
+    team_colors: Vec<Color>,
     player_name_above: bool,
     zoom: f32,
     camera_pos: Vec2,
@@ -115,17 +133,26 @@ impl RenderState {
         let attack_img = Image::from_bytes(&ctx.gfx, &config.attack_image()?)?;
         let parry_img = Image::from_bytes(&ctx.gfx, &config.parry_image()?)?;
 
+        let player_order = init_data
+            .teams
+            .iter()
+            .flatten()
+            .filter_map(|id| Uuid::parse_str(id).ok())
+            .collect();
+
         let mut players = HashMap::new();
         for (id, data) in init_data.players {
             players.insert(
-                Uuid::parse_str(&id).expect("Invalid UUID string"),
+                Uuid::parse_str(&id)?,
                 PlayerRenderState {
                     name: data.name,
-                    color: if init_data.teams[0].contains(&id) {
-                        config.team_one_color()
-                    } else {
-                        config.team_two_color()
-                    },
+                    color: config.team_color(
+                        init_data
+                            .teams
+                            .iter()
+                            .position(|team| team.contains(&id))
+                            .unwrap_or(0),
+                    ),
                     trail: TrailRenderer::new(
                         config.trail_delay(),
                         config.trail_opacity(),
@@ -137,10 +164,14 @@ impl RenderState {
 
         Ok(Self {
             c_player,
+            camera_mode: CameraMode::Players,
+            free_target: Vec2::ZERO,
+            player_order,
             camera_pos: Vec2::new(0.0, 0.0),
             bias_strength: config.camera_bias(),
-            team_one_color: config.team_one_color(),
-            team_two_color: config.team_two_color(),
+            team_colors: (0..init_data.teams.len())
+                .map(|team_idx| config.team_color(team_idx))
+                .collect(),
             player_name_above: config.player_name_above(),
             zoom: config.camera_zoom(),
             background_image: Some(bg_img),
@@ -203,6 +234,7 @@ impl RenderState {
         self.draw_map(&mut game_canvas, &mut ctx.gfx, &camera_transform, gs)?;
         self.draw_trails(&mut game_canvas, &mut ctx.gfx, &camera_transform)?;
         self.draw_players(&mut game_canvas, ctx, &camera_translation, gs)?;
+        self.draw_projectiles(&mut game_canvas, &mut ctx.gfx, &camera_transform, gs)?;
         self.draw_hud(&mut game_canvas, ctx, gs);
 
         // DEBUG
@@ -230,7 +262,62 @@ impl RenderState {
         final_canvas.finish(&mut ctx.gfx)
     }
 
+    #[must_use]
+    pub fn player_name(&self, player_id: &Uuid) -> Option<&str> {
+        self.players.get(player_id).map(|p| p.name.as_str())
+    }
+
+    #[must_use]
+    pub fn camera_mode(&self) -> CameraMode {
+        self.camera_mode
+    }
+
+    pub fn set_camera_mode(&mut self, mode: CameraMode) {
+        if mode == CameraMode::Free {
+            self.free_target = self.camera_pos;
+        }
+        self.camera_mode = mode;
+    }
+
+    /// Follows the next player in team order, after the last one the
+    /// camera goes back to keeping everyone in view
+    pub fn follow_next_player(&mut self) {
+        let next = match self.camera_mode {
+            CameraMode::Follow(id) => self
+                .player_order
+                .iter()
+                .position(|p| *p == id)
+                .and_then(|idx| self.player_order.get(idx + 1)),
+            _ => self.player_order.first(),
+        };
+
+        self.set_camera_mode(next.map_or(CameraMode::Players, |id| CameraMode::Follow(*id)));
+    }
+
+    /// Moves the free camera in the direction of `x` and `y` at its speed
+    pub fn pan_camera(&mut self, x: f32, y: f32, dt: f32) {
+        if self.camera_mode == CameraMode::Free {
+            self.free_target += Vec2::new(x, y).normalize_or_zero() * FREE_CAMERA_SPEED * dt;
+        }
+    }
+
     fn update_camera(&mut self, gs: &GameState) {
+        let lerp_factor = 0.1;
+
+        let target = match self.camera_mode {
+            CameraMode::Players => None,
+            CameraMode::Follow(id) => gs
+                .players
+                .get(&id)
+                .filter(|player| player.combat.is_alive())
+                .map(|player| player.physics.center()),
+            CameraMode::Free => Some(self.free_target),
+        };
+        if let Some(target) = target {
+            self.camera_pos = self.camera_pos.lerp(target, lerp_factor);
+            return;
+        }
+
         let mut sum = Vec2::ZERO;
         let mut count: usize = 0;
 
@@ -238,7 +325,7 @@ impl RenderState {
             if !player.combat.is_alive() {
                 continue;
             }
-            sum += player.physics.pos + PLAYER_SIZE / 2.0;
+            sum += player.physics.center();
             count += 1;
         }
 
@@ -248,12 +335,10 @@ impl RenderState {
 
         let player_center = sum / count as f32;
 
-        let map_rect = gs.map.get_rect();
-        let map_center = Vec2::new(map_rect.x + map_rect.w / 2.0, map_rect.y + map_rect.h / 2.0);
+        let map_center = gs.map.center();
 
         let biased_target = player_center.lerp(map_center, self.bias_strength);
 
-        let lerp_factor = 0.1;
         self.camera_pos = self.camera_pos.lerp(biased_target, lerp_factor);
     }
 
@@ -272,35 +357,45 @@ impl RenderState {
         camera_transform: &DrawParam,
         gs: &GameState,
     ) -> GameResult {
-        let map_mesh = Mesh::new_rectangle(
-            gfx,
-            DrawMode::fill(),
-            rect_to_ggez(gs.map.get_rect()),
-            color_to_ggez(&gs.map.get_color()),
-        )?;
-        game_canvas.draw(&map_mesh, *camera_transform);
+        let color = color_to_ggez(&gs.map.get_color());
+        for platform in gs.map.active_platforms() {
+            // one-way platforms are drawn see-through
+            let mut platform_color = color;
+            if platform.is_one_way() {
+                platform_color.a *= 0.5;
+            }
+
+            let mesh = Mesh::new_rectangle(
+                gfx,
+                DrawMode::fill(),
+                rect_to_ggez(&platform.rect),
+                platform_color,
+            )?;
+            game_canvas.draw(&mesh, *camera_transform);
+        }
 
         Ok(())
     }
 
-    fn draw_parry(&self, game_canvas: &mut Canvas, player_pos: Vec2) {
+    fn draw_parry(&self, game_canvas: &mut Canvas, player_center: Vec2) {
         if let Some(img) = self.parry_image.as_ref() {
             // draw frame
-            let draw_param = self.drawparam_constructor(
-                (player_pos + PLAYER_SIZE / 2.0) - (img.width() as f32 / 2.0),
-            );
+            let draw_param = self.drawparam_constructor(player_center - (img.width() as f32 / 2.0));
 
             game_canvas.draw(img, draw_param);
         }
     }
 
-    fn draw_attacks(&self, game_canvas: &mut Canvas, player_pos: Vec2, attacks: &[Attack]) {
+    fn draw_attacks(&self, game_canvas: &mut Canvas, player_center: Vec2, attacks: &[Attack]) {
         for atk in attacks {
-            if *atk.kind() == AttackKind::Dash || *atk.kind() == AttackKind::Slam {
+            if *atk.kind() == AttackKind::Dash
+                || *atk.kind() == AttackKind::Slam
+                || !atk.is_active()
+            {
                 continue;
             }
 
-            let rect = atk.get_rect(player_pos);
+            let rect = atk.get_rect(player_center);
 
             // get attack image rotation
             let facing: Vec2 = atk.facing();
@@ -352,7 +447,7 @@ impl RenderState {
                 let mesh = Mesh::new_rectangle(
                     gfx,
                     DrawMode::stroke(1.0),
-                    rect_to_ggez(&attack.get_rect(player.physics.pos)),
+                    rect_to_ggez(&attack.get_rect(player.physics.center())),
                     GgezColor::new(1.0, 1.0, 1.0, 0.4),
                 )?;
                 game_canvas.draw(&mesh, camera_transform);
@@ -383,6 +478,39 @@ impl RenderState {
         Ok(())
     }
 
+    /// Filled in the color of the team that fired them
+    fn draw_projectiles(
+        &self,
+        game_canvas: &mut Canvas,
+        gfx: &mut GraphicsContext,
+        camera_transform: &DrawParam,
+        gs: &GameState,
+    ) -> GameResult {
+        for projectile in &gs.projectiles {
+            let Some(owner) = self.players.get(&projectile.owner) else {
+                continue;
+            };
+
+            let mesh = Mesh::new_rectangle(
+                gfx,
+                DrawMode::fill(),
+                rect_to_ggez(&projectile.get_rect()),
+                color_to_ggez(&owner.color),
+            )?;
+            game_canvas.draw(&mesh, *camera_transform);
+
+            let outline = Mesh::new_rectangle(
+                gfx,
+                DrawMode::stroke(2.0),
+                rect_to_ggez(&projectile.get_rect()),
+                GgezColor::new(0.0, 0.0, 0.0, 1.0),
+            )?;
+            game_canvas.draw(&outline, *camera_transform);
+        }
+
+        Ok(())
+    }
+
     fn draw_players(
         &self,
         game_canvas: &mut Canvas,
@@ -437,11 +565,11 @@ impl RenderState {
             let text_dims = text.dimensions(ctx).unwrap();
 
             let draw_param = self.drawparam_constructor(Vec2::new(
-                player.physics.pos.x + (PLAYER_SIZE / 2.0) - (text_dims.w / 2.0),
+                player.physics.center().x - (text_dims.w / 2.0),
                 if self.player_name_above {
                     player.physics.pos.y - (text_dims.h * 1.5)
                 } else {
-                    player.physics.pos.y + PLAYER_SIZE + (text_dims.h / 2.0)
+                    player.physics.pos.y + player.physics.movement.size + (text_dims.h / 2.0)
                 },
             ));
             game_canvas.draw(&text, draw_param);
@@ -454,16 +582,16 @@ impl RenderState {
                     color: Some(GgezColor::new(1.0, 1.0, 1.0, 1.0)),
                 });
                 let draw_param_number = self.drawparam_constructor(Vec2::new(
-                    player.physics.pos.x + PLAYER_SIZE + 5.0,
+                    player.physics.pos.x + player.physics.movement.size + 5.0,
                     player.physics.pos.y - 10.0,
                 ));
                 game_canvas.draw(&combo_number, draw_param_number);
             }
 
-            self.draw_attacks(game_canvas, player.physics.pos, &player.combat.attacks);
+            self.draw_attacks(game_canvas, player.physics.center(), &player.combat.attacks);
 
             if player.status.parrying() {
-                self.draw_parry(game_canvas, player.physics.pos)
+                self.draw_parry(game_canvas, player.physics.center())
             }
         }
 
@@ -477,17 +605,23 @@ impl RenderState {
         const START_Y: f32 = MARGIN;
         const LINE_HEIGHT: f32 = MARGIN;
 
-        for (team_index, _team) in gs.teams.iter().enumerate() {
-            let is_right_team = team_index == 1;
-
-            for (player_index, player_id) in gs.teams[team_index].iter().enumerate() {
-                let y = START_Y + player_index as f32 * LINE_HEIGHT;
+        // even teams are listed on the left, odd teams on the right
+        let mut rows = [0usize; 2];
+        for (team_index, team) in gs.teams.iter().enumerate() {
+            let is_right_team = team_index % 2 == 1;
+
+            for player_id in team {
+                let row = &mut rows[team_index % 2];
+                let y = START_Y + *row as f32 * LINE_HEIGHT;
+                *row += 1;
+                let player = gs.players.get(player_id).unwrap();
+                let lives = if player.status.disconnected {
+                    String::from("DC")
+                } else {
+                    player.combat.lives.to_string()
+                };
                 let text = Text::new(TextFragment {
-                    text: format!(
-                        "{}: {}",
-                        self.players.get(player_id).unwrap().name,
-                        gs.players.get(player_id).unwrap().combat.lives,
-                    ),
+                    text: format!("{}: {}", self.players.get(player_id).unwrap().name, lives,),
                     font: None,
                     scale: Some(PxScale::from(36.0)),
                     ..Default::default()
@@ -521,21 +655,32 @@ impl RenderState {
             DrawParam::default().dest(Vec2::new(fps_x, fps_y).to_mint_point()),
         );
 
-        if gs.winner > 0 {
+        if let Some(outcome) = gs.outcome {
+            let (text, color) = match outcome {
+                Outcome::Win(team_idx) => {
+                    // a team of one is named after its player
+                    let text = match gs.teams[team_idx].as_slice() {
+                        [player_id] => format!(
+                            "{} WINS!",
+                            self.players.get(player_id).unwrap().name.to_uppercase()
+                        ),
+                        _ => format!("TEAM {} WINS!", team_idx + 1),
+                    };
+                    (text, color_to_ggez(&self.team_colors[team_idx]))
+                }
+                Outcome::Draw => (String::from("DRAW!"), GgezColor::WHITE),
+            };
+
             let winner_text = Text::new(TextFragment {
-                text: format!("TEAM {} WINS!", gs.winner),
+                text,
                 font: None,
                 scale: Some(PxScale::from(200.0)),
-                color: Some(if gs.winner == 1 {
-                    color_to_ggez(&self.team_one_color)
-                } else {
-                    color_to_ggez(&self.team_two_color)
-                }),
+                color: Some(color),
             });
 
             let winner_dims = winner_text.dimensions(ctx).unwrap();
-            let winner_x = (VIRTUAL_WIDTH - winner_dims.w as f32) / 2.0;
-            let winner_y = (VIRTUAL_HEIGHT - winner_dims.h as f32) / 3.5;
+            let winner_x = (VIRTUAL_WIDTH - winner_dims.w) / 2.0;
+            let winner_y = (VIRTUAL_HEIGHT - winner_dims.h) / 3.5;
 
             game_canvas.draw(
                 &winner_text,
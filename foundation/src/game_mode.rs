@@ -1,8 +1,40 @@
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use wincode::{SchemaRead, SchemaWrite};
 
-#[derive(Deserialize, Serialize, Clone, SchemaRead, SchemaWrite, Debug)]
-pub enum GameMode {
-    Solos,
-    Duos,
+/// Number of teams and players per team in a match.
+/// More than two teams of one player is a free-for-all.
+#[derive(
+    Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, SchemaRead, SchemaWrite, Debug,
+)]
+pub struct GameMode {
+    pub teams: usize,
+    pub team_size: usize,
+}
+
+impl GameMode {
+    /// Most players a match can have, so that a full snapshot and a match
+    /// record still fit in a single datagram
+    pub const MAX_PLAYERS: usize = 8;
+
+    #[must_use]
+    pub fn new(teams: usize, team_size: usize) -> Self {
+        Self { teams, team_size }
+    }
+
+    #[must_use]
+    pub fn players(&self) -> usize {
+        self.teams * self.team_size
+    }
+}
+
+impl fmt::Display for GameMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.team_size == 1 && self.teams > 2 {
+            return write!(f, "{}-player free-for-all", self.teams);
+        }
+
+        let sizes = vec![self.team_size.to_string(); self.teams];
+        write!(f, "{}", sizes.join("v"))
+    }
 }
@@ -1,4 +1,7 @@
-#[derive(Clone, Copy, PartialEq)]
+use serde::{Deserialize, Serialize};
+use wincode::{SchemaRead, SchemaWrite};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, SchemaWrite, SchemaRead)]
 pub struct Rect {
     pub x: f32,
     pub y: f32,
@@ -19,4 +22,9 @@ impl Rect {
             && self.y < other.y + other.h
             && self.y + self.h > other.y
     }
+
+    #[must_use]
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x <= self.x + self.w && y >= self.y && y <= self.y + self.h
+    }
 }
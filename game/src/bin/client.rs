@@ -1,42 +1,111 @@
 use anyhow::Result;
-use client_logic::{ClientEvent, ClientState, GameSession, NetworkClient};
+use client_logic::{
+    ClientEvent, ClientState, GameSession, NetworkClient, input::Bindings, load_init_data,
+};
 use display::menus;
-use display::render::RenderState;
+use display::render::{CameraMode, RenderState};
 use foundation::GameMode;
-use game_config::read::Config;
+use game_config::{read::Config, session};
 use ggez::{
     Context, ContextBuilder, GameResult,
-    event::EventHandler,
-    input::keyboard::{KeyCode, KeyInput},
+    event::{Axis, Button, EventHandler, GamepadId},
+    input::keyboard::{KeyCode, KeyInput, KeyMods},
+};
+use protocol::{
+    history::MatchRecord,
+    init::NetInitData,
+    net_client::MAX_NAME_LENGTH,
+    net_server::{NetGame, NetLobby, NetParty, ServerMessage},
 };
-use protocol::{init::InitData, net_server::ServerMessage};
 use simulation::constants::{VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
-use std::collections::HashSet;
+use simulation::{Character, PlayerInput};
 use std::sync::Arc;
-use tokio::{
-    sync::mpsc::{UnboundedReceiver, unbounded_channel},
-    task::JoinHandle,
-};
+use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
 use uuid::Uuid;
 
+// codes are shorter, this only keeps the text on screen
+const MAX_LOBBY_CODE_LENGTH: usize = 8;
+
 enum ClientView {
     Menu,
-    Queue(QueueController),
+    // the server turned us away, with the reason
+    Rejected(String),
+    Queue,
+    Prompt {
+        prompt: Prompt,
+        text: String,
+        error: Option<String>,
+    },
+    Lobby {
+        // none until the server confirms we are in
+        lobby: Option<NetLobby>,
+        error: Option<String>,
+    },
+    History {
+        // none until the server answers
+        account_id: Option<String>,
+        // how many matches the server is sending
+        expected: usize,
+        matches: Vec<MatchRecord>,
+    },
+    Games {
+        // none until the server answers
+        games: Option<Vec<NetGame>>,
+    },
     InGame {
         session: Box<GameSession>,
         client: Arc<ClientState>,
+        // watching, our key presses move the camera instead
+        spectating: bool,
     },
 }
 
-struct QueueController {
-    event_rx: UnboundedReceiver<QueueEvent>,
-    task: JoinHandle<()>,
+/// What the text typed in a prompt is for
+#[derive(Clone, Copy)]
+enum Prompt {
+    LobbyCode,
+    InviteName,
 }
 
-enum QueueEvent {
+impl Prompt {
+    fn title(self) -> &'static str {
+        match self {
+            Prompt::LobbyCode => "Enter lobby code",
+            Prompt::InviteName => "Invite player",
+        }
+    }
+
+    fn accepts(self, text: &str, character: char) -> bool {
+        match self {
+            Prompt::LobbyCode => {
+                character.is_ascii_alphanumeric() && text.len() < MAX_LOBBY_CODE_LENGTH
+            }
+            Prompt::InviteName => !character.is_control() && text.len() < MAX_NAME_LENGTH,
+        }
+    }
+}
+
+/// Server messages that matter outside of a match
+enum MenuEvent {
     MatchFound {
         c_player: String,
-        init_data: InitData,
+        init_data: NetInitData,
+    },
+    LobbyUpdated(NetLobby),
+    LobbyError(String),
+    PartyUpdated(Option<NetParty>),
+    PartyInvite(String),
+    PartyError(String),
+    Queued,
+    QueueLeft,
+    History {
+        account_id: String,
+        matches: usize,
+    },
+    HistoryEntry(Box<MatchRecord>),
+    Games(Vec<NetGame>),
+    Spectate {
+        init_data: NetInitData,
     },
 }
 
@@ -44,10 +113,28 @@ struct App {
     view: ClientView,
     network: Arc<NetworkClient>,
     config: Config,
+    bindings: Bindings,
+    server_name: String,
+    modes: Vec<GameMode>,
+    // played in the next match, the server is told on every change
+    character: Character,
+    // none while in a match, the game tasks read from the server then
+    menu_events: Option<UnboundedReceiver<MenuEvent>>,
+    party: Option<NetParty>,
+    // name of whoever invited us last
+    invite: Option<String>,
+    // shown in the menu until the next key press
+    notice: Option<String>,
 }
 
 impl App {
     async fn new(config: Config) -> Result<Self> {
+        let bindings = Bindings::new(
+            config.controls(),
+            config.gamepad_buttons(),
+            config.gamepad_deadzone(),
+            config.gamepad_with_keyboard(),
+        )?;
         let network = Arc::new(
             NetworkClient::new(
                 config.clientip(),
@@ -58,106 +145,220 @@ impl App {
             .await,
         );
 
-        network.handshake(config.playername()).await?;
+        let welcome = match network
+            .handshake(
+                config.playername(),
+                config.character(),
+                session::load_session_token(),
+                session::load_account_token(),
+            )
+            .await
+        {
+            Ok(welcome) => welcome,
+            Err(e) => {
+                return Ok(Self {
+                    view: ClientView::Rejected(e.to_string()),
+                    network,
+                    character: config.character(),
+                    config,
+                    bindings,
+                    server_name: String::new(),
+                    modes: Vec::new(),
+                    menu_events: None,
+                    party: None,
+                    invite: None,
+                    notice: None,
+                });
+            }
+        };
+        if let Err(e) = session::save_session_token(&welcome.session_token) {
+            eprintln!("Failed to save session token: {e}");
+        }
+        if let Err(e) = session::save_account_token(&welcome.account_token) {
+            eprintln!("Failed to save account token: {e}");
+        }
+
+        // we were in a match before restarting, the server resends it
+        let view = if welcome.in_game {
+            ClientView::Queue
+        } else {
+            ClientView::Menu
+        };
 
         Ok(Self {
-            view: ClientView::Menu,
+            view,
+            menu_events: Some(App::listen(Arc::clone(&network))),
             network,
+            character: config.character(),
             config,
+            bindings,
+            server_name: welcome.server_name,
+            modes: welcome.modes,
+            party: None,
+            invite: None,
+            notice: None,
         })
     }
 
-    fn start_queue(&self, _ctx: &Context, mode: GameMode) -> Result<QueueController> {
-        let (event_tx, event_rx) = unbounded_channel();
-
-        let network = Arc::clone(&self.network);
+    /// Sends a request to the server in the background
+    fn request<F, Fut>(&self, what: &'static str, request: F)
+    where
+        F: FnOnce(Arc<NetworkClient>) -> Fut,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        let request = request(Arc::clone(&self.network));
         tokio::spawn(async move {
-            if let Err(e) = network.enter_queue(mode).await {
-                eprintln!("Failed to join queue: {e}");
+            if let Err(e) = request.await {
+                eprintln!("Failed to {what}: {e}");
             }
         });
+    }
 
-        let network = Arc::clone(&self.network);
-        let task = tokio::spawn({
-            async move {
-                loop {
-                    match network.poll_queue().await {
-                        Ok(ServerMessage::StartGame {
-                            c_player,
-                            init_data,
-                        }) => {
-                            let _ = event_tx.send(QueueEvent::MatchFound {
-                                c_player,
-                                init_data,
-                            });
-                            break;
-                        }
-                        _ => {}
+    /// Moves on to the next character
+    fn change_character(&mut self) {
+        self.character = self.character.next();
+        let character = self.character;
+        self.request("pick character", move |network| async move {
+            network.pick_character(character).await
+        });
+    }
+
+    /// Forwards server messages until a match starts
+    fn listen(network: Arc<NetworkClient>) -> UnboundedReceiver<MenuEvent> {
+        let (event_tx, event_rx) = unbounded_channel();
+
+        tokio::spawn(async move {
+            loop {
+                let event = match network.poll_queue().await {
+                    Ok(ServerMessage::StartGame {
+                        c_player,
+                        init_data,
+                    }) => MenuEvent::MatchFound {
+                        c_player,
+                        init_data,
+                    },
+                    Ok(ServerMessage::Lobby(lobby)) => MenuEvent::LobbyUpdated(lobby),
+                    Ok(ServerMessage::LobbyError(e)) => MenuEvent::LobbyError(e.to_string()),
+                    Ok(ServerMessage::Party(party)) => MenuEvent::PartyUpdated(party),
+                    Ok(ServerMessage::PartyInvite { from }) => MenuEvent::PartyInvite(from),
+                    Ok(ServerMessage::PartyError(e)) => MenuEvent::PartyError(e.to_string()),
+                    Ok(ServerMessage::Queued(_)) => MenuEvent::Queued,
+                    Ok(ServerMessage::QueueLeft) => MenuEvent::QueueLeft,
+                    Ok(ServerMessage::MatchHistory {
+                        account_id,
+                        matches,
+                    }) => MenuEvent::History {
+                        account_id,
+                        matches: matches as usize,
+                    },
+                    Ok(ServerMessage::HistoryEntry(record)) => {
+                        MenuEvent::HistoryEntry(Box::new(record))
+                    }
+                    Ok(ServerMessage::Games(games)) => MenuEvent::Games(games),
+                    Ok(ServerMessage::Spectating { init_data }) => {
+                        MenuEvent::Spectate { init_data }
                     }
+                    _ => continue,
+                };
+
+                let found = matches!(
+                    event,
+                    MenuEvent::MatchFound { .. } | MenuEvent::Spectate { .. }
+                );
+                if event_tx.send(event).is_err() || found {
+                    break;
                 }
             }
         });
 
-        Ok(QueueController { event_rx, task })
-    }
-
-    fn update_menu(_app: &mut App, _ctx: &mut Context) -> GameResult<Option<ClientView>> {
-        Ok(None)
+        event_rx
     }
 
-    fn update_queue(
-        ctx: &mut Context,
-        controller: &mut QueueController,
-        config: &Config,
-        network: Arc<NetworkClient>,
-    ) -> GameResult<Option<ClientView>> {
-        if let Ok(event) = controller.event_rx.try_recv() {
+    fn update_menus(&mut self, ctx: &mut Context) -> GameResult<Option<ClientView>> {
+        while let Some(event) = self.menu_events.as_mut().and_then(|rx| rx.try_recv().ok()) {
             match event {
-                QueueEvent::MatchFound {
+                MenuEvent::MatchFound {
                     c_player,
                     init_data,
                 } => {
-                    let c_player = Uuid::parse_str(&c_player).expect("Invalid UUID string");
-                    let render_state =
-                        match RenderState::new(ctx, config, init_data.clone(), c_player) {
-                            Ok(render_state) => render_state,
-                            Err(e) => {
-                                eprintln!("Error initializing render_state: {e}");
-                                return Ok(Some(ClientView::Menu));
-                            }
-                        };
-
-                    let client = Arc::new(match ClientState::new(c_player, init_data) {
-                        Ok(client) => client,
+                    self.menu_events = None;
+                    let c_player = match Uuid::parse_str(&c_player) {
+                        Ok(c_player) => c_player,
                         Err(e) => {
-                            eprintln!("Unable to initialize client: {e}");
+                            eprintln!("Invalid player id from server: {e}");
                             return Ok(Some(ClientView::Menu));
                         }
-                    });
-
-                    // spawn networking tasks.
-                    network.spawn_receive_task(Arc::clone(&client));
-                    network.spawn_send_task(Arc::clone(&client));
-
-                    // forward keyboard input into the shared client input state.
-                    let current_input_write = Arc::clone(&client.current_input);
-                    let (input_tx, mut input_rx) = unbounded_channel::<HashSet<KeyCode>>();
-                    tokio::spawn(async move {
-                        while let Some(input) = input_rx.recv().await {
-                            let mut current = current_input_write.lock().await;
-                            *current = input;
-                        }
-                    });
-
-                    let session = Box::new(GameSession::new(
+                    };
+                    return App::start_match(
+                        ctx,
                         c_player,
-                        input_tx,
-                        Arc::clone(&client.snapshot_history),
-                        Arc::clone(&client.render_tick),
-                        render_state,
-                    ));
-
-                    return Ok(Some(ClientView::InGame { session, client }));
+                        init_data,
+                        &self.config,
+                        self.bindings.clone(),
+                        Arc::clone(&self.network),
+                    );
+                }
+                MenuEvent::Spectate { init_data } => {
+                    self.menu_events = None;
+                    // nobody is ours to predict, a nil id matches no player
+                    return App::start_match(
+                        ctx,
+                        Uuid::nil(),
+                        init_data,
+                        &self.config,
+                        self.bindings.clone(),
+                        Arc::clone(&self.network),
+                    );
+                }
+                MenuEvent::LobbyUpdated(updated) => {
+                    if let ClientView::Lobby { lobby, error } = &mut self.view {
+                        *lobby = Some(updated);
+                        *error = None;
+                    }
+                }
+                MenuEvent::LobbyError(e) => {
+                    if let ClientView::Lobby { error, .. } = &mut self.view {
+                        *error = Some(e);
+                    }
+                }
+                MenuEvent::PartyUpdated(party) => self.party = party,
+                MenuEvent::PartyInvite(from) => self.invite = Some(from),
+                MenuEvent::PartyError(e) => self.notice = Some(e),
+                // the party leader queued us
+                MenuEvent::Queued => {
+                    if matches!(self.view, ClientView::Menu) {
+                        return Ok(Some(ClientView::Queue));
+                    }
+                }
+                MenuEvent::QueueLeft => {
+                    if matches!(self.view, ClientView::Queue) {
+                        return Ok(Some(ClientView::Menu));
+                    }
+                }
+                MenuEvent::History {
+                    account_id: id,
+                    matches: count,
+                } => {
+                    if let ClientView::History {
+                        account_id,
+                        expected,
+                        matches,
+                    } = &mut self.view
+                    {
+                        *account_id = Some(id);
+                        *expected = count;
+                        matches.clear();
+                    }
+                }
+                MenuEvent::HistoryEntry(record) => {
+                    if let ClientView::History { matches, .. } = &mut self.view {
+                        matches.push(*record);
+                    }
+                }
+                MenuEvent::Games(running) => {
+                    if let ClientView::Games { games } = &mut self.view {
+                        *games = Some(running);
+                    }
                 }
             }
         }
@@ -165,6 +366,69 @@ impl App {
         Ok(None)
     }
 
+    fn start_match(
+        ctx: &mut Context,
+        c_player: Uuid,
+        init_data: NetInitData,
+        config: &Config,
+        bindings: Bindings,
+        network: Arc<NetworkClient>,
+    ) -> GameResult<Option<ClientView>> {
+        let init_data = match load_init_data(init_data) {
+            Ok(init_data) => init_data,
+            Err(e) => {
+                eprintln!("Unable to set up the match: {e}");
+                return Ok(Some(ClientView::Menu));
+            }
+        };
+
+        let render_state = match RenderState::new(ctx, config, init_data.clone(), c_player) {
+            Ok(render_state) => render_state,
+            Err(e) => {
+                eprintln!("Error initializing render_state: {e}");
+                return Ok(Some(ClientView::Menu));
+            }
+        };
+
+        let client = Arc::new(match ClientState::new(c_player, init_data) {
+            Ok(client) => client,
+            Err(e) => {
+                eprintln!("Unable to initialize client: {e}");
+                return Ok(Some(ClientView::Menu));
+            }
+        });
+
+        // spawn networking tasks.
+        network.spawn_receive_task(Arc::clone(&client));
+        network.spawn_send_task(Arc::clone(&client));
+
+        // forward keyboard input into the shared client input state.
+        let current_input_write = Arc::clone(&client.current_input);
+        let (input_tx, mut input_rx) = unbounded_channel::<PlayerInput>();
+        tokio::spawn(async move {
+            while let Some(input) = input_rx.recv().await {
+                let mut current = current_input_write.lock().await;
+                *current = input;
+            }
+        });
+
+        let session = Box::new(GameSession::new(
+            c_player,
+            input_tx,
+            bindings,
+            Arc::clone(&client.snapshot_history),
+            Arc::clone(&client.render_tick),
+            Arc::clone(&client.core),
+            render_state,
+        ));
+
+        Ok(Some(ClientView::InGame {
+            session,
+            client,
+            spectating: c_player.is_nil(),
+        }))
+    }
+
     fn update_game(client: &ClientState) -> GameResult<Option<ClientView>> {
         if client.event_rx.has_changed().unwrap()
             && let Some(ClientEvent::EndGame) = client.event_rx.borrow().clone()
@@ -186,22 +450,77 @@ impl App {
             Err(_) => return Ok(()), // skip this frame
         };
 
-        if let Some(game_state) = history.get_interpolated(*render_tick, session.c_player) {
+        // the predicted player is skipped if the core is busy
+        let predicted = session
+            .core
+            .try_lock()
+            .ok()
+            .and_then(|core| core.game_state().players.get(&session.c_player).cloned());
+
+        if let Some(game_state) =
+            history.get_interpolated(*render_tick, session.c_player, predicted)
+        {
             session.render_state.render(ctx, &game_state)?;
         }
 
         Ok(())
     }
+
+    fn draw_spectator_overlay(ctx: &mut Context, session: &GameSession) -> GameResult {
+        let render_state = &session.render_state;
+        let following = match render_state.camera_mode() {
+            CameraMode::Follow(id) => render_state.player_name(&id),
+            _ => None,
+        };
+
+        menus::draw_spectator_overlay(
+            ctx,
+            following,
+            render_state.camera_mode() == CameraMode::Free,
+        )
+    }
+
+    /// Moves the free camera with the arrow keys or WASD while they are held
+    fn pan_camera(ctx: &Context, session: &mut GameSession) {
+        let held = |keys: [KeyCode; 2]| keys.iter().any(|k| ctx.keyboard.is_key_pressed(*k));
+
+        let axis = |negative: bool, positive: bool| f32::from(positive) - f32::from(negative);
+        let x = axis(
+            held([KeyCode::Left, KeyCode::A]),
+            held([KeyCode::Right, KeyCode::D]),
+        );
+        let y = axis(
+            held([KeyCode::Up, KeyCode::W]),
+            held([KeyCode::Down, KeyCode::S]),
+        );
+
+        session
+            .render_state
+            .pan_camera(x, y, ctx.time.delta().as_secs_f32());
+    }
 }
 
 impl EventHandler for App {
     fn update(&mut self, ctx: &mut Context) -> GameResult {
         let transition = match &mut self.view {
-            ClientView::Menu => App::update_menu(self, ctx)?,
-            ClientView::Queue(controller) => {
-                App::update_queue(ctx, controller, &self.config, Arc::clone(&self.network))?
+            ClientView::Rejected(_) => None,
+            ClientView::InGame {
+                session,
+                client,
+                spectating,
+            } => {
+                if *spectating {
+                    App::pan_camera(ctx, session);
+                }
+                App::update_game(client)?
+            }
+            _ => {
+                // back from a match
+                if self.menu_events.is_none() {
+                    self.menu_events = Some(App::listen(Arc::clone(&self.network)));
+                }
+                self.update_menus(ctx)?
             }
-            ClientView::InGame { session: _, client } => App::update_game(client)?,
         };
 
         if let Some(new_view) = transition {
@@ -213,67 +532,367 @@ impl EventHandler for App {
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult {
         match &mut self.view {
-            ClientView::Menu => menus::draw_menu(ctx),
-            ClientView::Queue(_) => menus::draw_queue(ctx),
-            ClientView::InGame { session, client: _ } => App::draw_game(ctx, session),
+            ClientView::Menu => menus::draw_menu(
+                ctx,
+                &self.server_name,
+                &self.modes,
+                self.character,
+                self.party.as_ref(),
+                self.invite.as_deref(),
+                self.notice.as_deref(),
+            ),
+            ClientView::Rejected(reason) => menus::draw_rejected(ctx, reason),
+            ClientView::Queue => menus::draw_queue(ctx, self.character),
+            ClientView::Prompt {
+                prompt,
+                text,
+                error,
+            } => menus::draw_prompt(ctx, prompt.title(), text, error.as_deref()),
+            ClientView::Lobby { lobby, error } => menus::draw_lobby(
+                ctx,
+                lobby.as_ref(),
+                error.as_deref(),
+                self.config.playername(),
+            ),
+            ClientView::History {
+                account_id,
+                expected,
+                matches,
+            } => menus::draw_history(ctx, account_id.as_deref(), matches, *expected),
+            ClientView::Games { games } => menus::draw_games(ctx, games.as_deref()),
+            ClientView::InGame {
+                session,
+                spectating,
+                ..
+            } => {
+                App::draw_game(ctx, session)?;
+                if *spectating {
+                    App::draw_spectator_overlay(ctx, session)?;
+                }
+                Ok(())
+            }
         }
     }
 
     fn key_down_event(&mut self, ctx: &mut Context, input: KeyInput, _repeat: bool) -> GameResult {
-        if let Some(keycode) = input.keycode {
-            match &mut self.view {
-                ClientView::Menu => match keycode {
-                    KeyCode::Key1 => {
-                        self.view =
-                            ClientView::Queue(match App::start_queue(self, ctx, GameMode::Solos) {
-                                Ok(controller) => controller,
-                                Err(e) => {
-                                    eprintln!("Failed to start queue: {e}");
-                                    return Ok(());
-                                }
+        let Some(keycode) = input.keycode else {
+            return Ok(());
+        };
+
+        match &mut self.view {
+            ClientView::Menu => {
+                self.notice = None;
+
+                match keycode {
+                    KeyCode::Return => self.view = ClientView::prompt(Prompt::LobbyCode),
+                    KeyCode::Tab => self.view = ClientView::prompt(Prompt::InviteName),
+                    KeyCode::H => {
+                        self.request("fetch match history", |network| async move {
+                            network.request_history().await
+                        });
+                        self.view = ClientView::History {
+                            account_id: None,
+                            expected: 0,
+                            matches: Vec::new(),
+                        };
+                    }
+                    KeyCode::G => {
+                        self.request("list matches", |network| async move {
+                            network.list_games().await
+                        });
+                        self.view = ClientView::Games { games: None };
+                    }
+                    KeyCode::C => self.change_character(),
+                    KeyCode::Y if self.invite.take().is_some() => {
+                        self.request("accept invite", |network| async move {
+                            network.accept_invite().await
+                        });
+                    }
+                    KeyCode::N if self.invite.take().is_some() => {
+                        self.request("decline invite", |network| async move {
+                            network.decline_invite().await
+                        });
+                    }
+                    KeyCode::X if self.party.is_some() => {
+                        self.request("leave party", |network| async move {
+                            network.leave_party().await
+                        });
+                    }
+                    KeyCode::Q => ctx.request_quit(),
+                    _ => {
+                        // modes are listed in the order the server sent them
+                        let Some(mode) =
+                            number_key(keycode).and_then(|i| self.modes.get(i).copied())
+                        else {
+                            return Ok(());
+                        };
+
+                        if input.mods.contains(KeyMods::SHIFT) {
+                            self.request("create lobby", move |network| async move {
+                                network.create_lobby(mode).await
+                            });
+                            self.view = ClientView::lobby();
+                        } else {
+                            // the queue view follows once the server confirms
+                            self.request("join queue", move |network| async move {
+                                network.enter_queue(mode).await
                             });
+                        }
                     }
-                    KeyCode::Key2 => {
-                        self.view =
-                            ClientView::Queue(match App::start_queue(self, ctx, GameMode::Duos) {
-                                Ok(controller) => controller,
-                                Err(e) => {
-                                    eprintln!("Failed to start queue: {e}");
-                                    return Ok(());
-                                }
+                }
+            }
+            ClientView::Rejected(_) => {
+                if keycode == KeyCode::Q {
+                    ctx.request_quit();
+                }
+            }
+            ClientView::Queue => match keycode {
+                KeyCode::C => self.change_character(),
+                KeyCode::Escape => {
+                    self.request("leave queue", |network| async move {
+                        network.leave_queue().await
+                    });
+                    self.view = ClientView::Menu;
+                }
+                _ => {}
+            },
+            ClientView::Prompt {
+                prompt,
+                text,
+                error,
+            } => match keycode {
+                KeyCode::Back => {
+                    text.pop();
+                    *error = None;
+                }
+                KeyCode::Return if text.is_empty() => {
+                    *error = Some(String::from("Type something first"));
+                }
+                KeyCode::Return => {
+                    let text = std::mem::take(text);
+                    match prompt {
+                        Prompt::LobbyCode => {
+                            self.request("join lobby", |network| async move {
+                                network.join_lobby(&text).await
+                            });
+                            self.view = ClientView::lobby();
+                        }
+                        Prompt::InviteName => {
+                            self.request("invite player", |network| async move {
+                                network.invite(&text).await
                             });
+                            self.view = ClientView::Menu;
+                        }
                     }
-                    KeyCode::Q => panic!("Exiting..."), // exit hack, TODO
-                    _ => {}
-                },
-                ClientView::Queue(controller) => match keycode {
-                    KeyCode::Escape => {
-                        controller.task.abort();
-                        let network = Arc::clone(&self.network);
-                        tokio::spawn(async move {
-                            let _ = network.leave_queue().await;
+                }
+                KeyCode::Escape => self.view = ClientView::Menu,
+                _ => {}
+            },
+            ClientView::Lobby { .. } => match keycode {
+                KeyCode::C => self.change_character(),
+                KeyCode::Return => {
+                    self.request("start match", |network| async move {
+                        network.start_lobby().await
+                    });
+                }
+                KeyCode::Escape => {
+                    self.request("leave lobby", |network| async move {
+                        network.leave_lobby().await
+                    });
+                    self.view = ClientView::Menu;
+                }
+                _ => {
+                    if let Some(team) = number_key(keycode) {
+                        self.request("pick team", move |network| async move {
+                            network.pick_team(team as u8).await
                         });
-                        self.view = ClientView::Menu;
                     }
-                    _ => {}
-                },
-                ClientView::InGame { session, client: _ } => session.press(keycode),
+                }
+            },
+            ClientView::History { .. } => {
+                if keycode == KeyCode::Escape {
+                    self.view = ClientView::Menu;
+                }
             }
+            ClientView::Games { games } => match keycode {
+                KeyCode::R => {
+                    self.request("list matches", |network| async move {
+                        network.list_games().await
+                    });
+                }
+                KeyCode::Escape => self.view = ClientView::Menu,
+                _ => {
+                    let Some(game) = games
+                        .as_ref()
+                        .zip(number_key(keycode))
+                        .and_then(|(games, i)| games.get(i))
+                    else {
+                        return Ok(());
+                    };
+
+                    // the match starts once the server confirms, a fresh
+                    // list comes back instead if it already ended
+                    let game_id = game.id.clone();
+                    self.request("spectate", |network| async move {
+                        network.spectate(&game_id).await
+                    });
+                }
+            },
+            ClientView::InGame {
+                session,
+                spectating: true,
+                ..
+            } => match keycode {
+                // the server ends the match for us, which returns to the menu
+                KeyCode::Escape => {
+                    self.request("stop spectating", |network| async move {
+                        network.stop_spectating().await
+                    });
+                }
+                KeyCode::Tab => session.render_state.follow_next_player(),
+                KeyCode::F => {
+                    let mode = if session.render_state.camera_mode() == CameraMode::Free {
+                        CameraMode::Players
+                    } else {
+                        CameraMode::Free
+                    };
+                    session.render_state.set_camera_mode(mode);
+                }
+                _ => {}
+            },
+            ClientView::InGame { session, .. } => session.press(keycode),
+        }
+
+        Ok(())
+    }
+
+    fn text_input_event(&mut self, _ctx: &mut Context, character: char) -> GameResult {
+        if let ClientView::Prompt {
+            prompt,
+            text,
+            error,
+        } = &mut self.view
+            && prompt.accepts(text, character)
+        {
+            text.push(match prompt {
+                Prompt::LobbyCode => character.to_ascii_uppercase(),
+                Prompt::InviteName => character,
+            });
+            *error = None;
         }
 
         Ok(())
     }
 
     fn key_up_event(&mut self, _ctx: &mut Context, input: KeyInput) -> GameResult {
-        if let Some(keycode) = input.keycode {
-            match &mut self.view {
-                ClientView::InGame { session, client: _ } => session.release(&keycode),
-                _ => {}
-            }
+        if let Some(keycode) = input.keycode
+            && let ClientView::InGame {
+                session,
+                spectating: false,
+                ..
+            } = &mut self.view
+        {
+            session.release(&keycode);
+        }
+
+        Ok(())
+    }
+
+    fn gamepad_button_down_event(
+        &mut self,
+        _ctx: &mut Context,
+        btn: Button,
+        id: GamepadId,
+    ) -> GameResult {
+        if let ClientView::InGame {
+            session,
+            spectating: false,
+            ..
+        } = &mut self.view
+        {
+            session.press_button(id, btn);
+        }
+
+        Ok(())
+    }
+
+    fn gamepad_button_up_event(
+        &mut self,
+        _ctx: &mut Context,
+        btn: Button,
+        id: GamepadId,
+    ) -> GameResult {
+        if let ClientView::InGame {
+            session,
+            spectating: false,
+            ..
+        } = &mut self.view
+        {
+            session.release_button(id, btn);
+        }
+
+        Ok(())
+    }
+
+    fn gamepad_axis_event(
+        &mut self,
+        _ctx: &mut Context,
+        axis: Axis,
+        value: f32,
+        id: GamepadId,
+    ) -> GameResult {
+        if let ClientView::InGame {
+            session,
+            spectating: false,
+            ..
+        } = &mut self.view
+        {
+            session.move_stick(id, axis, value);
         }
 
         Ok(())
     }
+
+    fn quit_event(&mut self, _ctx: &mut Context) -> GameResult<bool> {
+        if let Err(e) = self.network.disconnect() {
+            eprintln!("Failed to notify server of disconnect: {e}");
+        }
+
+        Ok(false)
+    }
+}
+
+impl ClientView {
+    fn prompt(prompt: Prompt) -> Self {
+        ClientView::Prompt {
+            prompt,
+            text: String::new(),
+            error: None,
+        }
+    }
+
+    fn lobby() -> Self {
+        ClientView::Lobby {
+            lobby: None,
+            error: None,
+        }
+    }
+}
+
+/// Index for the number keys 1 to 9
+fn number_key(keycode: KeyCode) -> Option<usize> {
+    let keys = [
+        KeyCode::Key1,
+        KeyCode::Key2,
+        KeyCode::Key3,
+        KeyCode::Key4,
+        KeyCode::Key5,
+        KeyCode::Key6,
+        KeyCode::Key7,
+        KeyCode::Key8,
+        KeyCode::Key9,
+    ];
+    keys.iter().position(|&key| key == keycode)
 }
 
 #[tokio::main]
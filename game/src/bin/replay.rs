@@ -0,0 +1,153 @@
+use anyhow::{Result, anyhow};
+use client_logic::replay::ReplayPlayer;
+use display::menus;
+use display::render::RenderState;
+use game_config::{data::data_file, read::Config};
+use ggez::{
+    Context, ContextBuilder, GameResult,
+    event::EventHandler,
+    input::keyboard::{KeyCode, KeyInput},
+};
+use protocol::replay::Replay;
+use simulation::constants::{FIXED_DT, TICK_RATE, VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
+use std::fs;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+// seconds skipped by one press of left or right
+const SEEK_STEP: f32 = 5.0;
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 8.0;
+
+struct ReplayApp {
+    player: ReplayPlayer,
+    render_state: RenderState,
+    paused: bool,
+    speed: f32,
+    // real time not simulated yet
+    accumulator: f32,
+}
+
+impl ReplayApp {
+    fn seek_by(&mut self, seconds: f32) {
+        let ticks = (seconds.abs() * TICK_RATE as f32).round() as u64;
+        let tick = if seconds < 0.0 {
+            self.player.tick().saturating_sub(ticks)
+        } else {
+            self.player.tick() + ticks
+        };
+
+        self.player.seek(tick);
+        self.accumulator = 0.0;
+    }
+}
+
+impl EventHandler for ReplayApp {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        if self.paused {
+            return Ok(());
+        }
+
+        self.accumulator += ctx.time.delta().as_secs_f32() * self.speed;
+        while self.accumulator >= FIXED_DT {
+            self.accumulator -= FIXED_DT;
+            if !self.player.step() {
+                self.paused = true;
+                self.accumulator = 0.0;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        self.render_state.render(ctx, self.player.game_state())?;
+
+        menus::draw_replay_overlay(
+            ctx,
+            ticks_to_seconds(self.player.tick()),
+            ticks_to_seconds(self.player.len()),
+            self.speed,
+            self.paused,
+            self.player.desync().map(ticks_to_seconds),
+        )
+    }
+
+    fn key_down_event(&mut self, ctx: &mut Context, input: KeyInput, _repeat: bool) -> GameResult {
+        let Some(keycode) = input.keycode else {
+            return Ok(());
+        };
+
+        match keycode {
+            KeyCode::Space => {
+                // watching again from the start once it is over
+                if self.paused && self.player.tick() >= self.player.len() {
+                    self.player.seek(0);
+                }
+                self.paused = !self.paused;
+            }
+            KeyCode::Left => self.seek_by(-SEEK_STEP),
+            KeyCode::Right => self.seek_by(SEEK_STEP),
+            KeyCode::Up => self.speed = (self.speed * 2.0).min(MAX_SPEED),
+            KeyCode::Down => self.speed = (self.speed / 2.0).max(MIN_SPEED),
+            KeyCode::Home => {
+                self.player.seek(0);
+                self.accumulator = 0.0;
+            }
+            KeyCode::Q | KeyCode::Escape => ctx.request_quit(),
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+fn ticks_to_seconds(ticks: u64) -> f32 {
+    ticks as f32 * FIXED_DT
+}
+
+/// Most recently written replay in the user data directory
+fn latest_replay() -> Result<PathBuf> {
+    let dir = data_file("replays")?;
+    fs::read_dir(&dir)
+        .map_err(|e| anyhow!("Failed to read '{}': {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "replay"))
+        .max_by_key(|entry| entry.metadata().and_then(|m| m.modified()).ok())
+        .map(|entry| entry.path())
+        .ok_or_else(|| anyhow!("No replays in '{}'", dir.display()))
+}
+
+fn main() -> Result<()> {
+    let path = match std::env::args().nth(1) {
+        Some(path) => PathBuf::from(path),
+        None => latest_replay()?,
+    };
+    let replay = Replay::load(&path)?;
+    let config = Config::get()?;
+
+    let (ctx, event_loop) = ContextBuilder::new("platform", "Nqtural")
+        .window_setup(
+            ggez::conf::WindowSetup::default()
+                .vsync(config.vsync())
+                .title("Replay"),
+        )
+        .window_mode(
+            ggez::conf::WindowMode::default()
+                .dimensions(VIRTUAL_WIDTH, VIRTUAL_HEIGHT)
+                .resizable(true),
+        )
+        .build()?;
+
+    // nobody is highlighted as the local player
+    let render_state = RenderState::new(&ctx, &config, replay.init_data.clone(), Uuid::nil())?;
+    let app = ReplayApp {
+        player: ReplayPlayer::new(replay)?,
+        render_state,
+        paused: false,
+        speed: 1.0,
+        accumulator: 0.0,
+    };
+
+    ggez::event::run(ctx, event_loop, app);
+}
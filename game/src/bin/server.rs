@@ -1,51 +1,111 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use bimap::BiMap;
 use foundation::GameMode;
 use futures::future::pending;
-use game_config::read::Config;
-use protocol::init::{InitData, InitPlayerData};
-use protocol::net_client::ClientMessage;
+use game_config::{data::data_file, read::Config};
+use protocol::PROTOCOL_VERSION;
+use protocol::delta::SnapshotBaselines;
+use protocol::history::{MatchPlayer, MatchRecord};
+use protocol::init::{InitData, InitPlayerData, NetInitData, PlayerSlots};
+use protocol::net_client::{ClientMessage, HEARTBEAT_INTERVAL, Hello, MAX_NAME_LENGTH};
 use protocol::net_game_state;
-use protocol::net_server::ServerMessage;
+use protocol::net_server::{
+    LobbyError, NetGame, NetLobby, NetParty, PartyError, RejectReason, ServerMessage,
+};
+use protocol::reliable::{self, RESEND_INTERVAL, ReliableChannel};
+use server_logic::replay::ReplayRecorder;
 use server_logic::runtime::{
-    ClientSession, ClientState, GameHandle, GameInput, PlayerSlot, Queues,
+    ClientSession, ClientState, ConnectionEvent, GameHandle, GameInput, InputQueue, Lobbies, Lobby,
+    MATCHMAKING_INTERVAL, MAX_LISTED_GAMES, Parties, PlayerSlot, Queues, RatingGap,
 };
-use simulation::PlayerInput;
-use simulation::constants::FIXED_DT;
-use simulation::game_state::GameState;
+use server_logic::store::{HISTORY_LENGTH, Store, StoreWrite};
+use simulation::Character;
+use simulation::balance::Balance;
+use simulation::constants::{FIXED_DT, TICK_RATE};
+use simulation::game_state::{GameState, Outcome};
+use simulation::lag_compensation::StateHistory;
+use simulation::map::Map;
 use std::sync::Arc;
-use std::time::Duration;
-use std::{collections::HashMap, net::SocketAddr};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+};
 use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
 use tokio::time::{Instant, sleep};
 use tokio::{
     net::UdpSocket,
-    sync::{Mutex, RwLock},
+    sync::{Mutex, MutexGuard, RwLock},
 };
 use uuid::Uuid;
-use wincode::serialize;
+use wincode::{deserialize, serialize};
 
 pub struct Server {
     pub socket: Arc<UdpSocket>,
     pub sessions: RwLock<HashMap<Uuid, ClientSession>>,
     pub connections: RwLock<BiMap<SocketAddr, Uuid>>,
+    pub channels: Mutex<HashMap<SocketAddr, ReliableChannel>>,
     pub queues: Mutex<Queues>,
+    pub lobbies: Mutex<Lobbies>,
+    pub parties: Mutex<Parties>,
     pub games: RwLock<HashMap<Uuid, GameHandle>>,
+    pub store: Mutex<Store>,
+    // held while store changes are written, so they land in order
+    pub store_files: Mutex<()>,
+    pub rating_gap: RatingGap,
+    pub name: String,
+    pub max_players: usize,
+    pub modes: Vec<GameMode>,
+    pub map: Map,
+    pub balance: Balance,
+    // sent instead of the map, clients load their own copy
+    pub map_file: String,
+    pub max_rewind_ticks: u64,
+    pub record_replays: bool,
+    pub client_timeout: Duration,
+    pub reconnect_timeout: Duration,
 }
 
 impl Server {
-    pub fn new(socket: Arc<UdpSocket>) -> Arc<Self> {
-        Arc::new(Self {
+    pub fn new(socket: Arc<UdpSocket>, config: &Config) -> Result<Arc<Self>> {
+        let modes = vec![config.mode()?];
+
+        Ok(Arc::new(Self {
             socket,
             sessions: RwLock::new(HashMap::new()),
             connections: RwLock::new(BiMap::new()),
-            queues: Mutex::new(Queues::default()),
+            channels: Mutex::new(HashMap::new()),
+            queues: Mutex::new(Queues::new(&modes)),
+            lobbies: Mutex::new(Lobbies::default()),
+            parties: Mutex::new(Parties::default()),
             games: RwLock::new(HashMap::new()),
-        })
+            store: Mutex::new(Store::load(
+                data_file("profiles.toml")?,
+                data_file("matches.toml")?,
+            )?),
+            store_files: Mutex::new(()),
+            rating_gap: RatingGap {
+                initial: config.rating_gap() as f64,
+                growth: config.rating_gap_growth() as f64,
+            },
+            name: config.servername().to_string(),
+            max_players: config.max_players(),
+            modes,
+            map: config.map()?,
+            balance: config.balance()?,
+            map_file: config.map_file().to_string(),
+            max_rewind_ticks: (config.max_rewind() * TICK_RATE as f32).round() as u64,
+            record_replays: config.record_replays(),
+            client_timeout: Duration::from_secs_f32(config.client_timeout()),
+            reconnect_timeout: Duration::from_secs_f32(config.reconnect_timeout()),
+        }))
     }
 
     pub async fn run(self: &Arc<Self>) {
         self.spawn_network_task();
+        self.spawn_resend_task();
+        self.spawn_timeout_task();
+        self.spawn_matchmaking_task();
 
         pending::<()>().await;
     }
@@ -62,30 +122,166 @@ impl Server {
                     Err(_) => continue,
                 };
 
-                let msg = match wincode::deserialize::<ClientMessage>(&buf[..len]) {
-                    Ok(m) => m,
-                    Err(_) => continue,
+                let received = {
+                    let Ok(connection) = reliable::connection_id(&buf[..len]) else {
+                        continue;
+                    };
+
+                    let mut channels = server.channels.lock().await;
+                    let channel = channels
+                        .entry(addr)
+                        .or_insert_with(|| ReliableChannel::new(connection));
+                    // same address but a new connection, e.g. a restarted client
+                    if channel.connection() != connection {
+                        *channel = ReliableChannel::new(connection);
+                    }
+
+                    match channel.receive(&buf[..len]) {
+                        Ok(r) => r,
+                        Err(_) => continue,
+                    }
                 };
 
-                server.handle_packet(msg, addr).await;
+                if let Some(ack) = received.ack {
+                    let _ = server.socket.send_to(&ack, addr).await;
+                }
+
+                for payload in received.payloads {
+                    let msg = match deserialize::<ClientMessage>(&payload) {
+                        Ok(m) => m,
+                        Err(_) => continue,
+                    };
+
+                    server.handle_packet(msg, addr).await;
+                }
             }
         });
     }
 
-    async fn handle_packet(self: &Arc<Self>, msg: ClientMessage, addr: SocketAddr) {
-        if let ClientMessage::Hello { player_name } = &msg {
-            let client_id = Uuid::new_v4();
+    pub fn spawn_resend_task(self: &Arc<Self>) {
+        let server = Arc::clone(self);
 
-            let session = ClientSession {
-                client_id,
-                player_name: player_name.clone(),
-                state: ClientState::Menu,
-                addr,
-            };
+        tokio::spawn(async move {
+            loop {
+                sleep(RESEND_INTERVAL / 2).await;
+
+                let mut resends: Vec<(SocketAddr, Vec<u8>)> = Vec::new();
+                let mut unresponsive: Vec<SocketAddr> = Vec::new();
+                {
+                    let mut channels = server.channels.lock().await;
+                    for (addr, channel) in channels.iter_mut() {
+                        match channel.resends() {
+                            Ok(packets) => {
+                                resends.extend(packets.into_iter().map(|packet| (*addr, packet)));
+                            }
+                            Err(_) => unresponsive.push(*addr),
+                        }
+                    }
+                }
+
+                for (addr, packet) in resends {
+                    let _ = server.socket.send_to(&packet, addr).await;
+                }
+
+                for addr in unresponsive {
+                    server.lose_connection(addr).await;
+                }
+            }
+        });
+    }
+
+    pub fn spawn_timeout_task(self: &Arc<Self>) {
+        let server = Arc::clone(self);
+
+        tokio::spawn(async move {
+            loop {
+                sleep(HEARTBEAT_INTERVAL).await;
+
+                // players in a match get some extra time to reconnect
+                let (dropped, timed_out): (Vec<_>, Vec<_>) = {
+                    let sessions = server.sessions.read().await;
+                    sessions
+                        .values()
+                        .filter(|s| s.last_seen.elapsed() > server.client_timeout)
+                        .map(|s| {
+                            let can_resume = matches!(s.state, ClientState::InGame)
+                                && s.last_seen.elapsed()
+                                    <= server.client_timeout + server.reconnect_timeout;
+                            (s.client_id, can_resume)
+                        })
+                        .partition(|(_, can_resume)| *can_resume)
+                };
+
+                for (client_id, _) in dropped {
+                    server.drop_client(client_id).await;
+                }
+
+                for (client_id, _) in timed_out {
+                    server.disconnect_client(client_id, "timed out").await;
+                }
+
+                // connections that never got a session, e.g. rejected hellos
+                let connections = server.connections.read().await;
+                server.channels.lock().await.retain(|addr, channel| {
+                    connections.contains_left(addr) || channel.idle() <= server.client_timeout
+                });
+            }
+        });
+    }
+
+    /// Retries the queues as the allowed rating gap keeps widening
+    pub fn spawn_matchmaking_task(self: &Arc<Self>) {
+        let server = Arc::clone(self);
+
+        tokio::spawn(async move {
+            loop {
+                sleep(MATCHMAKING_INTERVAL).await;
+
+                for mode in &server.modes {
+                    server.try_start_match(*mode).await;
+                }
+            }
+        });
+    }
+
+    /// Sends a control message that must arrive, in order,
+    /// split over several datagrams if it is too large for one
+    async fn send_reliable(&self, addr: SocketAddr, msg: &ServerMessage) -> Result<()> {
+        let packets = {
+            let mut channels = self.channels.lock().await;
+            let channel = channels
+                .get_mut(&addr)
+                .ok_or_else(|| anyhow!("No connection to {addr}"))?;
+            channel.send_split(serialize(msg)?)?
+        };
+        for packet in packets {
+            self.socket.send_to(&packet, addr).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn send_unreliable(&self, addr: SocketAddr, msg: &ServerMessage) -> Result<()> {
+        let packet = {
+            let channels = self.channels.lock().await;
+            let channel = channels
+                .get(&addr)
+                .ok_or_else(|| anyhow!("No connection to {addr}"))?;
+            channel.unreliable(serialize(msg)?)?
+        };
+        self.socket.send_to(&packet, addr).await?;
+
+        Ok(())
+    }
 
-            self.sessions.write().await.insert(client_id, session);
-            self.connections.write().await.insert(addr, client_id);
+    async fn handle_packet(self: &Arc<Self>, msg: ClientMessage, addr: SocketAddr) {
+        if let ClientMessage::Hello(hello) = msg {
+            if let Err(e) = self.handle_hello(hello, addr).await {
+                eprintln!("Failed to welcome {addr}: {e}");
+            }
+            return;
         }
+
         let client_id_optional = {
             let connections = self.connections.read().await;
             connections.get_by_left(&addr).copied()
@@ -95,113 +291,863 @@ impl Server {
             None => return,
         };
 
+        if let Some(session) = self.sessions.write().await.get_mut(&client_id) {
+            session.last_seen = Instant::now();
+        }
+
         match msg {
-            ClientMessage::Hello { player_name: _ } => {} // already handled
+            ClientMessage::Hello(_) => {} // already handled
+            ClientMessage::Heartbeat => {}
+            ClientMessage::Disconnect => {
+                self.disconnect_client(client_id, "disconnected").await;
+            }
             ClientMessage::QueueJoin(mode) => {
                 self.queue_player(client_id, mode).await;
             }
             ClientMessage::QueueLeave => {
                 self.leave_queue(client_id).await;
             }
-            ClientMessage::Input { client_tick, input } => {
-                self.route_input(client_id, client_tick, input).await;
+            ClientMessage::PartyInvite(player_name) => {
+                self.invite_to_party(client_id, &player_name).await;
+            }
+            ClientMessage::PartyAccept => {
+                self.accept_invite(client_id).await;
+            }
+            ClientMessage::PartyDecline => {
+                self.parties.lock().await.decline(client_id);
+            }
+            ClientMessage::PartyLeave => {
+                self.leave_party(client_id).await;
+            }
+            ClientMessage::LobbyCreate(mode) => {
+                self.create_lobby(client_id, mode).await;
+            }
+            ClientMessage::LobbyJoin(code) => {
+                self.join_lobby(client_id, &code).await;
+            }
+            ClientMessage::LobbyPickTeam(team) => {
+                self.pick_team(client_id, team as usize).await;
+            }
+            ClientMessage::LobbyLeave => {
+                self.leave_lobby(client_id).await;
+            }
+            ClientMessage::LobbyStart => {
+                self.start_lobby(client_id).await;
+            }
+            ClientMessage::PickCharacter(character) => {
+                self.pick_character(client_id, character).await;
+            }
+            ClientMessage::MatchHistory => {
+                self.send_history(client_id).await;
+            }
+            ClientMessage::ListGames => {
+                self.send_games(client_id).await;
+            }
+            ClientMessage::Spectate(game_id) => {
+                self.spectate(client_id, &game_id).await;
+            }
+            ClientMessage::StopSpectating => {
+                self.stop_spectating(client_id).await;
+            }
+            ClientMessage::Input {
+                client_tick,
+                view_tick,
+                snapshot_ack,
+                input,
+            } => {
+                self.route_input(GameInput {
+                    client_id,
+                    client_tick,
+                    view_tick,
+                    snapshot_ack,
+                    input,
+                })
+                .await;
             }
         }
     }
 
+    /// Queues the player, or their whole party when they lead one
     async fn queue_player(self: &Arc<Self>, client_id: Uuid, mode: GameMode) {
-        self.leave_queue(client_id).await;
+        if !self.modes.contains(&mode) {
+            return; // not hosted here
+        }
+
+        let group = {
+            let parties = self.parties.lock().await;
+            match parties.party_of(client_id) {
+                Some(party) if party.leader != client_id => Err(PartyError::NotLeader),
+                Some(party) if party.members.len() > mode.team_size => Err(PartyError::TooLarge),
+                _ => Ok(parties.group_of(client_id)),
+            }
+        };
+        let group = match group {
+            Ok(group) => group,
+            Err(e) => {
+                self.send_to_client(client_id, &ServerMessage::PartyError(e))
+                    .await;
+                return;
+            }
+        };
+
+        let busy = {
+            let sessions = self.sessions.read().await;
+            group.iter().filter(|&&id| id != client_id).any(|id| {
+                sessions.get(id).is_none_or(|s| {
+                    matches!(
+                        s.state,
+                        ClientState::InGame | ClientState::InLobby(_) | ClientState::Spectating(_)
+                    )
+                })
+            })
+        };
+        if busy {
+            self.send_to_client(
+                client_id,
+                &ServerMessage::PartyError(PartyError::MemberBusy),
+            )
+            .await;
+            return;
+        }
+
+        self.leave_lobby(client_id).await;
+        self.stop_spectating(client_id).await;
+
+        let rating = {
+            let sessions = self.sessions.read().await;
+            let store = self.store.lock().await;
+            let total: f64 = group
+                .iter()
+                .filter_map(|id| sessions.get(id))
+                .map(|s| store.rating(s.account_id).rating)
+                .sum();
+            total / group.len() as f64
+        };
 
         {
             let mut queues = self.queues.lock().await;
-            match mode {
-                GameMode::Solos => queues.solos.add(client_id),
-                GameMode::Duos => queues.duos.add(client_id),
-            }
+            queues.remove(client_id);
+            let Some(queue) = queues.get_mut(&mode) else {
+                return;
+            };
+            queue.add(group.clone(), rating);
         }
 
         {
             let mut sessions = self.sessions.write().await;
-            if let Some(session) = sessions.get_mut(&client_id) {
-                session.state = ClientState::Queueing(mode.clone());
+            for id in &group {
+                if let Some(session) = sessions.get_mut(id) {
+                    session.state = ClientState::Queueing(mode);
+                }
             }
         }
 
+        for id in &group {
+            self.send_to_client(*id, &ServerMessage::Queued(mode)).await;
+        }
+
         self.try_start_match(mode).await;
     }
 
+    /// Takes the player and the rest of their group out of the queue
     async fn leave_queue(&self, client_id: Uuid) {
-        let mut queues = self.queues.lock().await;
+        let Some(group) = self.queues.lock().await.remove(client_id) else {
+            return;
+        };
+
+        {
+            let mut sessions = self.sessions.write().await;
+            for id in &group {
+                if let Some(session) = sessions.get_mut(id)
+                    && matches!(session.state, ClientState::Queueing(_))
+                {
+                    session.state = ClientState::Menu;
+                }
+            }
+        }
 
-        queues.solos.remove(client_id);
-        queues.duos.remove(client_id);
+        for id in &group {
+            self.send_to_client(*id, &ServerMessage::QueueLeft).await;
+        }
+    }
 
-        let mut sessions = self.sessions.write().await;
-        if let Some(session) = sessions.get_mut(&client_id) {
+    async fn invite_to_party(&self, client_id: Uuid, player_name: &str) {
+        let (invitee, from) = {
+            let sessions = self.sessions.read().await;
+            let invitee = sessions
+                .values()
+                .find(|s| s.player_name == player_name && s.client_id != client_id)
+                .map(|s| s.client_id);
+            let from = sessions.get(&client_id).map(|s| s.player_name.clone());
+            (invitee, from)
+        };
+        let (Some(invitee), Some(from)) = (invitee, from) else {
+            self.send_to_client(
+                client_id,
+                &ServerMessage::PartyError(PartyError::PlayerNotFound),
+            )
+            .await;
+            return;
+        };
+
+        let invited = self.parties.lock().await.invite(client_id, invitee);
+        match invited {
+            Ok(()) => {
+                self.send_to_client(invitee, &ServerMessage::PartyInvite { from })
+                    .await
+            }
+            Err(e) => {
+                self.send_to_client(client_id, &ServerMessage::PartyError(e))
+                    .await
+            }
+        }
+    }
+
+    async fn accept_invite(&self, client_id: Uuid) {
+        // anyone queued with the old or the new party has to queue again
+        let max_size = self.modes.iter().map(|m| m.team_size).max().unwrap_or(1);
+        let (accepted, left_behind) = {
+            let mut parties = self.parties.lock().await;
+            let old = parties.group_of(client_id);
+            let accepted = parties.accept(client_id, max_size);
+            (accepted, old)
+        };
+
+        let party = match accepted {
+            Ok(party) => party,
+            Err(e) => {
+                self.send_to_client(client_id, &ServerMessage::PartyError(e))
+                    .await;
+                return;
+            }
+        };
+
+        self.leave_queue(client_id).await;
+        self.leave_queue(party.leader).await;
+
+        let mut changed = left_behind;
+        changed.extend(party.members);
+        changed.sort();
+        changed.dedup();
+        self.send_party(&changed).await;
+    }
+
+    async fn leave_party(&self, client_id: Uuid) {
+        self.leave_queue(client_id).await;
+
+        let mut changed = self.parties.lock().await.leave(client_id);
+        changed.push(client_id);
+        self.send_party(&changed).await;
+    }
+
+    /// Tells every given player which party they are in now
+    async fn send_party(&self, client_ids: &[Uuid]) {
+        for client_id in client_ids {
+            let party = {
+                let parties = self.parties.lock().await;
+                let sessions = self.sessions.read().await;
+                let name = |id: &Uuid| {
+                    sessions
+                        .get(id)
+                        .map(|s| s.player_name.clone())
+                        .unwrap_or_default()
+                };
+
+                parties.party_of(*client_id).map(|party| NetParty {
+                    leader: name(&party.leader),
+                    members: party.members.iter().map(name).collect(),
+                })
+            };
+
+            self.send_to_client(*client_id, &ServerMessage::Party(party))
+                .await;
+        }
+    }
+
+    async fn create_lobby(&self, client_id: Uuid, mode: GameMode) {
+        if !self.modes.contains(&mode) {
+            self.send_to_client(
+                client_id,
+                &ServerMessage::LobbyError(LobbyError::ModeNotHosted),
+            )
+            .await;
+            return;
+        }
+
+        self.leave_lobby(client_id).await;
+        self.leave_queue(client_id).await;
+        self.stop_spectating(client_id).await;
+
+        let lobby = {
+            let mut lobbies = self.lobbies.lock().await;
+            let code = lobbies.create(mode, client_id);
+            lobbies.get(&code).cloned()
+        };
+        let Some(lobby) = lobby else {
+            return;
+        };
+
+        if let Some(session) = self.sessions.write().await.get_mut(&client_id) {
+            session.state = ClientState::InLobby(lobby.code.clone());
+            println!(
+                "Client '{}' opened lobby {}",
+                session.player_name, lobby.code
+            );
+        }
+        self.send_lobby(&lobby).await;
+    }
+
+    async fn join_lobby(&self, client_id: Uuid, code: &str) {
+        let code = code.trim().to_uppercase();
+        if self.lobby_of(client_id).await.as_deref() == Some(code.as_str()) {
+            return;
+        }
+
+        self.leave_lobby(client_id).await;
+        self.leave_queue(client_id).await;
+        self.stop_spectating(client_id).await;
+
+        let joined = {
+            let mut lobbies = self.lobbies.lock().await;
+            match lobbies.get_mut(&code) {
+                Some(lobby) => lobby.join(client_id).map(|_| lobby.clone()),
+                None => Err(LobbyError::NotFound),
+            }
+        };
+
+        match joined {
+            Ok(lobby) => {
+                if let Some(session) = self.sessions.write().await.get_mut(&client_id) {
+                    session.state = ClientState::InLobby(code);
+                }
+                self.send_lobby(&lobby).await;
+            }
+            Err(e) => {
+                self.send_to_client(client_id, &ServerMessage::LobbyError(e))
+                    .await
+            }
+        }
+    }
+
+    async fn pick_team(&self, client_id: Uuid, team: usize) {
+        let Some(code) = self.lobby_of(client_id).await else {
+            return;
+        };
+
+        let picked = {
+            let mut lobbies = self.lobbies.lock().await;
+            let Some(lobby) = lobbies.get_mut(&code) else {
+                return;
+            };
+            lobby.pick_team(client_id, team).map(|_| lobby.clone())
+        };
+
+        match picked {
+            Ok(lobby) => self.send_lobby(&lobby).await,
+            Err(e) => {
+                self.send_to_client(client_id, &ServerMessage::LobbyError(e))
+                    .await
+            }
+        }
+    }
+
+    async fn leave_lobby(&self, client_id: Uuid) {
+        let Some(_) = self.lobby_of(client_id).await else {
+            return;
+        };
+
+        let remaining = self.lobbies.lock().await.remove(client_id);
+        if let Some(session) = self.sessions.write().await.get_mut(&client_id) {
             session.state = ClientState::Menu;
         }
+
+        if let Some(lobby) = remaining {
+            self.send_lobby(&lobby).await;
+        }
     }
 
-    async fn try_start_match(self: &Arc<Self>, mode: GameMode) {
-        let players;
+    /// Starts the match with the teams as picked in the lobby
+    async fn start_lobby(self: &Arc<Self>, client_id: Uuid) {
+        let Some(code) = self.lobby_of(client_id).await else {
+            return;
+        };
+
+        let teams = {
+            let lobbies = self.lobbies.lock().await;
+            let Some(lobby) = lobbies.get(&code) else {
+                return;
+            };
+
+            if lobby.host == client_id {
+                lobby.ready_teams()
+            } else {
+                Err(LobbyError::NotHost)
+            }
+        };
+
+        match teams {
+            // private matches do not count towards ratings, the lobby
+            // stays open for another try if the match fails to start
+            Ok(teams) => match self.start_game_instance(teams, false).await {
+                Ok(()) => {
+                    self.lobbies.lock().await.close(&code);
+                }
+                Err(e) => eprintln!("Failed to start game: {e}"),
+            },
+            Err(e) => {
+                self.send_to_client(client_id, &ServerMessage::LobbyError(e))
+                    .await
+            }
+        }
+    }
+
+    /// Takes effect from the next match, a match already running keeps the old one
+    async fn pick_character(&self, client_id: Uuid, character: Character) {
+        if let Some(session) = self.sessions.write().await.get_mut(&client_id) {
+            session.character = character;
+        }
+
+        let Some(code) = self.lobby_of(client_id).await else {
+            return;
+        };
+        let lobby = self.lobbies.lock().await.get(&code).cloned();
+        if let Some(lobby) = lobby {
+            self.send_lobby(&lobby).await;
+        }
+    }
+
+    async fn lobby_of(&self, client_id: Uuid) -> Option<String> {
+        match &self.sessions.read().await.get(&client_id)?.state {
+            ClientState::InLobby(code) => Some(code.clone()),
+            _ => None,
+        }
+    }
+
+    /// Sends the current state of the lobby to all of its members
+    async fn send_lobby(&self, lobby: &Lobby) {
+        let msg = {
+            let sessions = self.sessions.read().await;
+            let name = |id: &Uuid| {
+                sessions
+                    .get(id)
+                    .map(|s| s.player_name.clone())
+                    .unwrap_or_default()
+            };
+
+            ServerMessage::Lobby(NetLobby {
+                code: lobby.code.clone(),
+                mode: lobby.mode,
+                host: name(&lobby.host),
+                teams: lobby
+                    .teams
+                    .iter()
+                    .map(|team| team.iter().map(name).collect())
+                    .collect(),
+                unassigned: lobby.unassigned.iter().map(name).collect(),
+                characters: lobby
+                    .members()
+                    .filter_map(|id| sessions.get(id))
+                    .map(|s| (s.player_name.clone(), s.character))
+                    .collect(),
+            })
+        };
+
+        let addrs: Vec<SocketAddr> = {
+            let connections = self.connections.read().await;
+            lobby
+                .members()
+                .filter_map(|id| connections.get_by_right(id).copied())
+                .collect()
+        };
+
+        for addr in addrs {
+            let _ = self.send_reliable(addr, &msg).await;
+        }
+    }
+
+    async fn send_to_client(&self, client_id: Uuid, msg: &ServerMessage) {
+        let addr = self
+            .connections
+            .read()
+            .await
+            .get_by_right(&client_id)
+            .copied();
+        if let Some(addr) = addr {
+            let _ = self.send_reliable(addr, msg).await;
+        }
+    }
+
+    async fn handle_hello(self: &Arc<Self>, hello: Hello, addr: SocketAddr) -> Result<()> {
+        let Hello {
+            protocol_version,
+            build,
+            player_name,
+            character,
+            session_token,
+            account_token,
+        } = hello;
+
+        if protocol_version != PROTOCOL_VERSION {
+            println!("Rejected client '{player_name}' running version {build}");
+            return self
+                .send_reliable(
+                    addr,
+                    &ServerMessage::Rejected(RejectReason::VersionMismatch {
+                        server_version: PROTOCOL_VERSION,
+                    }),
+                )
+                .await;
+        }
+
+        // a restarted client has lost its session token but can still
+        // pick its session back up through the account
+        let session_token = session_token.and_then(|t| Uuid::parse_str(&t).ok());
+        let account_id = self.store.lock().await.account_id(account_token.as_deref());
+        let (resumed, account_in_use) = {
+            let sessions = self.sessions.read().await;
+            // a session still active from another address is not taken over,
+            // e.g. a second client on the same machine sharing the tokens
+            let resumable = |s: &&ClientSession| {
+                s.addr == addr || s.last_seen.elapsed() > HEARTBEAT_INTERVAL * 2
+            };
+            let resumed = sessions
+                .values()
+                .find(|s| Some(s.session_token) == session_token)
+                .filter(resumable)
+                .or_else(|| {
+                    sessions
+                        .values()
+                        .find(|s| Some(s.account_id) == account_id)
+                        .filter(resumable)
+                })
+                .map(|s| s.client_id);
+            let account_in_use = sessions.values().any(|s| Some(s.account_id) == account_id);
+            (resumed, account_in_use)
+        };
+
+        // a new hello from a known address replaces the old session
+        let old_client_id = self.connections.read().await.get_by_left(&addr).copied();
+        if let Some(old_client_id) = old_client_id
+            && Some(old_client_id) != resumed
         {
-            let mut queues = self.queues.lock().await;
-            players = match mode {
-                GameMode::Solos => {
-                    if queues.solos.len() < 2 {
-                        return;
+            self.disconnect_client(old_client_id, "reconnected").await;
+        }
+
+        let client_id = match resumed {
+            Some(client_id) => {
+                self.resume_session(client_id, addr).await;
+                if let Some(session) = self.sessions.write().await.get_mut(&client_id) {
+                    session.character = character;
+                }
+                // a restarted client starts over in the menu unless it was in a match
+                let in_game = self
+                    .sessions
+                    .read()
+                    .await
+                    .get(&client_id)
+                    .is_some_and(|s| matches!(s.state, ClientState::InGame));
+                if !in_game {
+                    self.leave_lobby(client_id).await;
+                    self.leave_queue(client_id).await;
+                    self.stop_spectating(client_id).await;
+                }
+                client_id
+            }
+            None => {
+                let rejection = {
+                    let sessions = self.sessions.read().await;
+                    if player_name.len() > MAX_NAME_LENGTH {
+                        Some(RejectReason::NameTooLong)
+                    } else if account_in_use {
+                        // one account playing twice would be rated twice
+                        Some(RejectReason::AccountInUse)
+                    } else if sessions.values().any(|s| s.player_name == player_name) {
+                        Some(RejectReason::NameTaken)
+                    } else if sessions.len() >= self.max_players {
+                        Some(RejectReason::ServerFull)
+                    } else {
+                        None
                     }
-                    queues.solos.get_and_remove_players(2)
+                };
+                if let Some(reason) = rejection {
+                    println!("Rejected client '{player_name}': {reason}");
+                    return self
+                        .send_reliable(addr, &ServerMessage::Rejected(reason))
+                        .await;
                 }
 
-                GameMode::Duos => {
-                    if queues.duos.len() < 4 {
-                        return;
+                println!("Client '{player_name}' connected running version {build}");
+                let account_id = {
+                    let mut store = self.store.lock().await;
+                    let (account_id, _) = store.login(account_token.as_deref(), &player_name);
+                    let saved = match store.save() {
+                        Ok(write) => self.write_store(store, write).await,
+                        Err(e) => Err(e),
+                    };
+                    if let Err(e) = saved {
+                        eprintln!("Failed to save profiles: {e}");
                     }
-                    queues.duos.get_and_remove_players(4)
-                }
-            };
+                    account_id
+                };
+
+                let client_id = Uuid::new_v4();
+                let session = ClientSession {
+                    client_id,
+                    player_name,
+                    character,
+                    state: ClientState::Menu,
+                    addr,
+                    last_seen: Instant::now(),
+                    session_token: Uuid::new_v4(),
+                    account_id,
+                };
+
+                self.sessions.write().await.insert(client_id, session);
+                self.connections.write().await.insert(addr, client_id);
+                client_id
+            }
+        };
+
+        let (session_token, account_id, in_game) = {
+            let sessions = self.sessions.read().await;
+            let session = sessions
+                .get(&client_id)
+                .ok_or_else(|| anyhow!("Session vanished"))?;
+            (
+                session.session_token,
+                session.account_id,
+                matches!(session.state, ClientState::InGame),
+            )
+        };
+        let account_token = self
+            .store
+            .lock()
+            .await
+            .profile(account_id)
+            .map(|p| p.token)
+            .ok_or_else(|| anyhow!("Profile vanished"))?;
+
+        self.send_reliable(
+            addr,
+            &ServerMessage::Welcome {
+                client_id: client_id.to_string(),
+                session_token: session_token.to_string(),
+                account_token: account_token.to_string(),
+                in_game,
+                server_name: self.name.clone(),
+                tick_rate: TICK_RATE as u32,
+                modes: self.modes.clone(),
+            },
+        )
+        .await?;
+
+        // a restarted client does not know its party yet
+        if self.parties.lock().await.party_of(client_id).is_some() {
+            self.send_party(&[client_id]).await;
+        }
+
+        if in_game {
+            self.rejoin_game(client_id, addr).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves an existing session over to a new connection
+    async fn resume_session(&self, client_id: Uuid, addr: SocketAddr) {
+        let old_addr = {
+            let mut connections = self.connections.write().await;
+            let old = connections.remove_by_right(&client_id);
+            connections.insert(addr, client_id);
+            old.map(|(old_addr, _)| old_addr)
+        };
+
+        if let Some(old_addr) = old_addr
+            && old_addr != addr
+        {
+            self.channels.lock().await.remove(&old_addr);
+        }
+
+        if let Some(session) = self.sessions.write().await.get_mut(&client_id) {
+            session.addr = addr;
+            session.last_seen = Instant::now();
+            println!("Client '{}' resumed their session", session.player_name);
+        }
+    }
+
+    /// Resends the match a resumed client was in, snapshots follow in full
+    async fn rejoin_game(&self, client_id: Uuid, addr: SocketAddr) -> Result<()> {
+        let Some(game) = self
+            .games
+            .read()
+            .await
+            .values()
+            .find(|g| g.players.contains_key(&client_id))
+            .cloned()
+        else {
+            return Ok(());
+        };
+
+        self.send_reliable(
+            addr,
+            &ServerMessage::StartGame {
+                c_player: client_id.to_string(),
+                init_data: game.net_init_data.clone(),
+            },
+        )
+        .await?;
+        let _ = game
+            .connection_tx
+            .send(ConnectionEvent::Resumed(client_id, addr));
+
+        Ok(())
+    }
+
+    /// Tears down a connection whose reliable messages go unacknowledged,
+    /// like a timeout but without waiting for it
+    async fn lose_connection(&self, addr: SocketAddr) {
+        self.channels.lock().await.remove(&addr);
+
+        let client_id = self.connections.read().await.get_by_left(&addr).copied();
+        let Some(client_id) = client_id else {
+            return;
+        };
+        let in_game = self
+            .sessions
+            .read()
+            .await
+            .get(&client_id)
+            .is_some_and(|s| matches!(s.state, ClientState::InGame));
+
+        if in_game {
+            self.drop_client(client_id).await;
+        } else {
+            self.disconnect_client(client_id, "stopped responding")
+                .await;
         }
+    }
+
+    /// Forgets the connection of a client in a match,
+    /// its session is kept so it can be resumed
+    async fn drop_client(&self, client_id: Uuid) {
+        let addr = self.connections.write().await.remove_by_right(&client_id);
+        let Some((addr, _)) = addr else {
+            return;
+        };
+        self.channels.lock().await.remove(&addr);
+
+        if let Some(game) = self
+            .games
+            .read()
+            .await
+            .values()
+            .find(|g| g.players.contains_key(&client_id))
+        {
+            let _ = game.connection_tx.send(ConnectionEvent::Dropped(client_id));
+        }
+
+        if let Some(session) = self.sessions.read().await.get(&client_id) {
+            println!("Client '{}' lost connection", session.player_name);
+        }
+    }
+
+    /// Forgets everything about a client, players in a match are
+    /// removed from it while the rest of the match plays on
+    async fn disconnect_client(&self, client_id: Uuid, reason: &str) {
+        self.leave_lobby(client_id).await;
+        self.leave_queue(client_id).await;
+        self.stop_spectating(client_id).await;
+        let left_behind = self.parties.lock().await.remove(client_id);
+        self.send_party(&left_behind).await;
+
+        {
+            let games = self.games.read().await;
+            if let Some(game) = games.values().find(|g| g.players.contains_key(&client_id)) {
+                let _ = game.connection_tx.send(ConnectionEvent::Left(client_id));
+            }
+        }
+
+        let session = self.sessions.write().await.remove(&client_id);
+        let addr = self.connections.write().await.remove_by_right(&client_id);
+        if let Some((addr, _)) = addr {
+            self.channels.lock().await.remove(&addr);
+        }
+
+        if let Some(session) = session {
+            println!("Client '{}' {reason}", session.player_name);
+        }
+    }
 
-        match self.start_game_instance(players, mode).await {
-            Ok(_) => {}
-            Err(e) => eprintln!("Failed to start game: {e}"),
+    async fn try_start_match(self: &Arc<Self>, mode: GameMode) {
+        let matched = {
+            let mut queues = self.queues.lock().await;
+            let Some(matched) = queues
+                .get_mut(&mode)
+                .and_then(|q| q.take_match(mode, self.rating_gap))
+            else {
+                return;
+            };
+            matched
         };
+
+        if let Err(e) = self.start_game_instance(matched.teams.clone(), true).await {
+            eprintln!("Failed to start game: {e}");
+
+            // whoever is still queueing waits for the next try
+            let sessions = self.sessions.read().await;
+            if let Some(queue) = self.queues.lock().await.get_mut(&mode) {
+                queue.put_back(matched, |id| {
+                    sessions
+                        .get(id)
+                        .is_some_and(|s| matches!(s.state, ClientState::Queueing(m) if m == mode))
+                });
+            }
+        }
     }
 
     async fn start_game_instance(
         self: &Arc<Self>,
-        player_ids: Vec<Uuid>,
-        mode: GameMode,
+        teams: Vec<Vec<Uuid>>,
+        rated: bool,
     ) -> Result<()> {
-        let teams = match mode {
-            GameMode::Solos => [
-                vec![player_ids[0].to_string()],
-                vec![player_ids[1].to_string()],
-            ],
-            GameMode::Duos => [
-                vec![player_ids[0].to_string(), player_ids[1].to_string()],
-                vec![player_ids[2].to_string(), player_ids[3].to_string()],
-            ],
-        };
+        let player_ids: Vec<Uuid> = teams.iter().flatten().copied().collect();
 
         let mut players = HashMap::new();
+        let mut accounts = HashMap::new();
         {
+            // a player may have timed out since the match was put together
             let sessions = self.sessions.read().await;
-            for player_id in &player_ids {
+            for (slot, player_id) in player_ids.iter().enumerate() {
+                let session = sessions
+                    .get(player_id)
+                    .ok_or_else(|| anyhow!("Player {player_id} left before the match started"))?;
+                accounts.insert(*player_id, session.account_id);
                 players.insert(
                     player_id.to_string(),
                     InitPlayerData {
-                        name: sessions.get(player_id).unwrap().player_name.clone(),
+                        name: session.player_name.clone(),
+                        slot: slot as u8,
+                        character: session.character,
                     },
                 );
             }
         }
-        let init_data = InitData { players, teams };
+        let init_data = InitData {
+            players,
+            teams: teams
+                .iter()
+                .map(|team| team.iter().map(Uuid::to_string).collect())
+                .collect(),
+            map: self.map.clone(),
+            balance: self.balance.clone(),
+        };
 
-        let gs = init_data.to_game_state();
+        let gs = init_data.to_game_state()?;
+        let slots = init_data.player_slots()?;
+        let net_init_data = NetInitData::new(&init_data, &self.map_file)?;
 
         let (input_tx, input_rx) = unbounded_channel::<GameInput>();
+        let (connection_tx, connection_rx) = unbounded_channel::<ConnectionEvent>();
         let game_id = Uuid::new_v4();
         {
             let mut sessions = self.sessions.write().await;
@@ -214,61 +1160,15 @@ impl Server {
         }
 
         let mut players = HashMap::new();
-        match mode {
-            GameMode::Solos => {
+        for (team_id, team) in teams.iter().enumerate() {
+            for (player_id, client_id) in team.iter().enumerate() {
                 players.insert(
-                    player_ids[0],
+                    *client_id,
                     PlayerSlot {
-                        team_id: 0,
-                        player_id: 0,
-                        client_id: player_ids[0],
-                    },
-                );
-
-                players.insert(
-                    player_ids[1],
-                    PlayerSlot {
-                        team_id: 1,
-                        player_id: 0,
-                        client_id: player_ids[1],
-                    },
-                );
-            }
-
-            GameMode::Duos => {
-                players.insert(
-                    player_ids[0],
-                    PlayerSlot {
-                        team_id: 0,
-                        player_id: 0,
-                        client_id: player_ids[0],
-                    },
-                );
-
-                players.insert(
-                    player_ids[1],
-                    PlayerSlot {
-                        team_id: 0,
-                        player_id: 1,
-                        client_id: player_ids[1],
-                    },
-                );
-
-                players.insert(
-                    player_ids[2],
-                    PlayerSlot {
-                        team_id: 1,
-                        player_id: 0,
-                        client_id: player_ids[2],
-                    },
-                );
-
-                players.insert(
-                    player_ids[3],
-                    PlayerSlot {
-                        team_id: 1,
-                        player_id: 1,
-                        client_id: player_ids[3],
+                        team_id,
+                        player_id,
+                        client_id: *client_id,
+                        account_id: accounts.get(client_id).copied().unwrap_or_default(),
                     },
                 );
             }
@@ -278,6 +1178,10 @@ impl Server {
             game_id,
             players: players.clone(),
             input_tx,
+            connection_tx,
+            init_data: init_data.clone(),
+            net_init_data: net_init_data.clone(),
+            rated,
         };
         {
             self.games.write().await.insert(game_id, handle.clone());
@@ -291,27 +1195,32 @@ impl Server {
                 None => continue,
             };
 
-            self.socket
-                .send_to(
-                    &serialize(&ServerMessage::StartGame {
+            // the match goes on either way, a player who missed it gets it
+            // resent when they reconnect
+            if let Err(e) = self
+                .send_reliable(
+                    *addr,
+                    &ServerMessage::StartGame {
                         c_player: uuid.to_string(),
-                        init_data: init_data.clone(),
-                    })?,
-                    addr,
+                        init_data: net_init_data.clone(),
+                    },
                 )
-                .await?;
+                .await
+            {
+                eprintln!("Failed to send the match to {addr}: {e}");
+            }
         }
 
-        let player_addrs: Vec<SocketAddr> = players
-            .iter()
-            .filter_map(|(id, _)| connections.get_by_right(id).copied())
+        let player_addrs: Vec<(Uuid, SocketAddr)> = players
+            .keys()
+            .filter_map(|id| Some((*id, *connections.get_by_right(id)?)))
             .collect();
         let server = Arc::clone(self);
         tokio::spawn(async move {
             println!("Starting game with id '{game_id}'");
 
             if let Err(e) = server
-                .handle_game(gs, input_rx, players, player_addrs, game_id)
+                .handle_game(gs, slots, input_rx, connection_rx, player_addrs, game_id)
                 .await
             {
                 eprintln!("Game ' {game_id}' crashed: {e}");
@@ -321,53 +1230,172 @@ impl Server {
         Ok(())
     }
 
-    async fn route_input(&self, client_id: Uuid, client_tick: u64, input: PlayerInput) {
+    async fn route_input(&self, input: GameInput) {
+        // spectators only send input to acknowledge snapshots
+        let spectating = match self.sessions.read().await.get(&input.client_id) {
+            Some(session) => match session.state {
+                ClientState::Spectating(game_id) => Some(game_id),
+                _ => None,
+            },
+            None => None,
+        };
+
         let games = self.games.read().await;
 
-        let game = games.values().find(|g| g.players.contains_key(&client_id));
+        let game = match spectating {
+            Some(game_id) => games.get(&game_id),
+            None => games
+                .values()
+                .find(|g| g.players.contains_key(&input.client_id)),
+        };
 
         let Some(game) = game else {
             return;
         };
 
-        let _ = game.input_tx.send(GameInput {
-            client_id,
-            client_tick,
-            input,
-        });
+        let _ = game.input_tx.send(input);
     }
 
     async fn handle_game(
         self: Arc<Self>,
         mut gs: GameState,
+        slots: PlayerSlots,
         mut input_rx: UnboundedReceiver<GameInput>,
-        players: HashMap<Uuid, PlayerSlot>,
-        player_addrs: Vec<SocketAddr>,
+        mut connection_rx: UnboundedReceiver<ConnectionEvent>,
+        mut player_addrs: Vec<(Uuid, SocketAddr)>,
         game_id: Uuid,
     ) -> Result<()> {
         let mut tick: u64 = 0;
+        let mut history = StateHistory::new(self.max_rewind_ticks);
+        let mut input_queues: HashMap<Uuid, InputQueue> = HashMap::new();
+        let mut client_ticks: HashMap<Uuid, u64> = HashMap::new();
+        let mut view_ticks: HashMap<Uuid, u64> = HashMap::new();
+        let mut snapshot_acks: HashMap<Uuid, u64> = HashMap::new();
+        let mut baselines = SnapshotBaselines::default();
+        let mut dropped: HashSet<Uuid> = HashSet::new();
+        let mut spectators: Vec<(Uuid, SocketAddr)> = Vec::new();
+        let mut recorder = if self.record_replays {
+            self.games.read().await.get(&game_id).and_then(|handle| {
+                ReplayRecorder::new(handle.init_data.clone(), self.max_rewind_ticks)
+                    .map_err(|e| eprintln!("Unable to record game {game_id}: {e}"))
+                    .ok()
+            })
+        } else {
+            None
+        };
 
         loop {
             let frame_start = Instant::now();
 
             while let Ok(input) = input_rx.try_recv() {
-                let Some(player) = gs.players.get_mut(&input.client_id) else {
+                if spectators.iter().any(|(id, _)| *id == input.client_id) {
+                    if let Some(ack) = input.snapshot_ack {
+                        snapshot_acks.insert(input.client_id, ack);
+                    }
+                    continue;
+                }
+
+                if !gs.players.contains_key(&input.client_id) {
+                    continue;
+                }
+                if let Some(ack) = input.snapshot_ack {
+                    snapshot_acks.insert(input.client_id, ack);
+                }
+                input_queues.entry(input.client_id).or_default().push(input);
+            }
+
+            while let Ok(event) = connection_rx.try_recv() {
+                match event {
+                    ConnectionEvent::Dropped(client_id) => {
+                        gs.set_disconnected(&client_id, true);
+                        if let Some(recorder) = &mut recorder {
+                            recorder.disconnected(&client_id);
+                        }
+                        player_addrs.retain(|(id, _)| *id != client_id);
+                        input_queues.remove(&client_id);
+                        view_ticks.remove(&client_id);
+                        dropped.insert(client_id);
+                    }
+                    ConnectionEvent::Resumed(client_id, addr) => {
+                        gs.set_disconnected(&client_id, false);
+                        if let Some(recorder) = &mut recorder {
+                            recorder.reconnected(&client_id);
+                        }
+                        player_addrs.retain(|(id, _)| *id != client_id);
+                        player_addrs.push((client_id, addr));
+                        dropped.remove(&client_id);
+                        // the client starts over, next snapshot is sent in full
+                        input_queues.remove(&client_id);
+                        client_ticks.remove(&client_id);
+                        view_ticks.remove(&client_id);
+                        snapshot_acks.remove(&client_id);
+                    }
+                    ConnectionEvent::Left(client_id) => {
+                        gs.forfeit_player(&client_id);
+                        if let Some(recorder) = &mut recorder {
+                            recorder.forfeited(&client_id);
+                        }
+                        player_addrs.retain(|(id, _)| *id != client_id);
+                        input_queues.remove(&client_id);
+                        view_ticks.remove(&client_id);
+                        dropped.remove(&client_id);
+                    }
+                    ConnectionEvent::SpectatorJoined(client_id, addr) => {
+                        spectators.retain(|(id, _)| *id != client_id);
+                        spectators.push((client_id, addr));
+                        // the first snapshot is sent in full
+                        snapshot_acks.remove(&client_id);
+                    }
+                    ConnectionEvent::SpectatorLeft(client_id) => {
+                        spectators.retain(|(id, _)| *id != client_id);
+                        snapshot_acks.remove(&client_id);
+                    }
+                }
+            }
+
+            // nobody left to play for
+            if player_addrs.is_empty() && dropped.is_empty() {
+                break;
+            }
+
+            // one input per player and tick, acknowledged once simulated,
+            // a player without a new one keeps the last
+            for (client_id, queue) in &mut input_queues {
+                let Some(input) = queue.pop() else {
                     continue;
                 };
-                player.input = input.input;
+                gs.apply_input(client_id, input.input);
+                client_ticks.insert(*client_id, input.client_tick);
+                view_ticks.insert(*client_id, input.view_tick);
             }
 
-            gs.update(FIXED_DT);
+            // resolve each client's attacks against what they saw
+            let views: HashMap<Uuid, &GameState> = view_ticks
+                .iter()
+                .filter_map(|(id, &view_tick)| Some((*id, history.view(view_tick, tick)?)))
+                .collect();
+            if let Some(recorder) = &mut recorder {
+                recorder.record_tick(&gs, &view_ticks);
+            }
+            gs.update_with_views(FIXED_DT, &views);
+            history.push(tick, &gs);
+            if let Some(recorder) = &mut recorder {
+                recorder.record_checksum(tick, &gs);
+            }
 
-            let snapshot = net_game_state::to_net(&gs);
-            let msg = ServerMessage::Snapshot {
-                server_tick: tick,
-                server_state: snapshot,
-            };
-            let bytes = serialize(&msg)?;
-            for addr in &player_addrs {
-                let _ = self.socket.send_to(&bytes, addr).await;
+            let snapshot = net_game_state::to_net(&gs, tick, &slots);
+            for (client_id, addr) in player_addrs.iter().chain(&spectators) {
+                let msg = ServerMessage::Snapshot {
+                    server_tick: tick,
+                    ack_tick: client_ticks.get(client_id).copied().unwrap_or(0),
+                    server_state: baselines
+                        .encode(snapshot_acks.get(client_id).copied(), &snapshot),
+                };
+                if let Err(e) = self.send_unreliable(*addr, &msg).await {
+                    eprintln!("Failed to send a snapshot to {addr}: {e}");
+                }
             }
+            baselines.push(snapshot);
 
             tick += 1;
 
@@ -378,24 +1406,258 @@ impl Server {
             }
         }
 
-        self.games.write().await.remove(&game_id);
+        if let Some(recorder) = recorder {
+            let saved = data_file(&format!("replays/{game_id}.replay"))
+                .and_then(|path| recorder.finish().save(&path));
+            if let Err(e) = saved {
+                eprintln!("Failed to save replay: {e}");
+            }
+        }
+
+        let handle = self.games.write().await.remove(&game_id);
+        if let Some(handle) = handle
+            && let Some(outcome) = gs.outcome
+        {
+            self.record_match(&handle, &gs, outcome, tick).await;
+        }
 
         let mut sessions = self.sessions.write().await;
 
-        let msg = ServerMessage::EndGame;
-        let bytes = serialize(&msg)?;
-        for addr in &player_addrs {
-            let _ = self.socket.send_to(&bytes, addr).await;
+        for (_, addr) in player_addrs.iter().chain(&spectators) {
+            let _ = self.send_reliable(*addr, &ServerMessage::EndGame).await;
         }
 
-        for client_id in players.keys() {
+        for client_id in gs.players.keys() {
             if let Some(session) = sessions.get_mut(client_id) {
                 session.state = ClientState::Menu;
             }
         }
+        for session in sessions.values_mut() {
+            if matches!(session.state, ClientState::Spectating(id) if id == game_id) {
+                session.state = ClientState::Menu;
+            }
+        }
 
         Ok(())
     }
+
+    /// Stores the finished match, updating the profiles and, if it was
+    /// rated, the ratings of everyone who played
+    async fn record_match(
+        &self,
+        handle: &GameHandle,
+        gs: &GameState,
+        outcome: Outcome,
+        ticks: u64,
+    ) {
+        let players = gs
+            .teams
+            .iter()
+            .enumerate()
+            .flat_map(|(team, ids)| ids.iter().map(move |id| (team, id)))
+            .filter_map(|(team, id)| {
+                let slot = handle.players.get(id)?;
+                let player = gs.players.get(id)?;
+                Some(MatchPlayer {
+                    account_id: slot.account_id.to_string(),
+                    name: handle.init_data.players.get(&id.to_string())?.name.clone(),
+                    team,
+                    character: player.character,
+                    lives_left: player.combat.lives,
+                    stats: player.stats,
+                    rating: None,
+                })
+            })
+            .collect();
+
+        let record = MatchRecord {
+            id: handle.game_id.to_string(),
+            finished_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            duration: ticks as f32 * FIXED_DT,
+            mode: handle.init_data.mode(),
+            map: handle.init_data.map.name().to_string(),
+            balance: handle.init_data.balance.name.clone(),
+            rated: handle.rated,
+            winner: match outcome {
+                Outcome::Win(team_idx) => Some(team_idx),
+                Outcome::Draw => None,
+            },
+            players,
+        };
+
+        let mut store = self.store.lock().await;
+        let recorded = match store.record_match(record) {
+            Ok((record, write)) => self.write_store(store, write).await.map(|()| record),
+            Err(e) => Err(e),
+        };
+        match recorded {
+            Ok(record) => {
+                for player in &record.players {
+                    if let Some(rating) = player.rating {
+                        println!("Rating of '{}' is now {rating:.0}", player.name);
+                    }
+                }
+            }
+            Err(e) => eprintln!("Failed to save match: {e}"),
+        }
+    }
+
+    /// Writes store changes on a blocking thread, letting go of the store
+    /// so nobody waits on the disk
+    async fn write_store(&self, store: MutexGuard<'_, Store>, write: StoreWrite) -> Result<()> {
+        // taken before the store is let go, so writes land in the order made
+        let _files = self.store_files.lock().await;
+        drop(store);
+
+        tokio::task::spawn_blocking(move || write.write()).await?
+    }
+
+    /// Sends the client the most recent matches of its account
+    async fn send_history(&self, client_id: Uuid) {
+        let Some(account_id) = self
+            .sessions
+            .read()
+            .await
+            .get(&client_id)
+            .map(|s| s.account_id)
+        else {
+            return;
+        };
+
+        let matches: Vec<MatchRecord> = self
+            .store
+            .lock()
+            .await
+            .history(account_id, HISTORY_LENGTH)
+            .into_iter()
+            .cloned()
+            .collect();
+
+        self.send_to_client(
+            client_id,
+            &ServerMessage::MatchHistory {
+                account_id: account_id.to_string(),
+                matches: matches.len() as u32,
+            },
+        )
+        .await;
+        for record in matches {
+            self.send_to_client(client_id, &ServerMessage::HistoryEntry(record))
+                .await;
+        }
+    }
+
+    /// Sends the client the running matches it can spectate, oldest first
+    async fn send_games(&self, client_id: Uuid) {
+        let mut games: Vec<NetGame> = {
+            let games = self.games.read().await;
+            let sessions = self.sessions.read().await;
+            games
+                .values()
+                .map(|game| {
+                    let name = |id: &String| {
+                        game.init_data
+                            .players
+                            .get(id)
+                            .map_or_else(|| id.clone(), |p| p.name.clone())
+                    };
+                    NetGame {
+                        id: game.game_id.to_string(),
+                        mode: game.init_data.mode(),
+                        map: game.init_data.map.name().to_string(),
+                        teams: game
+                            .init_data
+                            .teams
+                            .iter()
+                            .map(|team| team.iter().map(name).collect())
+                            .collect(),
+                        spectators: sessions
+                            .values()
+                            .filter(|s| {
+                                matches!(s.state, ClientState::Spectating(id) if id == game.game_id)
+                            })
+                            .count() as u32,
+                    }
+                })
+                .collect()
+        };
+        games.sort_by(|a, b| a.id.cmp(&b.id));
+        games.truncate(MAX_LISTED_GAMES);
+
+        self.send_to_client(client_id, &ServerMessage::Games(games))
+            .await;
+    }
+
+    /// Starts streaming a running match to the client, players cannot spectate
+    async fn spectate(&self, client_id: Uuid, game_id: &str) {
+        let in_game = self
+            .sessions
+            .read()
+            .await
+            .get(&client_id)
+            .is_none_or(|s| matches!(s.state, ClientState::InGame));
+        if in_game {
+            return;
+        }
+
+        let game = {
+            let games = self.games.read().await;
+            Uuid::parse_str(game_id)
+                .ok()
+                .and_then(|id| games.get(&id))
+                .map(|g| (g.game_id, g.net_init_data.clone(), g.connection_tx.clone()))
+        };
+        let addr = self
+            .connections
+            .read()
+            .await
+            .get_by_right(&client_id)
+            .copied();
+        let (Some((game_id, init_data, connection_tx)), Some(addr)) = (game, addr) else {
+            // the match has probably ended, show what is running instead
+            self.send_games(client_id).await;
+            return;
+        };
+
+        self.stop_spectating(client_id).await;
+        self.leave_lobby(client_id).await;
+        self.leave_queue(client_id).await;
+
+        let _ = self
+            .send_reliable(addr, &ServerMessage::Spectating { init_data })
+            .await;
+        if let Some(session) = self.sessions.write().await.get_mut(&client_id) {
+            session.state = ClientState::Spectating(game_id);
+        }
+        let _ = connection_tx.send(ConnectionEvent::SpectatorJoined(client_id, addr));
+
+        println!("Client {client_id} is spectating game {game_id}");
+    }
+
+    /// Stops streaming a match to the client and sends it back to the menu
+    async fn stop_spectating(&self, client_id: Uuid) {
+        let game_id = {
+            let mut sessions = self.sessions.write().await;
+            let Some(session) = sessions.get_mut(&client_id) else {
+                return;
+            };
+            let ClientState::Spectating(game_id) = session.state else {
+                return;
+            };
+            session.state = ClientState::Menu;
+            game_id
+        };
+
+        if let Some(game) = self.games.read().await.get(&game_id) {
+            let _ = game
+                .connection_tx
+                .send(ConnectionEvent::SpectatorLeft(client_id));
+        }
+        self.send_to_client(client_id, &ServerMessage::EndGame)
+            .await;
+    }
 }
 
 async fn sleep_until_next_tick(frame_start: Instant) {
@@ -412,9 +1674,10 @@ async fn sleep_until_next_tick(frame_start: Instant) {
 async fn main() -> Result<()> {
     let config = Config::get()?;
 
-    let server = Server::new(Arc::new(
-        UdpSocket::bind(format!("{}:{}", config.serverip(), config.serverport())).await?,
-    ));
+    let server = Server::new(
+        Arc::new(UdpSocket::bind(format!("{}:{}", config.serverip(), config.serverport())).await?),
+        &config,
+    )?;
 
     tokio::select! {
         _ = server.run() => {}
@@ -0,0 +1,9 @@
+use anyhow::{Result, anyhow};
+use std::path::PathBuf;
+
+/// Path of a file the game writes, kept in the user data directory
+/// (usually ~/.local/share/platformer)
+pub fn data_file(filename: &str) -> Result<PathBuf> {
+    let data_dir = dirs::data_dir().ok_or_else(|| anyhow!("No user data directory"))?;
+    Ok(data_dir.join("platformer").join(filename))
+}
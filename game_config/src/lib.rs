@@ -1,2 +1,4 @@
+pub mod data;
 pub mod read;
+pub mod session;
 mod utils;
@@ -1,7 +1,12 @@
 use crate::utils::{find_resource_path, load_resource_bytes};
-use anyhow::Result;
+use anyhow::{Result, anyhow};
+use foundation::GameMode;
 use foundation::color::Color;
 use serde::Deserialize;
+use simulation::balance::Balance;
+use simulation::map::Map;
+use simulation::{Action, Character};
+use std::collections::HashMap;
 use toml;
 
 #[derive(Clone, Deserialize)]
@@ -10,6 +15,9 @@ pub struct Config {
     teams: Teams,
     appearance: Appearance,
     camera: Camera,
+    // key names for each action
+    controls: HashMap<Action, Vec<String>>,
+    gamepad: GamepadConfig,
     client: ClientConfig,
     server: ServerConfig,
 }
@@ -17,12 +25,15 @@ pub struct Config {
 #[derive(Clone, Deserialize)]
 struct Player {
     name: String,
+    character: Character,
 }
 
 #[derive(Clone, Deserialize)]
 struct Teams {
     team_one_color: Color,
     team_two_color: Color,
+    team_three_color: Color,
+    team_four_color: Color,
 }
 
 #[derive(Clone, Deserialize)]
@@ -40,6 +51,14 @@ struct Camera {
     player_name_above: bool,
 }
 
+#[derive(Clone, Deserialize)]
+struct GamepadConfig {
+    deadzone: f32,
+    with_keyboard: bool,
+    // button names for each action
+    buttons: HashMap<Action, Vec<String>>,
+}
+
 #[derive(Clone, Deserialize)]
 struct ClientConfig {
     ip: String,
@@ -50,7 +69,18 @@ struct ClientConfig {
 struct ServerConfig {
     ip: String,
     port: String,
+    name: String,
+    max_players: usize,
     team_size: usize,
+    teams: usize,
+    map: String,
+    balance: String,
+    max_rewind: f32,
+    client_timeout: f32,
+    reconnect_timeout: f32,
+    rating_gap: f32,
+    rating_gap_growth: f32,
+    record_replays: bool,
 }
 
 impl Config {
@@ -66,13 +96,20 @@ impl Config {
     }
 
     #[must_use]
-    pub fn team_one_color(&self) -> Color {
-        self.teams.team_one_color.clone()
+    pub fn character(&self) -> Character {
+        self.player.character
     }
 
     #[must_use]
-    pub fn team_two_color(&self) -> Color {
-        self.teams.team_two_color.clone()
+    pub fn team_color(&self, team_idx: usize) -> Color {
+        let colors = [
+            &self.teams.team_one_color,
+            &self.teams.team_two_color,
+            &self.teams.team_three_color,
+            &self.teams.team_four_color,
+        ];
+
+        colors[team_idx % colors.len()].clone()
     }
 
     pub fn background_image(&self) -> Result<Vec<u8>> {
@@ -97,9 +134,79 @@ impl Config {
         &self.server.port
     }
 
+    /// Map file the server hosts, validated for its mode
+    pub fn map(&self) -> Result<Map> {
+        load_map(&self.server.map, self.mode()?)
+    }
+
+    pub fn balance(&self) -> Result<Balance> {
+        load_balance(&self.server.balance)
+    }
+
+    /// Name of the map file, without the extension
+    #[must_use]
+    pub fn map_file(&self) -> &str {
+        &self.server.map
+    }
+
+    /// Mode the server hosts, from `teams` and `team_size`
+    pub fn mode(&self) -> Result<GameMode> {
+        if self.server.teams < 2 || self.server.team_size < 1 {
+            return Err(anyhow!(
+                "A match needs at least two teams of at least one player"
+            ));
+        }
+
+        let mode = GameMode::new(self.server.teams, self.server.team_size);
+        if mode.players() > GameMode::MAX_PLAYERS {
+            return Err(anyhow!(
+                "A match can have at most {} players, {mode} has {}",
+                GameMode::MAX_PLAYERS,
+                mode.players()
+            ));
+        }
+
+        Ok(mode)
+    }
+
     #[must_use]
-    pub fn team_size(&self) -> usize {
-        self.server.team_size
+    pub fn max_rewind(&self) -> f32 {
+        self.server.max_rewind
+    }
+
+    #[must_use]
+    pub fn client_timeout(&self) -> f32 {
+        self.server.client_timeout
+    }
+
+    #[must_use]
+    pub fn servername(&self) -> &str {
+        &self.server.name
+    }
+
+    #[must_use]
+    pub fn max_players(&self) -> usize {
+        self.server.max_players
+    }
+
+    #[must_use]
+    pub fn reconnect_timeout(&self) -> f32 {
+        self.server.reconnect_timeout
+    }
+
+    #[must_use]
+    pub fn rating_gap(&self) -> f32 {
+        self.server.rating_gap
+    }
+
+    #[must_use]
+    pub fn rating_gap_growth(&self) -> f32 {
+        self.server.rating_gap_growth
+    }
+
+    #[must_use]
+    pub fn record_replays(&self) -> bool {
+        self.server.record_replays
     }
 
     #[must_use]
@@ -146,4 +253,95 @@ impl Config {
     pub fn player_name_above(&self) -> bool {
         self.camera.player_name_above
     }
+
+    #[must_use]
+    pub fn controls(&self) -> &HashMap<Action, Vec<String>> {
+        &self.controls
+    }
+
+    #[must_use]
+    pub fn gamepad_buttons(&self) -> &HashMap<Action, Vec<String>> {
+        &self.gamepad.buttons
+    }
+
+    #[must_use]
+    pub fn gamepad_deadzone(&self) -> f32 {
+        self.gamepad.deadzone
+    }
+
+    #[must_use]
+    pub fn gamepad_with_keyboard(&self) -> bool {
+        self.gamepad.with_keyboard
+    }
+}
+
+/// Loads `assets/maps/<name>.toml`, fails when it cannot hold `mode`
+pub fn load_map(name: &str, mode: GameMode) -> Result<Map> {
+    let filename = format!("assets/maps/{name}.toml");
+    let map: Map = toml::from_slice(&load_resource_bytes(&filename)?)
+        .map_err(|e| anyhow!("Invalid map file '{filename}': {e}"))?;
+
+    if map.platforms().is_empty() {
+        return Err(anyhow!("Map '{}' has no platforms", map.name()));
+    }
+
+    for team in 0..mode.teams {
+        if map
+            .spawn_position(team, mode.team_size.saturating_sub(1))
+            .is_none()
+        {
+            return Err(anyhow!(
+                "Map '{}' needs at least {} spawn points for team {} to play {}",
+                map.name(),
+                mode.team_size,
+                team + 1,
+                mode
+            ));
+        }
+    }
+
+    Ok(map)
+}
+
+/// Loads `assets/balance/<name>.toml`
+pub fn load_balance(name: &str) -> Result<Balance> {
+    let filename = format!("assets/balance/{name}.toml");
+    let balance: Balance = toml::from_slice(&load_resource_bytes(&filename)?)
+        .map_err(|e| anyhow!("Invalid balance file '{filename}': {e}"))?;
+
+    for character in Character::ALL {
+        let properties = balance.character(character);
+        let movement = &properties.movement;
+        if movement.size <= 0.0 || movement.weight <= 0.0 {
+            return Err(anyhow!(
+                "Balance '{}' needs a positive size and weight for {character}",
+                balance.name
+            ));
+        }
+
+        for (name, attack) in properties.attacks() {
+            if attack.active <= 0.0
+                || attack.startup < 0.0
+                || attack.recovery < 0.0
+                || attack.frame_count == 0
+            {
+                return Err(anyhow!(
+                    "Balance '{}' needs a positive active time and frame count, and no negative startup or recovery, for the {name} attack of {character}",
+                    balance.name
+                ));
+            }
+
+            if let Some(projectile) = &attack.projectile {
+                // dashes and slams move the character, they can't be fired off
+                if !matches!(name, "light" | "normal") || projectile.speed <= 0.0 {
+                    return Err(anyhow!(
+                        "Balance '{}' can only fire the light and normal attacks, at a positive speed, but the {name} attack of {character} is a projectile",
+                        balance.name
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(balance)
 }
@@ -0,0 +1,40 @@
+use crate::data::data_file;
+use anyhow::{Result, anyhow};
+use std::fs;
+
+// kept in the user data directory so a restarted client can resume its match
+const SESSION_FILE: &str = "session_token";
+// identifies the player's account on servers, never deleted by the game
+const ACCOUNT_FILE: &str = "account_token";
+
+#[must_use]
+pub fn load_session_token() -> Option<String> {
+    load_token(SESSION_FILE)
+}
+
+pub fn save_session_token(token: &str) -> Result<()> {
+    save_token(SESSION_FILE, token)
+}
+
+#[must_use]
+pub fn load_account_token() -> Option<String> {
+    load_token(ACCOUNT_FILE)
+}
+
+pub fn save_account_token(token: &str) -> Result<()> {
+    save_token(ACCOUNT_FILE, token)
+}
+
+fn load_token(filename: &str) -> Option<String> {
+    let token = fs::read_to_string(data_file(filename).ok()?).ok()?;
+    Some(token.trim().to_string())
+}
+
+fn save_token(filename: &str, token: &str) -> Result<()> {
+    let path = data_file(filename)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(&path, token).map_err(|e| anyhow!("Failed to write '{}': {}", path.display(), e))
+}
@@ -1,3 +0,0 @@
-pub const TEAM_ONE_START_POS: [f32; 2] = [820.0, 470.0];
-pub const TEAM_TWO_START_POS: [f32; 2] = [1160.0, 470.0];
-pub const DUO_OFFSET: f32 = 50.0;
@@ -0,0 +1,388 @@
+use crate::{
+    net_platform::NetPlatform,
+    net_player::{NetCombat, NetPhysics, NetPlayer, NetStatus},
+    net_projectile::NetProjectile,
+    net_server::NetSnapshot,
+};
+use std::collections::VecDeque;
+use wincode::{SchemaRead, SchemaWrite};
+
+// about half a second of snapshots
+const BASELINE_HISTORY_SIZE: usize = 32;
+
+/// Player groups that did not change since the baseline are `None`
+#[derive(SchemaWrite, SchemaRead, Clone)]
+pub struct NetPlayerDelta {
+    pub slot: u8,
+    pub physics: Option<NetPhysics>,
+    pub combat: Option<NetCombat>,
+    pub status: Option<NetStatus>,
+    pub cooldowns: Option<[u16; 4]>,
+}
+
+/// Snapshot encoded against the last snapshot the client acknowledged.
+/// Without a baseline it holds the full state.
+#[derive(SchemaWrite, SchemaRead, Clone)]
+pub struct NetSnapshotDelta {
+    pub baseline: Option<u64>,
+    // see `net_game_state::outcome_to_net`
+    pub outcome: u8,
+    // only players and platforms that changed
+    pub players: Vec<NetPlayerDelta>,
+    pub platforms: Vec<NetPlatform>,
+    // always all of them, they rarely stay put between snapshots
+    pub projectiles: Vec<NetProjectile>,
+}
+
+/// Recently sent or received snapshots that deltas can be built on
+pub struct SnapshotBaselines {
+    snapshots: VecDeque<NetSnapshot>,
+    capacity: usize,
+}
+
+impl Default for SnapshotBaselines {
+    fn default() -> Self {
+        Self {
+            snapshots: VecDeque::new(),
+            capacity: BASELINE_HISTORY_SIZE,
+        }
+    }
+}
+
+impl SnapshotBaselines {
+    pub fn push(&mut self, snapshot: NetSnapshot) {
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    #[must_use]
+    pub fn get(&self, tick: u64) -> Option<&NetSnapshot> {
+        self.snapshots.iter().find(|s| s.tick == tick)
+    }
+
+    /// Encodes `current` against the snapshot at `ack`,
+    /// falling back to a full snapshot if it is no longer stored
+    #[must_use]
+    pub fn encode(&self, ack: Option<u64>, current: &NetSnapshot) -> NetSnapshotDelta {
+        let baseline = ack.and_then(|tick| self.get(tick));
+
+        let players = current
+            .players
+            .iter()
+            .filter_map(|player| {
+                let old = baseline.and_then(|b| b.players.iter().find(|p| p.slot == player.slot));
+                encode_player(old, player)
+            })
+            .collect();
+
+        let platforms = current
+            .platforms
+            .iter()
+            .filter(|platform| baseline.is_none_or(|b| !b.platforms.contains(platform)))
+            .cloned()
+            .collect();
+
+        NetSnapshotDelta {
+            baseline: baseline.map(|b| b.tick),
+            outcome: current.outcome,
+            players,
+            platforms,
+            projectiles: current.projectiles.clone(),
+        }
+    }
+
+    /// Rebuilds the full snapshot, or `None` if the baseline is unknown
+    #[must_use]
+    pub fn decode(&self, tick: u64, delta: &NetSnapshotDelta) -> Option<NetSnapshot> {
+        let mut snapshot = match delta.baseline {
+            Some(baseline) => self.get(baseline)?.clone(),
+            None => NetSnapshot {
+                tick,
+                outcome: 0,
+                players: Vec::new(),
+                platforms: Vec::new(),
+                projectiles: Vec::new(),
+            },
+        };
+        snapshot.tick = tick;
+        snapshot.outcome = delta.outcome;
+        snapshot.projectiles = delta.projectiles.clone();
+
+        for player in &delta.players {
+            match snapshot.players.iter_mut().find(|p| p.slot == player.slot) {
+                Some(existing) => apply_player(existing, player),
+                None => snapshot.players.push(NetPlayer {
+                    slot: player.slot,
+                    physics: player.physics.clone()?,
+                    combat: player.combat.clone()?,
+                    status: player.status.clone()?,
+                    cooldowns: player.cooldowns?,
+                }),
+            }
+        }
+
+        for platform in &delta.platforms {
+            match snapshot
+                .platforms
+                .iter_mut()
+                .find(|p| p.index == platform.index)
+            {
+                Some(existing) => *existing = platform.clone(),
+                None => snapshot.platforms.push(platform.clone()),
+            }
+        }
+
+        Some(snapshot)
+    }
+}
+
+fn encode_player(old: Option<&NetPlayer>, new: &NetPlayer) -> Option<NetPlayerDelta> {
+    fn changed<T: PartialEq + Clone>(old: Option<&T>, new: &T) -> Option<T> {
+        if old == Some(new) {
+            None
+        } else {
+            Some(new.clone())
+        }
+    }
+
+    let delta = NetPlayerDelta {
+        slot: new.slot,
+        physics: changed(old.map(|p| &p.physics), &new.physics),
+        combat: changed(old.map(|p| &p.combat), &new.combat),
+        status: changed(old.map(|p| &p.status), &new.status),
+        cooldowns: changed(old.map(|p| &p.cooldowns), &new.cooldowns),
+    };
+
+    let unchanged = delta.physics.is_none()
+        && delta.combat.is_none()
+        && delta.status.is_none()
+        && delta.cooldowns.is_none();
+
+    if unchanged { None } else { Some(delta) }
+}
+
+fn apply_player(player: &mut NetPlayer, delta: &NetPlayerDelta) {
+    if let Some(physics) = &delta.physics {
+        player.physics = physics.clone();
+    }
+    if let Some(combat) = &delta.combat {
+        player.combat = combat.clone();
+    }
+    if let Some(status) = &delta.status {
+        player.status = status.clone();
+    }
+    if let Some(cooldowns) = delta.cooldowns {
+        player.cooldowns = cooldowns;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net_attack::NetAttack;
+    use crate::net_server::ServerMessage;
+    use crate::reliable::ReliableChannel;
+    use foundation::GameMode;
+    use simulation::attack::AttackKind;
+    use simulation::map::{Ledge, LedgeSide};
+    use wincode::serialize;
+
+    fn player(slot: u8, x: i16) -> NetPlayer {
+        NetPlayer {
+            slot,
+            physics: NetPhysics {
+                pos: [x, 0],
+                vel: [0, 0],
+                double_jumps: 1,
+                has_jumped: false,
+                drop_through: false,
+                ledge: None,
+            },
+            combat: NetCombat {
+                lives: 3,
+                combo: 0,
+                combo_timer: 0,
+                knockback_multiplier: 100,
+                attacks: Vec::new(),
+            },
+            status: NetStatus {
+                stunned: 0,
+                invulnerable: 0,
+                parry: 0,
+                respawn_timer: 0,
+                can_slam: true,
+                disconnected: false,
+                hang: 0,
+                ledge_cooldown: 0,
+                ledge_intangibility: false,
+            },
+            cooldowns: [0; 4],
+        }
+    }
+
+    fn platform(index: u8, x: i16) -> NetPlatform {
+        NetPlatform {
+            index,
+            pos: [x, 500],
+            vel: [0, 0],
+            active: true,
+        }
+    }
+
+    fn snapshot(tick: u64, players: Vec<NetPlayer>, platforms: Vec<NetPlatform>) -> NetSnapshot {
+        NetSnapshot {
+            tick,
+            outcome: 0,
+            players,
+            platforms,
+            projectiles: Vec::new(),
+        }
+    }
+
+    fn assert_same(decoded: &NetSnapshot, expected: &NetSnapshot) {
+        assert_eq!(decoded.tick, expected.tick);
+        assert_eq!(decoded.outcome, expected.outcome);
+        assert!(decoded.players == expected.players);
+        assert!(decoded.platforms == expected.platforms);
+        assert!(decoded.projectiles == expected.projectiles);
+    }
+
+    /// Everything a player can be sending at once: hanging off a ledge
+    /// mid-combo with a dash, an attack and a slam going
+    fn busy_player(slot: u8) -> NetPlayer {
+        let mut player = player(slot, i16::MAX);
+        player.physics.ledge = Some(Ledge {
+            platform: usize::MAX,
+            side: LedgeSide::Right,
+        });
+        player.combat.combo = u32::MAX;
+        player.combat.attacks = [AttackKind::Dash, AttackKind::Normal, AttackKind::Slam]
+            .into_iter()
+            .map(|kind| NetAttack {
+                timer: u16::MAX,
+                knockback: [i16::MAX; 2],
+                kind,
+                facing: [1, 1],
+                frame: u8::MAX,
+            })
+            .collect();
+        player
+    }
+
+    fn projectile(owner: u8) -> NetProjectile {
+        NetProjectile {
+            id: u16::MAX,
+            owner,
+            kind: AttackKind::Light,
+            pos: [i16::MAX; 2],
+            vel: [i16::MAX; 2],
+            timer: u16::MAX,
+        }
+    }
+
+    #[test]
+    fn full_snapshot_of_the_largest_match_fits_in_a_datagram() {
+        let slots = 0..GameMode::MAX_PLAYERS as u8;
+        let mut current = snapshot(
+            u64::MAX,
+            slots.clone().map(busy_player).collect(),
+            (0..8).map(|index| platform(index, 0)).collect(),
+        );
+        current.projectiles = slots
+            .flat_map(|slot| [projectile(slot), projectile(slot)])
+            .collect();
+
+        let message = ServerMessage::Snapshot {
+            server_tick: u64::MAX,
+            ack_tick: u64::MAX,
+            server_state: SnapshotBaselines::default().encode(None, &current),
+        };
+        let bytes = serialize(&message).unwrap();
+        assert!(ReliableChannel::open().unreliable(bytes).is_ok());
+    }
+
+    #[test]
+    fn round_trips_a_full_snapshot() {
+        let current = snapshot(5, vec![player(0, 10), player(1, 20)], vec![platform(0, 0)]);
+
+        let delta = SnapshotBaselines::default().encode(None, &current);
+        assert!(delta.baseline.is_none());
+        let decoded = SnapshotBaselines::default().decode(5, &delta).unwrap();
+        assert_same(&decoded, &current);
+    }
+
+    #[test]
+    fn round_trips_against_an_acknowledged_baseline() {
+        let old = snapshot(
+            5,
+            vec![player(0, 10), player(1, 20)],
+            vec![platform(0, 0), platform(1, 0)],
+        );
+        let mut current = snapshot(
+            6,
+            vec![player(0, 10), player(1, 25)],
+            vec![platform(0, 0), platform(1, 5)],
+        );
+        current.players[1].status.stunned = 3;
+        current.outcome = 1;
+
+        let mut server = SnapshotBaselines::default();
+        let mut client = SnapshotBaselines::default();
+        server.push(old.clone());
+        client.push(old);
+
+        let delta = server.encode(Some(5), &current);
+        assert_eq!(delta.baseline, Some(5));
+        // only what changed is sent
+        assert_eq!(delta.players.len(), 1);
+        assert!(delta.players[0].physics.is_some() && delta.players[0].status.is_some());
+        assert!(delta.players[0].combat.is_none() && delta.players[0].cooldowns.is_none());
+        assert_eq!(delta.platforms.len(), 1);
+
+        let decoded = client.decode(6, &delta).unwrap();
+        assert_same(&decoded, &current);
+    }
+
+    #[test]
+    fn adds_players_missing_from_the_baseline() {
+        let old = snapshot(5, vec![player(0, 10)], Vec::new());
+        let current = snapshot(6, vec![player(0, 10), player(1, 20)], Vec::new());
+
+        let mut baselines = SnapshotBaselines::default();
+        baselines.push(old);
+
+        let delta = baselines.encode(Some(5), &current);
+        assert_same(&baselines.decode(6, &delta).unwrap(), &current);
+    }
+
+    #[test]
+    fn falls_back_to_a_full_snapshot_for_a_forgotten_baseline() {
+        let mut baselines = SnapshotBaselines::default();
+        for tick in 0..=BASELINE_HISTORY_SIZE as u64 {
+            baselines.push(snapshot(tick, vec![player(0, 10)], Vec::new()));
+        }
+        assert!(baselines.get(0).is_none());
+
+        let current = snapshot(40, vec![player(0, 10)], Vec::new());
+        let delta = baselines.encode(Some(0), &current);
+        assert!(delta.baseline.is_none());
+        assert_same(
+            &SnapshotBaselines::default().decode(40, &delta).unwrap(),
+            &current,
+        );
+    }
+
+    #[test]
+    fn cannot_decode_without_the_baseline() {
+        let old = snapshot(5, vec![player(0, 10)], Vec::new());
+        let current = snapshot(6, vec![player(0, 15)], Vec::new());
+
+        let mut server = SnapshotBaselines::default();
+        server.push(old);
+        let delta = server.encode(Some(5), &current);
+
+        assert!(SnapshotBaselines::default().decode(6, &delta).is_none());
+    }
+}
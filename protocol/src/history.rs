@@ -0,0 +1,90 @@
+use foundation::GameMode;
+use serde::{Deserialize, Serialize};
+use simulation::{Character, PlayerStats};
+use wincode::{SchemaRead, SchemaWrite};
+
+/// A finished match as kept by the server and sent back in match histories
+#[derive(Serialize, Deserialize, SchemaWrite, SchemaRead, Clone, Debug)]
+pub struct MatchRecord {
+    pub id: String,
+    // unix time in seconds
+    pub finished_at: u64,
+    // seconds of game time
+    pub duration: f32,
+    pub mode: GameMode,
+    pub map: String,
+    // name of the balance file played with
+    #[serde(default)]
+    pub balance: String,
+    pub rated: bool,
+    // index of the winning team, none for a draw
+    pub winner: Option<usize>,
+    pub players: Vec<MatchPlayer>,
+}
+
+#[derive(Serialize, Deserialize, SchemaWrite, SchemaRead, Clone, Debug)]
+pub struct MatchPlayer {
+    pub account_id: String,
+    // name at the time of the match
+    pub name: String,
+    pub team: usize,
+    #[serde(default)]
+    pub character: Character,
+    pub lives_left: u8,
+    pub stats: PlayerStats,
+    // rating after the match, only for rated matches
+    pub rating: Option<f64>,
+}
+
+impl MatchRecord {
+    #[must_use]
+    pub fn player(&self, account_id: &str) -> Option<&MatchPlayer> {
+        self.players.iter().find(|p| p.account_id == account_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net_client::MAX_NAME_LENGTH;
+    use crate::net_server::ServerMessage;
+    use crate::reliable::ReliableChannel;
+    use uuid::Uuid;
+    use wincode::serialize;
+
+    #[test]
+    fn history_entry_of_the_largest_match_fits_in_a_datagram() {
+        let stats = PlayerStats {
+            hits_landed: u32::MAX,
+            hits_taken: u32::MAX,
+            parries: u32::MAX,
+            falls: u32::MAX,
+            best_combo: u32::MAX,
+        };
+        let players = (0..GameMode::MAX_PLAYERS)
+            .map(|team| MatchPlayer {
+                account_id: Uuid::new_v4().to_string(),
+                name: "n".repeat(MAX_NAME_LENGTH),
+                team,
+                character: Character::default(),
+                lives_left: u8::MAX,
+                stats,
+                rating: Some(f64::MAX),
+            })
+            .collect();
+        let record = MatchRecord {
+            id: Uuid::new_v4().to_string(),
+            finished_at: u64::MAX,
+            duration: f32::MAX,
+            mode: GameMode::new(GameMode::MAX_PLAYERS, 1),
+            map: "m".repeat(MAX_NAME_LENGTH),
+            balance: "b".repeat(MAX_NAME_LENGTH),
+            rated: true,
+            winner: Some(usize::MAX),
+            players,
+        };
+
+        let bytes = serialize(&ServerMessage::HistoryEntry(record)).unwrap();
+        assert!(ReliableChannel::open().send(bytes).is_ok());
+    }
+}
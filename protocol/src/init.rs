@@ -1,53 +1,307 @@
-use simulation::{Player, game_state::GameState};
+use anyhow::{Result, anyhow};
+use foundation::GameMode;
+use simulation::{Character, MatchBuilder, balance::Balance, game_state::GameState, map::Map};
 use std::collections::HashMap;
 use uuid::Uuid;
-use wincode::{SchemaRead, SchemaWrite};
-
-use crate::constants::{DUO_OFFSET, TEAM_ONE_START_POS, TEAM_TWO_START_POS};
+use wincode::{SchemaRead, SchemaWrite, serialize};
 
 #[derive(SchemaWrite, SchemaRead, Clone)]
 pub struct InitPlayerData {
     pub name: String,
+    // compact id used for this player in snapshots
+    pub slot: u8,
+    pub character: Character,
 }
 
 #[derive(SchemaWrite, SchemaRead, Clone)]
 pub struct InitData {
     pub players: HashMap<String, InitPlayerData>,
-    pub teams: [Vec<String>; 2],
+    pub teams: Vec<Vec<String>>,
+    pub map: Map,
+    pub balance: Balance,
 }
 
 impl InitData {
-    pub fn to_game_state(&self) -> GameState {
-        let mut players = HashMap::new();
-        for (team_index, team) in self.teams.iter().enumerate() {
-            for (player_index, player_id) in team.iter().enumerate() {
-                players.insert(
-                    Uuid::parse_str(player_id).expect("Invalid UUID string"),
-                    Player::new(spawn_position(team_index, player_index), team_index),
-                );
+    /// Number of teams and the size of the largest one
+    #[must_use]
+    pub fn mode(&self) -> GameMode {
+        GameMode::new(
+            self.teams.len(),
+            self.teams.iter().map(Vec::len).max().unwrap_or(0),
+        )
+    }
+
+    /// Fails on a malformed player id, the data comes from
+    /// the network or a replay file
+    pub fn player_slots(&self) -> Result<PlayerSlots> {
+        let mut ids = vec![Uuid::nil(); self.players.len()];
+        for (id, data) in &self.players {
+            if let Some(entry) = ids.get_mut(data.slot as usize) {
+                *entry = parse_player_id(id)?;
             }
         }
 
-        let teams: Vec<Vec<Uuid>> = self
+        Ok(PlayerSlots { ids })
+    }
+
+    /// Fails on a malformed player id or a match the map cannot hold
+    pub fn to_game_state(&self) -> Result<GameState> {
+        let mut builder = MatchBuilder::new(self.map.clone()).balance(self.balance.clone());
+        for team in &self.teams {
+            let team = team
+                .iter()
+                .map(|id| parse_player_id(id))
+                .collect::<Result<Vec<_>>>()?;
+            builder = builder.team(team);
+        }
+        for (id, data) in &self.players {
+            builder = builder.character(parse_player_id(id)?, data.character);
+        }
+
+        builder
+            .build_state()
+            .map_err(|e| anyhow!("Invalid match: {e}"))
+    }
+}
+
+/// Match setup as sent to players and spectators. The balance is sent
+/// along, split over several datagrams, so the server can tune it freely.
+/// The map goes by file name and each side loads its own copy.
+#[derive(SchemaWrite, SchemaRead, Clone)]
+pub struct NetInitData {
+    // in slot order
+    pub players: Vec<NetInitPlayer>,
+    // slots of each team's players
+    pub teams: Vec<Vec<u8>>,
+    pub map_file: String,
+    // catches a client whose map differs from the server's
+    pub map_fingerprint: u64,
+    pub balance: Balance,
+}
+
+#[derive(SchemaWrite, SchemaRead, Clone)]
+pub struct NetInitPlayer {
+    pub id: u128,
+    pub name: String,
+    pub character: Character,
+}
+
+impl NetInitData {
+    pub fn new(init_data: &InitData, map_file: &str) -> Result<Self> {
+        let slots = init_data.player_slots()?;
+        let players = slots
+            .ids
+            .iter()
+            .map(|id| {
+                let data = init_data
+                    .players
+                    .get(&id.to_string())
+                    .ok_or_else(|| anyhow!("No player in every slot"))?;
+                Ok(NetInitPlayer {
+                    id: id.as_u128(),
+                    name: data.name.clone(),
+                    character: data.character,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let teams = init_data
             .teams
             .iter()
             .map(|team| {
                 team.iter()
-                    .map(|id| Uuid::parse_str(id).expect("Invalid UUID string"))
+                    .map(|id| {
+                        slots
+                            .slot(&parse_player_id(id)?)
+                            .ok_or_else(|| anyhow!("Player {id} has no slot"))
+                    })
                     .collect()
             })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            players,
+            teams,
+            map_file: map_file.to_string(),
+            map_fingerprint: fingerprint(&init_data.map)?,
+            balance: init_data.balance.clone(),
+        })
+    }
+
+    /// Number of teams and the size of the largest one
+    #[must_use]
+    pub fn mode(&self) -> GameMode {
+        GameMode::new(
+            self.teams.len(),
+            self.teams.iter().map(Vec::len).max().unwrap_or(0),
+        )
+    }
+
+    /// Fails when the loaded map differs from the server's
+    pub fn into_init_data(self, map: Map) -> Result<InitData> {
+        if fingerprint(&map)? != self.map_fingerprint {
+            return Err(anyhow!(
+                "The map '{}' differs from the server's",
+                self.map_file
+            ));
+        }
+
+        let id = |slot: u8| {
+            self.players
+                .get(slot as usize)
+                .map(|p| Uuid::from_u128(p.id).to_string())
+                .ok_or_else(|| anyhow!("No player in slot {slot}"))
+        };
+        let teams = self
+            .teams
+            .iter()
+            .map(|team| team.iter().map(|slot| id(*slot)).collect())
+            .collect::<Result<_>>()?;
+        let players = self
+            .players
+            .iter()
+            .enumerate()
+            .map(|(slot, p)| {
+                let data = InitPlayerData {
+                    name: p.name.clone(),
+                    slot: slot as u8,
+                    character: p.character,
+                };
+                (Uuid::from_u128(p.id).to_string(), data)
+            })
             .collect();
 
-        GameState::new(players, teams.try_into().expect("Expected exactly 2 teams"))
+        Ok(InitData {
+            players,
+            teams,
+            map,
+            balance: self.balance,
+        })
+    }
+}
+
+// FNV-1a, stable across builds unlike the std hasher
+fn fingerprint(map: &Map) -> Result<u64> {
+    Ok(serialize(map)?
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        }))
+}
+
+fn parse_player_id(id: &str) -> Result<Uuid> {
+    Uuid::parse_str(id).map_err(|e| anyhow!("Invalid player id '{id}': {e}"))
+}
+
+/// Maps snapshot slots to player ids and back
+#[derive(Clone)]
+pub struct PlayerSlots {
+    ids: Vec<Uuid>,
+}
+
+impl PlayerSlots {
+    #[must_use]
+    pub fn slot(&self, player_id: &Uuid) -> Option<u8> {
+        self.ids
+            .iter()
+            .position(|id| id == player_id)
+            .map(|slot| slot as u8)
+    }
+
+    #[must_use]
+    pub fn id(&self, slot: u8) -> Option<Uuid> {
+        self.ids.get(slot as usize).copied()
     }
 }
 
-fn spawn_position(team_id: usize, player_id: usize) -> [f32; 2] {
-    match (team_id, player_id) {
-        (0, 0) => TEAM_ONE_START_POS,
-        (0, 1) => [TEAM_ONE_START_POS[0] + DUO_OFFSET, TEAM_ONE_START_POS[1]],
-        (1, 0) => TEAM_TWO_START_POS,
-        (1, 1) => [TEAM_TWO_START_POS[0] - DUO_OFFSET, TEAM_TWO_START_POS[1]],
-        _ => unreachable!(),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net_client::MAX_NAME_LENGTH;
+    use crate::net_server::ServerMessage;
+    use crate::reliable::ReliableChannel;
+    use wincode::deserialize;
+
+    fn init_data(teams: usize, team_size: usize) -> InitData {
+        let teams: Vec<Vec<String>> = (0..teams)
+            .map(|_| (0..team_size).map(|_| Uuid::new_v4().to_string()).collect())
+            .collect();
+        let players = teams
+            .iter()
+            .flatten()
+            .enumerate()
+            .map(|(slot, id)| {
+                let data = InitPlayerData {
+                    name: "n".repeat(MAX_NAME_LENGTH),
+                    slot: slot as u8,
+                    character: Character::default(),
+                };
+                (id.clone(), data)
+            })
+            .collect();
+
+        InitData {
+            players,
+            teams,
+            map: Map::default(),
+            balance: Balance::default(),
+        }
+    }
+
+    #[test]
+    fn start_game_arrives_with_the_balance() {
+        let mut init_data = init_data(GameMode::MAX_PLAYERS, 1);
+        init_data.balance.cooldowns.dash = 1.5;
+        let message = ServerMessage::StartGame {
+            c_player: Uuid::new_v4().to_string(),
+            init_data: NetInitData::new(&init_data, "default").unwrap(),
+        };
+
+        let mut sender = ReliableChannel::new(1);
+        let mut receiver = ReliableChannel::new(1);
+        let mut payloads = Vec::new();
+        for packet in sender.send_split(serialize(&message).unwrap()).unwrap() {
+            payloads.extend(receiver.receive(&packet).unwrap().payloads);
+        }
+        assert_eq!(payloads.len(), 1);
+
+        let ServerMessage::StartGame {
+            init_data: sent, ..
+        } = deserialize(&payloads[0]).unwrap()
+        else {
+            panic!("not a start game message");
+        };
+        assert_eq!(sent.balance, init_data.balance);
+    }
+
+    #[test]
+    fn rebuilds_the_match_from_matching_files() {
+        let init_data = init_data(2, 2);
+        let net_init_data = NetInitData::new(&init_data, "default").unwrap();
+        assert_eq!(net_init_data.mode(), GameMode::new(2, 2));
+
+        let rebuilt = net_init_data.into_init_data(Map::default()).unwrap();
+        assert!(rebuilt.to_game_state().is_ok());
+        assert_eq!(
+            rebuilt.player_slots().unwrap().id(0),
+            init_data.player_slots().unwrap().id(0)
+        );
+    }
+
+    #[test]
+    fn refuses_a_map_that_differs_from_the_server() {
+        let init_data = init_data(2, 1);
+        let net_init_data = NetInitData::new(&init_data, "default").unwrap();
+
+        let mut map = Map::default();
+        map.platforms_mut()[0].rect.w += 1.0;
+        assert!(net_init_data.into_init_data(map).is_err());
+    }
+
+    #[test]
+    fn reports_malformed_player_ids() {
+        let mut init_data = init_data(2, 1);
+        init_data.teams[0][0] = "not a uuid".to_string();
+
+        assert!(init_data.to_game_state().is_err());
     }
 }
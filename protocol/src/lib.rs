@@ -1,8 +1,17 @@
-pub mod constants;
+pub mod delta;
+pub mod history;
 pub mod init;
 mod net_attack;
 pub mod net_client;
 pub mod net_game_state;
+pub mod net_platform;
 pub mod net_player;
+pub mod net_projectile;
 pub mod net_server;
+pub mod quantize;
+pub mod reliable;
+pub mod replay;
 pub mod utils;
+
+/// Bumped whenever the wire format changes, clients and servers must match
+pub const PROTOCOL_VERSION: u32 = 14;
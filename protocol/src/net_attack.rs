@@ -1,46 +1,44 @@
+use crate::quantize;
 use serde::{Deserialize, Serialize};
 use simulation::attack::{Attack, AttackKind};
+use simulation::balance::CharacterProperties;
 use uuid::Uuid;
 use wincode::{SchemaRead, SchemaWrite};
 
-#[derive(Serialize, Deserialize, Clone, SchemaWrite, SchemaRead)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, SchemaWrite, SchemaRead)]
 pub struct NetAttack {
-    pub timer: f32,
-    pub owner: String,
-    pub knockback: [f32; 2],
+    pub timer: u16,
+    pub knockback: [i16; 2],
     pub kind: AttackKind,
-    pub facing: [f32; 2],
-    pub frame: usize,
+    pub facing: [i8; 2],
+    pub frame: u8,
 }
 
+/// Attacks are sent with the player owning them, so the owner
+/// and their character are not networked
 #[must_use]
-pub fn from_net(net: NetAttack) -> Attack {
-    let properties = net.kind.properties();
+pub fn from_net(net: NetAttack, owner: Uuid, character: &CharacterProperties) -> Attack {
+    let facing = quantize::from_direction(net.facing);
+    let attack = Attack::new(net.kind.clone(), character.attack(&net.kind), owner, facing);
+    let timer = quantize::from_timer(net.timer);
 
+    // targets already hit only matter to the server, which resolves hits
     Attack {
-        offset: properties.offset,
-        size: properties.size,
-        knockback: net.knockback.into(),
-        kind: net.kind,
-        duration: properties.duration,
-        timer: net.timer,
-        owner: Uuid::parse_str(&net.owner).expect("Invalid UUID string"),
-        facing: net.facing.into(),
-        stun: properties.stun,
-        knockback_increase: properties.knockback_increase,
-        frame: net.frame,
-        frame_count: properties.frame_count,
+        knockback: quantize::from_vel(net.knockback),
+        timer,
+        fired: attack.projectile.is_some() && timer >= attack.startup,
+        frame: net.frame as usize,
+        ..attack
     }
 }
 
 #[must_use]
 pub fn to_net(attack: &Attack) -> NetAttack {
     NetAttack {
-        timer: attack.timer,
-        owner: attack.owner.to_string(),
-        knockback: attack.knockback.into(),
+        timer: quantize::timer(attack.timer),
+        knockback: quantize::vel(attack.knockback),
         kind: attack.kind.clone(),
-        facing: attack.facing.into(),
-        frame: attack.frame,
+        facing: quantize::direction(attack.facing),
+        frame: attack.frame as u8,
     }
 }
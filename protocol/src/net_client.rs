@@ -1,17 +1,65 @@
 use foundation::GameMode;
 use serde::{Deserialize, Serialize};
-use simulation::PlayerInput;
+use simulation::{Character, PlayerInput};
+use std::time::Duration;
 use wincode::{SchemaRead, SchemaWrite};
 
+/// How often an otherwise idle client tells the server it is still there
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+/// Longest player name in bytes, keeps lobbies and matches within a datagram
+pub const MAX_NAME_LENGTH: usize = 32;
+
+/// First message of every connection, answered with a welcome or a rejection
+#[derive(Serialize, Deserialize, SchemaRead, SchemaWrite)]
+pub struct Hello {
+    pub protocol_version: u32,
+    // client version, for the server log
+    pub build: String,
+    pub player_name: String,
+    pub character: Character,
+    // token from a previous welcome, to resume that session
+    pub session_token: Option<String>,
+    // token from an earlier welcome that identifies the player's account
+    pub account_token: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, SchemaRead, SchemaWrite)]
 pub enum ClientMessage {
-    Hello {
-        player_name: String,
-    },
+    Hello(Hello),
+    /// Queues the sender, or their whole party if they lead one
     QueueJoin(GameMode),
     QueueLeave,
+    /// Invites a player by name to the sender's party
+    PartyInvite(String),
+    /// Accepts the latest invite
+    PartyAccept,
+    PartyDecline,
+    PartyLeave,
+    /// Opens a private lobby with the sender as host
+    LobbyCreate(GameMode),
+    /// Joins a private lobby by its code
+    LobbyJoin(String),
+    LobbyPickTeam(u8),
+    LobbyLeave,
+    /// Starts the match with the picked teams, host only
+    LobbyStart,
+    /// Plays as this character from the next match on
+    PickCharacter(Character),
+    /// Asks for the sender's most recent matches
+    MatchHistory,
+    /// Asks for the matches running on the server
+    ListGames,
+    /// Watches a running match by its id
+    Spectate(String),
+    StopSpectating,
+    Heartbeat,
+    Disconnect,
     Input {
         client_tick: u64,
+        // server tick the client was rendering when sampling the input
+        view_tick: u64,
+        // latest snapshot received, used as baseline for the next delta
+        snapshot_ack: Option<u64>,
         input: PlayerInput,
     },
 }
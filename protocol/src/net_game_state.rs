@@ -1,40 +1,86 @@
-use crate::{net_player, net_server::NetSnapshot};
-use simulation::game_state::GameState;
-use uuid::Uuid;
+use crate::{
+    init::PlayerSlots,
+    net_platform::{self, NetPlatform},
+    net_player, net_projectile,
+    net_server::NetSnapshot,
+};
+use simulation::game_state::{GameState, Outcome};
+
+const DRAW: u8 = u8::MAX;
 
 #[must_use]
-pub fn to_net(gs: &GameState) -> NetSnapshot {
+pub fn to_net(gs: &GameState, tick: u64, slots: &PlayerSlots) -> NetSnapshot {
+    let mut players: Vec<_> = gs
+        .players
+        .iter()
+        .filter_map(|(id, player)| Some(net_player::to_net(slots.slot(id)?, player)))
+        .collect();
+    players.sort_by_key(|p| p.slot);
+
     NetSnapshot {
-        tick: 0,
-        winner: gs.winner,
-        players: gs.players.iter().map(net_player::to_net).collect(),
+        tick,
+        outcome: outcome_to_net(gs.outcome),
+        players,
+        platforms: dynamic_platforms(gs),
+        projectiles: gs
+            .projectiles
+            .iter()
+            .filter_map(|projectile| net_projectile::to_net(projectile, slots))
+            .collect(),
     }
 }
 
-pub fn apply_snapshot(gs: &mut GameState, snapshot: &NetSnapshot) {
-    gs.winner = snapshot.winner;
+pub fn apply_snapshot(gs: &mut GameState, snapshot: &NetSnapshot, slots: &PlayerSlots) {
+    gs.outcome = outcome_from_net(snapshot.outcome);
 
     for net_player in &snapshot.players {
-        if let Some(player) = gs
-            .players
-            .get_mut(&Uuid::parse_str(&net_player.player_id).expect("Invalid UUID string"))
-        {
-            net_player::from_net(player, net_player);
+        let Some(player_id) = slots.id(net_player.slot) else {
+            continue;
+        };
+
+        if let Some(player) = gs.players.get_mut(&player_id) {
+            net_player::from_net(player_id, player, net_player, &gs.balance);
         }
     }
+
+    for net_platform in &snapshot.platforms {
+        if let Some(platform) = gs.map.platforms_mut().get_mut(net_platform.index as usize) {
+            net_platform::from_net(platform, net_platform);
+        }
+    }
+
+    gs.projectiles = snapshot
+        .projectiles
+        .iter()
+        .filter_map(|net_projectile| net_projectile::from_net(net_projectile, gs, slots))
+        .collect();
 }
 
+/// 0 while the match is running, otherwise the winning team + 1 or `DRAW`
 #[must_use]
-pub fn to_snapshot(gs: &GameState) -> NetSnapshot {
-    let mut net_players = Vec::new();
-
-    for player in gs.players.iter() {
-        net_players.push(net_player::to_net(player));
+pub fn outcome_to_net(outcome: Option<Outcome>) -> u8 {
+    match outcome {
+        None => 0,
+        Some(Outcome::Win(team_idx)) => team_idx as u8 + 1,
+        Some(Outcome::Draw) => DRAW,
     }
+}
 
-    NetSnapshot {
-        tick: 0,
-        winner: gs.winner,
-        players: net_players,
+#[must_use]
+pub fn outcome_from_net(outcome: u8) -> Option<Outcome> {
+    match outcome {
+        0 => None,
+        DRAW => Some(Outcome::Draw),
+        team => Some(Outcome::Win(team as usize - 1)),
     }
 }
+
+fn dynamic_platforms(gs: &GameState) -> Vec<NetPlatform> {
+    gs.map
+        .platforms()
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| p.is_dynamic())
+        .map(net_platform::to_net)
+        .collect()
+}
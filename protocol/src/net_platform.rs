@@ -0,0 +1,30 @@
+use crate::quantize;
+use serde::{Deserialize, Serialize};
+use simulation::map::Platform;
+use wincode::{SchemaRead, SchemaWrite};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, SchemaWrite, SchemaRead)]
+pub struct NetPlatform {
+    pub index: u8,
+    pub pos: [i16; 2],
+    pub vel: [i16; 2],
+    pub active: bool,
+}
+
+#[must_use]
+pub fn to_net(platform: (usize, &Platform)) -> NetPlatform {
+    NetPlatform {
+        index: platform.0 as u8,
+        pos: quantize::pos([platform.1.rect.x, platform.1.rect.y].into()),
+        vel: quantize::vel(platform.1.velocity()),
+        active: platform.1.active,
+    }
+}
+
+pub fn from_net(platform: &mut Platform, net_platform: &NetPlatform) {
+    let pos = quantize::from_pos(net_platform.pos);
+    platform.rect.x = pos.x;
+    platform.rect.y = pos.y;
+    platform.vel = quantize::from_vel(net_platform.vel).into();
+    platform.active = net_platform.active;
+}
@@ -1,57 +1,131 @@
-use crate::net_attack;
+use crate::{net_attack, quantize};
 use serde::{Deserialize, Serialize};
-use simulation::Player;
+use simulation::{Player, balance::Balance, map::Ledge};
 use uuid::Uuid;
 use wincode::{SchemaRead, SchemaWrite};
 
-#[derive(Serialize, Deserialize, Clone, SchemaWrite, SchemaRead)]
-pub struct NetPlayer {
-    pub player_id: String,
-    pub pos: [f32; 2],
-    pub vel: [f32; 2],
+#[derive(Serialize, Deserialize, Clone, PartialEq, SchemaWrite, SchemaRead)]
+pub struct NetPhysics {
+    pub pos: [i16; 2],
+    pub vel: [i16; 2],
+    pub double_jumps: u8,
+    pub has_jumped: bool,
+    pub drop_through: bool,
+    pub ledge: Option<Ledge>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, SchemaWrite, SchemaRead)]
+pub struct NetCombat {
+    pub lives: u8,
     pub combo: u32,
-    pub knockback_multiplier: f32,
+    pub combo_timer: u16,
+    pub knockback_multiplier: u16,
     pub attacks: Vec<net_attack::NetAttack>,
-    pub stunned: f32,
-    pub invulnerable: f32,
-    pub parry: f32,
-    pub lives: u8,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, SchemaWrite, SchemaRead)]
+pub struct NetStatus {
+    pub stunned: u16,
+    pub invulnerable: u16,
+    pub parry: u16,
+    pub respawn_timer: u16,
+    pub can_slam: bool,
+    pub disconnected: bool,
+    pub hang: u16,
+    pub ledge_cooldown: u16,
+    pub ledge_intangibility: bool,
+}
+
+/// Fields are grouped so snapshot deltas can skip groups that did not change
+#[derive(Serialize, Deserialize, Clone, PartialEq, SchemaWrite, SchemaRead)]
+pub struct NetPlayer {
+    pub slot: u8,
+    pub physics: NetPhysics,
+    pub combat: NetCombat,
+    pub status: NetStatus,
+    pub cooldowns: [u16; 4], // dash, normal, light, parry
 }
 
 #[must_use]
-pub fn to_net(player: (&Uuid, &Player)) -> NetPlayer {
+pub fn to_net(slot: u8, player: &Player) -> NetPlayer {
+    let cooldowns = &player.cooldowns;
+
     NetPlayer {
-        player_id: player.0.to_string(),
-        pos: player.1.physics.pos.into(),
-        vel: player.1.physics.vel.into(),
-        combo: player.1.combat.combo,
-        knockback_multiplier: player.1.combat.knockback_multiplier,
-        attacks: player
-            .1
-            .combat
-            .attacks()
-            .iter()
-            .map(net_attack::to_net)
-            .collect(),
-        stunned: player.1.status.stunned,
-        invulnerable: player.1.status.invulnerable_timer,
-        parry: player.1.status.parry,
-        lives: player.1.combat.lives,
+        slot,
+        physics: NetPhysics {
+            pos: quantize::pos(player.physics.pos),
+            vel: quantize::vel(player.physics.vel),
+            double_jumps: player.physics.double_jumps,
+            has_jumped: player.physics.has_jumped,
+            drop_through: player.physics.drop_through,
+            ledge: player.physics.ledge,
+        },
+        combat: NetCombat {
+            lives: player.combat.lives,
+            combo: player.combat.combo,
+            combo_timer: quantize::timer(player.combat.combo_timer),
+            knockback_multiplier: quantize::fraction(player.combat.knockback_multiplier),
+            attacks: player
+                .combat
+                .attacks()
+                .iter()
+                .map(net_attack::to_net)
+                .collect(),
+        },
+        status: NetStatus {
+            stunned: quantize::timer(player.status.stunned),
+            invulnerable: quantize::timer(player.status.invulnerable_timer),
+            parry: quantize::timer(player.status.parry),
+            respawn_timer: quantize::timer(player.status.respawn_timer),
+            can_slam: player.status.can_slam,
+            disconnected: player.status.disconnected,
+            hang: quantize::timer(player.status.hang),
+            ledge_cooldown: quantize::timer(player.status.ledge_cooldown),
+            ledge_intangibility: player.status.ledge_intangibility,
+        },
+        cooldowns: [
+            quantize::timer(cooldowns.dash),
+            quantize::timer(cooldowns.normal),
+            quantize::timer(cooldowns.light),
+            quantize::timer(cooldowns.parry),
+        ],
     }
 }
 
-pub fn from_net(player: &mut Player, net_player: &NetPlayer) {
-    player.physics.pos = net_player.pos.into();
-    player.physics.vel = net_player.vel.into();
-    player.combat.lives = net_player.lives;
-    player.combat.combo = net_player.combo;
-    player.combat.knockback_multiplier = net_player.knockback_multiplier;
-    player.combat.attacks = net_player
+pub fn from_net(player_id: Uuid, player: &mut Player, net_player: &NetPlayer, balance: &Balance) {
+    let physics = &net_player.physics;
+    player.physics.pos = quantize::from_pos(physics.pos);
+    player.physics.vel = quantize::from_vel(physics.vel);
+    player.physics.double_jumps = physics.double_jumps;
+    player.physics.has_jumped = physics.has_jumped;
+    player.physics.drop_through = physics.drop_through;
+    player.physics.ledge = physics.ledge;
+
+    let character = balance.character(player.character);
+    let combat = &net_player.combat;
+    player.combat.lives = combat.lives;
+    player.combat.combo = combat.combo;
+    player.combat.combo_timer = quantize::from_timer(combat.combo_timer);
+    player.combat.knockback_multiplier = quantize::from_fraction(combat.knockback_multiplier);
+    player.combat.attacks = combat
         .attacks
         .iter()
-        .map(|na| net_attack::from_net(na.clone()))
+        .map(|na| net_attack::from_net(na.clone(), player_id, character))
         .collect();
-    player.status.stunned = net_player.stunned;
-    player.status.invulnerable_timer = net_player.invulnerable;
-    player.status.parry = net_player.parry;
+
+    let status = &net_player.status;
+    player.status.stunned = quantize::from_timer(status.stunned);
+    player.status.invulnerable_timer = quantize::from_timer(status.invulnerable);
+    player.status.parry = quantize::from_timer(status.parry);
+    player.status.respawn_timer = quantize::from_timer(status.respawn_timer);
+    player.status.can_slam = status.can_slam;
+    player.status.disconnected = status.disconnected;
+    player.status.hang = quantize::from_timer(status.hang);
+    player.status.ledge_cooldown = quantize::from_timer(status.ledge_cooldown);
+    player.status.ledge_intangibility = status.ledge_intangibility;
+
+    player.cooldowns.dash = quantize::from_timer(net_player.cooldowns[0]);
+    player.cooldowns.normal = quantize::from_timer(net_player.cooldowns[1]);
+    player.cooldowns.light = quantize::from_timer(net_player.cooldowns[2]);
+    player.cooldowns.parry = quantize::from_timer(net_player.cooldowns[3]);
 }
@@ -0,0 +1,53 @@
+use crate::{init::PlayerSlots, quantize};
+use serde::{Deserialize, Serialize};
+use simulation::{Projectile, attack::AttackKind, game_state::GameState};
+use wincode::{SchemaRead, SchemaWrite};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, SchemaWrite, SchemaRead)]
+pub struct NetProjectile {
+    // low bits of the id, enough to follow it between snapshots
+    pub id: u16,
+    // slot of the player who fired it
+    pub owner: u8,
+    pub kind: AttackKind,
+    pub pos: [i16; 2],
+    pub vel: [i16; 2],
+    pub timer: u16,
+}
+
+#[must_use]
+pub fn to_net(projectile: &Projectile, slots: &PlayerSlots) -> Option<NetProjectile> {
+    Some(NetProjectile {
+        id: projectile.id as u16,
+        owner: slots.slot(&projectile.owner)?,
+        kind: projectile.kind.clone(),
+        pos: quantize::pos(projectile.pos),
+        vel: quantize::vel(projectile.vel),
+        timer: quantize::timer(projectile.timer),
+    })
+}
+
+/// The rest is looked up from the owner's character, `None` if the
+/// owner is unknown or their attack is not fired off
+#[must_use]
+pub fn from_net(net: &NetProjectile, gs: &GameState, slots: &PlayerSlots) -> Option<Projectile> {
+    let owner = slots.id(net.owner)?;
+    let character = gs.balance.character(gs.players.get(&owner)?.character);
+    let attack = character.attack(&net.kind);
+    let projectile = attack.projectile.as_ref()?;
+
+    Some(Projectile {
+        id: net.id.into(),
+        owner,
+        kind: net.kind.clone(),
+        pos: quantize::from_pos(net.pos),
+        vel: quantize::from_vel(net.vel),
+        timer: quantize::from_timer(net.timer),
+        lifetime: attack.active,
+        size: attack.size,
+        gravity: projectile.gravity,
+        stun: attack.stun,
+        knockback_increase: attack.knockback_increase,
+        knockback: attack.knockback,
+    })
+}
@@ -1,27 +1,187 @@
-use crate::{init::InitData, net_player::NetPlayer};
+use crate::{
+    delta::NetSnapshotDelta, history::MatchRecord, init::NetInitData, net_client::MAX_NAME_LENGTH,
+    net_platform::NetPlatform, net_player::NetPlayer, net_projectile::NetProjectile,
+};
+use foundation::GameMode;
 use serde::{Deserialize, Serialize};
+use simulation::Character;
+use std::collections::HashMap;
+use std::fmt;
 use wincode::{SchemaRead, SchemaWrite};
 
 #[derive(Serialize, Deserialize, Clone, SchemaWrite, SchemaRead)]
 pub struct NetSnapshot {
     pub tick: u64,
-    pub winner: usize,
+    pub outcome: u8,
     pub players: Vec<NetPlayer>,
+    pub platforms: Vec<NetPlatform>,
+    pub projectiles: Vec<NetProjectile>,
 }
 
 #[derive(SchemaWrite, SchemaRead)]
 pub enum ServerMessage {
     Welcome {
-        team_id: usize,
-        player_id: usize,
+        client_id: String,
+        // presented in a later hello to resume this session
+        session_token: String,
+        // kept by the client for good, presented in every hello
+        account_token: String,
+        // the session is in a match, which is resent right after
+        in_game: bool,
+        server_name: String,
+        tick_rate: u32,
+        modes: Vec<GameMode>,
     },
+    Rejected(RejectReason),
+    /// Current state of the private lobby, sent to every member on changes
+    Lobby(NetLobby),
+    LobbyError(LobbyError),
+    /// The party the receiver is in, none once they are on their own
+    Party(Option<NetParty>),
+    PartyInvite {
+        from: String,
+    },
+    PartyError(PartyError),
+    /// The receiver, and their party, is queued for a match
+    Queued(GameMode),
+    /// The receiver left the queue without a match, e.g. their leader cancelled
+    QueueLeft,
     StartGame {
         c_player: String,
-        init_data: InitData,
+        init_data: NetInitData,
     },
     EndGame,
+    /// Matches the receiver can spectate, also the answer to a failed spectate
+    Games(Vec<NetGame>),
+    /// The receiver is watching a match, snapshots follow
+    Spectating {
+        init_data: NetInitData,
+    },
+    /// Answer to a history request, followed by one entry per match, newest first
+    MatchHistory {
+        account_id: String,
+        matches: u32,
+    },
+    // sent one by one to keep every message within a datagram
+    HistoryEntry(MatchRecord),
     Snapshot {
         server_tick: u64,
-        server_state: NetSnapshot,
+        // last client tick whose input is included in this snapshot
+        ack_tick: u64,
+        server_state: NetSnapshotDelta,
+    },
+}
+
+#[derive(SchemaWrite, SchemaRead, Debug)]
+pub enum RejectReason {
+    VersionMismatch {
+        server_version: u32,
     },
+    ServerFull,
+    NameTaken,
+    NameTooLong,
+    /// The account is already playing from another client
+    AccountInUse,
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RejectReason::VersionMismatch { server_version } => write!(
+                f,
+                "Version mismatch, the server runs protocol version {server_version}"
+            ),
+            RejectReason::ServerFull => write!(f, "The server is full"),
+            RejectReason::NameTaken => write!(f, "Someone on the server already has that name"),
+            RejectReason::NameTooLong => {
+                write!(f, "Names can be at most {MAX_NAME_LENGTH} bytes long")
+            }
+            RejectReason::AccountInUse => {
+                write!(f, "This account is already playing from another client")
+            }
+        }
+    }
+}
+
+/// Private lobby as seen by its members, players are listed by name
+#[derive(SchemaWrite, SchemaRead, Clone, Debug)]
+pub struct NetLobby {
+    pub code: String,
+    pub mode: GameMode,
+    pub host: String,
+    // in the order they picked the team
+    pub teams: Vec<Vec<String>>,
+    // joined but not on a team yet
+    pub unassigned: Vec<String>,
+    // picked by each member, by name
+    pub characters: HashMap<String, Character>,
+}
+
+#[derive(SchemaWrite, SchemaRead, Debug)]
+pub enum LobbyError {
+    NotFound,
+    LobbyFull,
+    TeamFull,
+    InvalidTeam,
+    ModeNotHosted,
+    NotHost,
+    /// Someone has not picked a team or a team is empty
+    NotReady,
+}
+
+impl fmt::Display for LobbyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LobbyError::NotFound => write!(f, "No lobby with that code"),
+            LobbyError::LobbyFull => write!(f, "The lobby is full"),
+            LobbyError::TeamFull => write!(f, "That team is full"),
+            LobbyError::InvalidTeam => write!(f, "There is no such team"),
+            LobbyError::ModeNotHosted => write!(f, "The server does not host that mode"),
+            LobbyError::NotHost => write!(f, "Only the host can start the match"),
+            LobbyError::NotReady => {
+                write!(f, "Everyone has to pick a team and no team can be empty")
+            }
+        }
+    }
+}
+
+/// A running match as listed to spectators, players are listed by name
+#[derive(SchemaWrite, SchemaRead, Clone, Debug)]
+pub struct NetGame {
+    pub id: String,
+    pub mode: GameMode,
+    pub map: String,
+    pub teams: Vec<Vec<String>>,
+    pub spectators: u32,
+}
+
+#[derive(SchemaWrite, SchemaRead, Clone, Debug)]
+pub struct NetParty {
+    pub leader: String,
+    pub members: Vec<String>,
+}
+
+#[derive(SchemaWrite, SchemaRead, Debug)]
+pub enum PartyError {
+    PlayerNotFound,
+    NoInvite,
+    NotLeader,
+    PartyFull,
+    /// The party has more players than a team in the queued mode
+    TooLarge,
+    /// A member is busy, e.g. in a match or a private lobby
+    MemberBusy,
+}
+
+impl fmt::Display for PartyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PartyError::PlayerNotFound => write!(f, "No player with that name"),
+            PartyError::NoInvite => write!(f, "The invite is no longer valid"),
+            PartyError::NotLeader => write!(f, "Only the party leader can do that"),
+            PartyError::PartyFull => write!(f, "The party is full"),
+            PartyError::TooLarge => write!(f, "The party does not fit in a team of that mode"),
+            PartyError::MemberBusy => write!(f, "Someone in the party is busy"),
+        }
+    }
 }
@@ -0,0 +1,65 @@
+//! Lossy conversions used to shrink snapshots. Precision is chosen
+//! so that quantized values are indistinguishable on screen.
+
+use glam::Vec2;
+
+// positions are sent in quarter pixels, covering roughly +-8000 px
+const POS_SCALE: f32 = 4.0;
+
+#[must_use]
+pub fn pos(v: Vec2) -> [i16; 2] {
+    [to_i16(v.x * POS_SCALE), to_i16(v.y * POS_SCALE)]
+}
+
+#[must_use]
+pub fn from_pos(q: [i16; 2]) -> Vec2 {
+    Vec2::new(q[0] as f32 / POS_SCALE, q[1] as f32 / POS_SCALE)
+}
+
+/// Whole pixels per second
+#[must_use]
+pub fn vel(v: Vec2) -> [i16; 2] {
+    [to_i16(v.x), to_i16(v.y)]
+}
+
+#[must_use]
+pub fn from_vel(q: [i16; 2]) -> Vec2 {
+    Vec2::new(q[0] as f32, q[1] as f32)
+}
+
+/// Milliseconds, saturating at about 65 seconds
+#[must_use]
+pub fn timer(t: f32) -> u16 {
+    (t * 1000.0).round().clamp(0.0, u16::MAX as f32) as u16
+}
+
+#[must_use]
+pub fn from_timer(q: u16) -> f32 {
+    q as f32 / 1000.0
+}
+
+/// Thousandths
+#[must_use]
+pub fn fraction(v: f32) -> u16 {
+    (v * 1000.0).round().clamp(0.0, u16::MAX as f32) as u16
+}
+
+#[must_use]
+pub fn from_fraction(q: u16) -> f32 {
+    q as f32 / 1000.0
+}
+
+/// Directions are always built from -1, 0 and 1 per axis
+#[must_use]
+pub fn direction(v: Vec2) -> [i8; 2] {
+    [v.x.round() as i8, v.y.round() as i8]
+}
+
+#[must_use]
+pub fn from_direction(q: [i8; 2]) -> Vec2 {
+    Vec2::new(q[0] as f32, q[1] as f32)
+}
+
+fn to_i16(v: f32) -> i16 {
+    v.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
@@ -0,0 +1,400 @@
+use anyhow::{Result, anyhow};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+use wincode::{SchemaRead, SchemaWrite, deserialize, serialize};
+
+pub const RESEND_INTERVAL: Duration = Duration::from_millis(100);
+/// Largest datagram sent, anything bigger risks fragmentation on the way
+pub const MAX_DATAGRAM_SIZE: usize = 1400;
+// give up after about five seconds
+const MAX_RESENDS: u32 = 50;
+// payloads larger than this are split over several packets
+const PIECE_SIZE: usize = 1024;
+/// Largest payload `send_split` takes, which bounds what a peer buffers
+pub const MAX_SPLIT_PAYLOAD: usize = 64 * 1024;
+// how far past the next expected payload later ones are held on to,
+// anything further is left unacknowledged and resent by the peer
+const RECEIVE_WINDOW: u32 = 256;
+
+/// Envelope for every datagram. Payloads are serialized
+/// `ClientMessage`s or `ServerMessage`s.
+#[derive(SchemaWrite, SchemaRead)]
+pub enum Packet {
+    /// Fire-and-forget, used for inputs and snapshots
+    Unreliable(Vec<u8>),
+    /// Resent until acknowledged and delivered in order
+    Reliable {
+        seq: u32,
+        payload: Vec<u8>,
+        // more pieces of the same payload follow
+        more: bool,
+    },
+    Ack {
+        seq: u32,
+    },
+}
+
+#[derive(SchemaWrite, SchemaRead)]
+struct Datagram {
+    // picked by the client, a new id means a new connection
+    connection: u32,
+    packet: Packet,
+}
+
+pub struct Received {
+    /// Packet to send back to the sender, if any
+    pub ack: Option<Vec<u8>>,
+    /// Payloads ready to be handled, in order
+    pub payloads: Vec<Vec<u8>>,
+}
+
+struct Pending {
+    packet: Vec<u8>,
+    last_sent: Instant,
+    resends: u32,
+}
+
+/// Reliability state for one peer
+pub struct ReliableChannel {
+    connection: u32,
+    next_send_seq: u32,
+    pending: BTreeMap<u32, Pending>,
+    next_receive_seq: u32,
+    out_of_order: BTreeMap<u32, (Vec<u8>, bool)>,
+    // pieces of a split payload delivered so far
+    partial: Vec<u8>,
+    last_received: Instant,
+}
+
+impl ReliableChannel {
+    #[must_use]
+    pub fn new(connection: u32) -> Self {
+        Self {
+            connection,
+            next_send_seq: 0,
+            pending: BTreeMap::new(),
+            next_receive_seq: 0,
+            out_of_order: BTreeMap::new(),
+            partial: Vec::new(),
+            last_received: Instant::now(),
+        }
+    }
+
+    /// Starts a new connection with a random id
+    #[must_use]
+    pub fn open() -> Self {
+        Self::new(Uuid::new_v4().as_u128() as u32)
+    }
+
+    #[must_use]
+    pub fn connection(&self) -> u32 {
+        self.connection
+    }
+
+    /// Time since the peer last sent anything on this connection
+    #[must_use]
+    pub fn idle(&self) -> Duration {
+        self.last_received.elapsed()
+    }
+
+    /// Wraps `payload` in a reliable packet and keeps it for resending
+    pub fn send(&mut self, payload: Vec<u8>) -> Result<Vec<u8>> {
+        self.send_piece(payload, false)
+    }
+
+    /// Like `send`, but a payload too large for one datagram is split
+    /// over several packets that the peer puts back together
+    pub fn send_split(&mut self, payload: Vec<u8>) -> Result<Vec<Vec<u8>>> {
+        if payload.len() > MAX_SPLIT_PAYLOAD {
+            return Err(anyhow!(
+                "A {} byte payload is over the {MAX_SPLIT_PAYLOAD} byte limit",
+                payload.len()
+            ));
+        }
+        if payload.len() <= PIECE_SIZE {
+            return Ok(vec![self.send(payload)?]);
+        }
+
+        let pieces = payload.len().div_ceil(PIECE_SIZE);
+        payload
+            .chunks(PIECE_SIZE)
+            .enumerate()
+            .map(|(i, piece)| self.send_piece(piece.to_vec(), i + 1 < pieces))
+            .collect()
+    }
+
+    fn send_piece(&mut self, payload: Vec<u8>, more: bool) -> Result<Vec<u8>> {
+        let seq = self.next_send_seq;
+        // a payload that does not fit must not use up a sequence number
+        let packet = self.wrap(Packet::Reliable { seq, payload, more })?;
+        self.next_send_seq = self.next_send_seq.wrapping_add(1);
+
+        self.pending.insert(
+            seq,
+            Pending {
+                packet: packet.clone(),
+                last_sent: Instant::now(),
+                resends: 0,
+            },
+        );
+
+        Ok(packet)
+    }
+
+    pub fn unreliable(&self, payload: Vec<u8>) -> Result<Vec<u8>> {
+        self.wrap(Packet::Unreliable(payload))
+    }
+
+    pub fn receive(&mut self, data: &[u8]) -> Result<Received> {
+        let mut received = Received {
+            ack: None,
+            payloads: Vec::new(),
+        };
+
+        let datagram = deserialize::<Datagram>(data)?;
+        // leftovers from a previous connection
+        if datagram.connection != self.connection {
+            return Ok(received);
+        }
+        self.last_received = Instant::now();
+
+        match datagram.packet {
+            Packet::Unreliable(payload) => received.payloads.push(payload),
+            Packet::Ack { seq } => {
+                self.pending.remove(&seq);
+            }
+            Packet::Reliable { seq, payload, more } => {
+                // sequence numbers wrap, so compare by distance,
+                // anything more than half the range ahead is behind
+                let ahead = seq.wrapping_sub(self.next_receive_seq);
+                let behind = ahead > u32::MAX / 2;
+                if !behind && ahead >= RECEIVE_WINDOW {
+                    return Ok(received);
+                }
+
+                // always ack, the previous ack might have been lost
+                received.ack = Some(self.wrap(Packet::Ack { seq })?);
+
+                if !behind {
+                    self.out_of_order.insert(seq, (payload, more));
+                }
+
+                while let Some((payload, more)) = self.out_of_order.remove(&self.next_receive_seq) {
+                    self.next_receive_seq = self.next_receive_seq.wrapping_add(1);
+                    self.partial.extend(payload);
+                    // a peer sending endless pieces gets its payload dropped
+                    if self.partial.len() > MAX_SPLIT_PAYLOAD {
+                        self.partial.clear();
+                    }
+                    if !more {
+                        received.payloads.push(std::mem::take(&mut self.partial));
+                    }
+                }
+            }
+        }
+
+        Ok(received)
+    }
+
+    /// Packets that have gone unacknowledged for too long,
+    /// fails once the peer has stopped acknowledging altogether
+    pub fn resends(&mut self) -> Result<Vec<Vec<u8>>> {
+        self.resends_at(Instant::now())
+    }
+
+    fn resends_at(&mut self, now: Instant) -> Result<Vec<Vec<u8>>> {
+        if self.pending.values().any(|p| p.resends >= MAX_RESENDS) {
+            return Err(anyhow!("The peer stopped acknowledging"));
+        }
+
+        Ok(self
+            .pending
+            .values_mut()
+            .filter(|p| now.saturating_duration_since(p.last_sent) >= RESEND_INTERVAL)
+            .map(|p| {
+                p.last_sent = now;
+                p.resends += 1;
+                p.packet.clone()
+            })
+            .collect())
+    }
+
+    fn wrap(&self, packet: Packet) -> Result<Vec<u8>> {
+        let datagram = serialize(&Datagram {
+            connection: self.connection,
+            packet,
+        })?;
+        if datagram.len() > MAX_DATAGRAM_SIZE {
+            return Err(anyhow!(
+                "A {} byte datagram is over the {MAX_DATAGRAM_SIZE} byte limit",
+                datagram.len()
+            ));
+        }
+
+        Ok(datagram)
+    }
+}
+
+/// Connection id of a raw datagram
+pub fn connection_id(data: &[u8]) -> Result<u32> {
+    Ok(deserialize::<Datagram>(data)?.connection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channels() -> (ReliableChannel, ReliableChannel) {
+        (ReliableChannel::new(7), ReliableChannel::new(7))
+    }
+
+    fn payloads(received: &Received) -> Vec<u8> {
+        received.payloads.iter().map(|p| p[0]).collect()
+    }
+
+    #[test]
+    fn delivers_in_order_after_reordering() {
+        let (mut sender, mut receiver) = channels();
+        let packets: Vec<_> = (0..3).map(|i| sender.send(vec![i]).unwrap()).collect();
+
+        let third = receiver.receive(&packets[2]).unwrap();
+        assert!(third.ack.is_some());
+        assert!(third.payloads.is_empty());
+
+        assert_eq!(payloads(&receiver.receive(&packets[0]).unwrap()), [0]);
+        assert_eq!(payloads(&receiver.receive(&packets[1]).unwrap()), [1, 2]);
+    }
+
+    #[test]
+    fn acks_duplicates_without_delivering_them_again() {
+        let (mut sender, mut receiver) = channels();
+        let packet = sender.send(vec![1]).unwrap();
+
+        assert_eq!(payloads(&receiver.receive(&packet).unwrap()), [1]);
+        let duplicate = receiver.receive(&packet).unwrap();
+        assert!(duplicate.ack.is_some());
+        assert!(duplicate.payloads.is_empty());
+    }
+
+    #[test]
+    fn keeps_order_across_sequence_wrap_around() {
+        let (mut sender, mut receiver) = channels();
+        sender.next_send_seq = u32::MAX - 1;
+        receiver.next_receive_seq = u32::MAX - 1;
+        let packets: Vec<_> = (0..4).map(|i| sender.send(vec![i]).unwrap()).collect();
+
+        assert!(receiver.receive(&packets[3]).unwrap().payloads.is_empty());
+        assert!(receiver.receive(&packets[2]).unwrap().payloads.is_empty());
+        assert_eq!(payloads(&receiver.receive(&packets[0]).unwrap()), [0]);
+        assert_eq!(payloads(&receiver.receive(&packets[1]).unwrap()), [1, 2, 3]);
+
+        // the packet from before the wrap is old now
+        let duplicate = receiver.receive(&packets[0]).unwrap();
+        assert!(duplicate.ack.is_some());
+        assert!(duplicate.payloads.is_empty());
+        assert!(receiver.out_of_order.is_empty());
+    }
+
+    #[test]
+    fn leaves_packets_past_the_window_for_later() {
+        let (mut sender, mut receiver) = channels();
+        sender.next_send_seq = RECEIVE_WINDOW;
+        let packet = sender.send(vec![1]).unwrap();
+
+        let received = receiver.receive(&packet).unwrap();
+        assert!(received.ack.is_none());
+        assert!(receiver.out_of_order.is_empty());
+    }
+
+    #[test]
+    fn refuses_oversized_payloads_without_skipping_a_sequence_number() {
+        let (mut sender, mut receiver) = channels();
+
+        assert!(sender.send(vec![0; MAX_DATAGRAM_SIZE]).is_err());
+        assert!(sender.unreliable(vec![0; MAX_DATAGRAM_SIZE]).is_err());
+
+        let packet = sender.send(vec![1]).unwrap();
+        assert_eq!(payloads(&receiver.receive(&packet).unwrap()), [1]);
+    }
+
+    #[test]
+    fn puts_split_payloads_back_together() {
+        let (mut sender, mut receiver) = channels();
+        let payload: Vec<u8> = (0..PIECE_SIZE * 2 + 10).map(|i| i as u8).collect();
+        let packets = sender.send_split(payload.clone()).unwrap();
+        assert_eq!(packets.len(), 3);
+
+        assert!(receiver.receive(&packets[2]).unwrap().payloads.is_empty());
+        assert!(receiver.receive(&packets[0]).unwrap().payloads.is_empty());
+        let received = receiver.receive(&packets[1]).unwrap();
+        assert_eq!(received.payloads, [payload]);
+
+        // small payloads still go in one packet
+        let packets = sender.send_split(vec![1]).unwrap();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(payloads(&receiver.receive(&packets[0]).unwrap()), [1]);
+    }
+
+    #[test]
+    fn refuses_split_payloads_over_the_limit() {
+        let (mut sender, _) = channels();
+
+        assert!(sender.send_split(vec![0; MAX_SPLIT_PAYLOAD + 1]).is_err());
+        assert_eq!(sender.next_send_seq, 0);
+    }
+
+    #[test]
+    fn ignores_other_connections() {
+        let mut sender = ReliableChannel::new(1);
+        let mut receiver = ReliableChannel::new(2);
+        let packet = sender.send(vec![1]).unwrap();
+
+        let received = receiver.receive(&packet).unwrap();
+        assert!(received.ack.is_none());
+        assert!(received.payloads.is_empty());
+    }
+
+    #[test]
+    fn resends_until_acknowledged() {
+        let (mut sender, mut receiver) = channels();
+        let packet = sender.send(vec![1]).unwrap();
+        let start = Instant::now();
+
+        assert!(sender.resends_at(start).unwrap().is_empty());
+        let resent = sender.resends_at(start + RESEND_INTERVAL).unwrap();
+        assert_eq!(resent.len(), 1);
+        assert_eq!(resent[0], packet);
+
+        let ack = receiver.receive(&packet).unwrap().ack.unwrap();
+        sender.receive(&ack).unwrap();
+        assert!(
+            sender
+                .resends_at(start + RESEND_INTERVAL * 2)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn fails_once_the_peer_stops_acknowledging() {
+        let (mut sender, _) = channels();
+        sender.send(vec![1]).unwrap();
+        let start = Instant::now();
+
+        for i in 1..=MAX_RESENDS {
+            assert_eq!(
+                sender
+                    .resends_at(start + RESEND_INTERVAL * i)
+                    .unwrap()
+                    .len(),
+                1
+            );
+        }
+        assert!(
+            sender
+                .resends_at(start + RESEND_INTERVAL * (MAX_RESENDS + 1))
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use crate::init::InitData;
+use anyhow::{Result, anyhow};
+use simulation::PlayerInput;
+use std::fs;
+use std::path::Path;
+use wincode::config::{self, Configuration};
+use wincode::{SchemaRead, SchemaWrite, deserialize};
+
+/// Bumped whenever the format changes or the simulation
+/// changes in a way that old replays play back differently
+pub const REPLAY_VERSION: u32 = 8;
+
+/// Ticks between two checksums in a replay
+pub const CHECKSUM_INTERVAL: u64 = 60;
+
+// wincode's default 4 MiB cap on preallocated memory only fits about
+// 16 minutes of ticks, this one fits matches of several hours
+const MAX_PREALLOCATION: usize = 256 << 20;
+
+fn file_config() -> Configuration<true, MAX_PREALLOCATION> {
+    Configuration::default().with_preallocation_size_limit()
+}
+
+/// Everything needed to re-simulate a match tick by tick
+#[derive(SchemaWrite, SchemaRead)]
+pub struct Replay {
+    // first so a replay of another version is recognized before decoding the rest
+    pub version: u32,
+    pub init_data: InitData,
+    // lag compensation window the match was played with
+    pub max_rewind_ticks: u64,
+    pub ticks: Vec<ReplayTick>,
+    // tick and `GameState::checksum` right after stepping it
+    pub checksums: Vec<(u64, u64)>,
+}
+
+/// What changed right before the server stepped a tick, players by slot
+#[derive(SchemaWrite, SchemaRead, Default)]
+pub struct ReplayTick {
+    pub events: Vec<ReplayEvent>,
+    // only the players whose input changed
+    pub inputs: Vec<(u8, PlayerInput)>,
+    // tick each player was viewing, their attacks are checked against it
+    pub views: Vec<(u8, u64)>,
+}
+
+#[derive(SchemaWrite, SchemaRead, Clone, Copy)]
+pub enum ReplayEvent {
+    Disconnected(u8),
+    Reconnected(u8),
+    Forfeited(u8),
+}
+
+impl Replay {
+    #[must_use]
+    pub fn new(init_data: InitData, max_rewind_ticks: u64) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            init_data,
+            max_rewind_ticks,
+            ticks: Vec::new(),
+            checksums: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes =
+            fs::read(path).map_err(|e| anyhow!("Failed to read '{}': {}", path.display(), e))?;
+
+        let version: u32 = deserialize(&bytes)
+            .map_err(|e| anyhow!("'{}' is not a replay: {}", path.display(), e))?;
+        if version != REPLAY_VERSION {
+            return Err(anyhow!(
+                "'{}' was recorded with replay version {version}, this build plays version {REPLAY_VERSION}",
+                path.display()
+            ));
+        }
+
+        config::deserialize(&bytes, file_config())
+            .map_err(|e| anyhow!("Failed to parse '{}': {}", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, config::serialize(self, file_config())?)
+            .map_err(|e| anyhow!("Failed to write '{}': {}", path.display(), e))
+    }
+
+    /// Number of ticks recorded
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        self.ticks.len() as u64
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.ticks.is_empty()
+    }
+
+    /// Checksum recorded right after stepping `tick`, if any
+    #[must_use]
+    pub fn checksum(&self, tick: u64) -> Option<u64> {
+        self.checksums
+            .binary_search_by_key(&tick, |(t, _)| *t)
+            .ok()
+            .map(|idx| self.checksums[idx].1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simulation::{balance::Balance, map::Map};
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    #[test]
+    fn loads_a_replay_of_an_hour_long_match() {
+        let init_data = InitData {
+            players: HashMap::new(),
+            teams: Vec::new(),
+            map: Map::default(),
+            balance: Balance::default(),
+        };
+        let mut replay = Replay::new(init_data, 30);
+        // an hour at 60 ticks per second
+        replay.ticks.resize_with(60 * 60 * 60, ReplayTick::default);
+        replay.ticks[0].inputs.push((0, PlayerInput::default()));
+        replay.checksums = (0..replay.len())
+            .step_by(CHECKSUM_INTERVAL as usize)
+            .map(|tick| (tick, tick))
+            .collect();
+
+        let path = std::env::temp_dir().join(format!("{}.replay", Uuid::new_v4()));
+        replay.save(&path).unwrap();
+        let loaded = Replay::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.len(), replay.len());
+        assert_eq!(loaded.ticks[0].inputs.len(), 1);
+        assert_eq!(loaded.checksum(CHECKSUM_INTERVAL), Some(CHECKSUM_INTERVAL));
+    }
+}
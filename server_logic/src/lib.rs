@@ -1,2 +1,5 @@
 mod network_server;
+pub mod rating;
+pub mod replay;
 pub mod runtime;
+pub mod store;
@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+// Glicko-2 constants, see http://www.glicko.net/glicko/glicko2.pdf
+const SCALE: f64 = 173.7178;
+const DEFAULT_RATING: f64 = 1500.0;
+const DEFAULT_DEVIATION: f64 = 350.0;
+const DEFAULT_VOLATILITY: f64 = 0.06;
+// how much the volatility can change, lower is steadier
+const TAU: f64 = 0.5;
+const EPSILON: f64 = 0.000_001;
+
+/// Glicko-2 rating of a player, every match is its own rating period
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Rating {
+    pub rating: f64,
+    // uncertainty, shrinks the more a player plays
+    pub deviation: f64,
+    pub volatility: f64,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Self {
+            rating: DEFAULT_RATING,
+            deviation: DEFAULT_DEVIATION,
+            volatility: DEFAULT_VOLATILITY,
+        }
+    }
+}
+
+impl Rating {
+    /// Rating after a match against `results`, pairs of the opponent's
+    /// rating and the score against them (1 win, 0.5 draw, 0 loss)
+    #[must_use]
+    pub fn updated(&self, results: &[(Rating, f64)]) -> Rating {
+        let mu = (self.rating - DEFAULT_RATING) / SCALE;
+        let phi = self.deviation / SCALE;
+
+        if results.is_empty() {
+            return Rating {
+                deviation: (phi.powi(2) + self.volatility.powi(2)).sqrt() * SCALE,
+                ..*self
+            };
+        }
+
+        let mut v_inv = 0.0;
+        let mut score_sum = 0.0;
+        for (opponent, score) in results {
+            let mu_j = (opponent.rating - DEFAULT_RATING) / SCALE;
+            let g = g(opponent.deviation / SCALE);
+            let e = 1.0 / (1.0 + (-g * (mu - mu_j)).exp());
+            v_inv += g.powi(2) * e * (1.0 - e);
+            score_sum += g * (score - e);
+        }
+        let v = 1.0 / v_inv;
+        let delta = v * score_sum;
+
+        let volatility = self.new_volatility(phi, v, delta);
+        let phi_star = (phi.powi(2) + volatility.powi(2)).sqrt();
+        let new_phi = 1.0 / (1.0 / phi_star.powi(2) + 1.0 / v).sqrt();
+        let new_mu = mu + new_phi.powi(2) * score_sum;
+
+        Rating {
+            rating: new_mu * SCALE + DEFAULT_RATING,
+            deviation: new_phi * SCALE,
+            volatility,
+        }
+    }
+
+    // step 5 of the paper, solved with the Illinois algorithm
+    fn new_volatility(&self, phi: f64, v: f64, delta: f64) -> f64 {
+        let a = self.volatility.powi(2).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            let d = phi.powi(2) + v + ex;
+            ex * (delta.powi(2) - d) / (2.0 * d.powi(2)) - (x - a) / TAU.powi(2)
+        };
+
+        let mut lower = a;
+        let mut upper = if delta.powi(2) > phi.powi(2) + v {
+            (delta.powi(2) - phi.powi(2) - v).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * TAU) < 0.0 {
+                k += 1.0;
+            }
+            a - k * TAU
+        };
+
+        let mut f_lower = f(lower);
+        let mut f_upper = f(upper);
+        while (upper - lower).abs() > EPSILON {
+            let c = lower + (lower - upper) * f_lower / (f_upper - f_lower);
+            let f_c = f(c);
+            if f_c * f_upper <= 0.0 {
+                lower = upper;
+                f_lower = f_upper;
+            } else {
+                f_lower /= 2.0;
+            }
+            upper = c;
+            f_upper = f_c;
+        }
+
+        (lower / 2.0).exp()
+    }
+}
+
+fn g(phi: f64) -> f64 {
+    1.0 / (1.0 + 3.0 * phi.powi(2) / PI.powi(2)).sqrt()
+}
+
+/// Ratings of every player after a match, `winner` is the index of the
+/// winning team or none for a draw. Every player is rated against each
+/// player on the other teams, players on two losing teams draw each other.
+#[must_use]
+pub fn rate_match(teams: &[Vec<Rating>], winner: Option<usize>) -> Vec<Vec<Rating>> {
+    teams
+        .iter()
+        .enumerate()
+        .map(|(team_idx, team)| {
+            let results: Vec<(Rating, f64)> = teams
+                .iter()
+                .enumerate()
+                .filter(|(other_idx, _)| *other_idx != team_idx)
+                .flat_map(|(other_idx, others)| {
+                    let score = match winner {
+                        Some(w) if w == team_idx => 1.0,
+                        Some(w) if w == other_idx => 0.0,
+                        _ => 0.5,
+                    };
+                    others.iter().map(move |r| (*r, score))
+                })
+                .collect();
+
+            team.iter().map(|player| player.updated(&results)).collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rating(rating: f64, deviation: f64) -> Rating {
+        Rating {
+            rating,
+            deviation,
+            volatility: DEFAULT_VOLATILITY,
+        }
+    }
+
+    #[test]
+    fn matches_the_example_from_the_paper() {
+        let updated = rating(1500.0, 200.0).updated(&[
+            (rating(1400.0, 30.0), 1.0),
+            (rating(1550.0, 100.0), 0.0),
+            (rating(1700.0, 300.0), 0.0),
+        ]);
+
+        assert!((updated.rating - 1464.06).abs() < 0.01);
+        assert!((updated.deviation - 151.52).abs() < 0.01);
+        assert!((updated.volatility - 0.05999).abs() < 0.00001);
+    }
+
+    #[test]
+    fn only_grows_the_deviation_without_results() {
+        let player = rating(1600.0, 200.0);
+        let updated = player.updated(&[]);
+
+        assert_eq!(updated.rating, player.rating);
+        assert!(updated.deviation > player.deviation);
+    }
+
+    #[test]
+    fn moves_winners_up_and_losers_down() {
+        let teams = vec![vec![Rating::default(); 2]; 2];
+        let rated = rate_match(&teams, Some(1));
+
+        for (loser, winner) in rated[0].iter().zip(&rated[1]) {
+            assert!(loser.rating < DEFAULT_RATING);
+            assert!(winner.rating > DEFAULT_RATING);
+            assert!(
+                (DEFAULT_RATING - loser.rating - (winner.rating - DEFAULT_RATING)).abs() < 1e-9
+            );
+        }
+    }
+
+    #[test]
+    fn leaves_equal_players_level_on_a_draw() {
+        let teams = vec![vec![Rating::default()]; 3];
+
+        for team in rate_match(&teams, None) {
+            assert!((team[0].rating - DEFAULT_RATING).abs() < 1e-9);
+            assert!(team[0].deviation < DEFAULT_DEVIATION);
+        }
+    }
+
+    #[test]
+    fn losing_teams_draw_each_other() {
+        let teams = vec![
+            vec![Rating::default()],
+            vec![rating(1700.0, 100.0)],
+            vec![rating(1300.0, 100.0)],
+        ];
+        let rated = rate_match(&teams, Some(0));
+
+        // the weaker loser gains on the stronger one, which makes up for part of the loss
+        assert!(rated[1][0].rating - 1700.0 < rated[2][0].rating - 1300.0);
+    }
+}
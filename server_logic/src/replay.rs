@@ -0,0 +1,87 @@
+use anyhow::Result;
+use protocol::init::{InitData, PlayerSlots};
+use protocol::replay::{CHECKSUM_INTERVAL, Replay, ReplayEvent, ReplayTick};
+use simulation::PlayerInput;
+use simulation::game_state::GameState;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Builds the replay of a match while the server runs it
+pub struct ReplayRecorder {
+    replay: Replay,
+    slots: PlayerSlots,
+    // last input written for each slot
+    inputs: Vec<PlayerInput>,
+    // waiting for the next tick
+    events: Vec<ReplayEvent>,
+}
+
+impl ReplayRecorder {
+    pub fn new(init_data: InitData, max_rewind_ticks: u64) -> Result<Self> {
+        let slots = init_data.player_slots()?;
+        let inputs = vec![PlayerInput::default(); init_data.players.len()];
+
+        Ok(Self {
+            replay: Replay::new(init_data, max_rewind_ticks),
+            slots,
+            inputs,
+            events: Vec::new(),
+        })
+    }
+
+    pub fn disconnected(&mut self, player_id: &Uuid) {
+        if let Some(slot) = self.slots.slot(player_id) {
+            self.events.push(ReplayEvent::Disconnected(slot));
+        }
+    }
+
+    pub fn reconnected(&mut self, player_id: &Uuid) {
+        if let Some(slot) = self.slots.slot(player_id) {
+            self.events.push(ReplayEvent::Reconnected(slot));
+        }
+    }
+
+    pub fn forfeited(&mut self, player_id: &Uuid) {
+        if let Some(slot) = self.slots.slot(player_id) {
+            self.events.push(ReplayEvent::Forfeited(slot));
+        }
+    }
+
+    /// Records what the next step will run with, call right before stepping `gs`
+    pub fn record_tick(&mut self, gs: &GameState, view_ticks: &HashMap<Uuid, u64>) {
+        let mut tick = ReplayTick {
+            events: std::mem::take(&mut self.events),
+            ..Default::default()
+        };
+
+        for (slot, last) in self.inputs.iter_mut().enumerate() {
+            let Some(player) = self.slots.id(slot as u8).and_then(|id| gs.players.get(&id)) else {
+                continue;
+            };
+            if player.input != *last {
+                *last = player.input.clone();
+                tick.inputs.push((slot as u8, player.input.clone()));
+            }
+        }
+
+        tick.views = view_ticks
+            .iter()
+            .filter_map(|(id, view_tick)| Some((self.slots.slot(id)?, *view_tick)))
+            .collect();
+        tick.views.sort_unstable();
+
+        self.replay.ticks.push(tick);
+    }
+
+    /// Call right after stepping `gs` through `tick`
+    pub fn record_checksum(&mut self, tick: u64, gs: &GameState) {
+        if tick.is_multiple_of(CHECKSUM_INTERVAL) {
+            self.replay.checksums.push((tick, gs.checksum()));
+        }
+    }
+
+    #[must_use]
+    pub fn finish(self) -> Replay {
+        self.replay
+    }
+}
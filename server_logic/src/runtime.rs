@@ -1,37 +1,505 @@
 use foundation::GameMode;
-use simulation::PlayerInput;
+use protocol::init::{InitData, NetInitData};
+use protocol::net_server::{LobbyError, PartyError};
 use simulation::game_state::GameState;
+use simulation::{Character, PlayerInput};
 use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
+use std::time::Duration;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::time::Instant;
 use uuid::Uuid;
 
+/// One queue for every mode the server hosts
 #[derive(Default)]
 pub struct Queues {
-    pub solos: Queue,
-    pub duos: Queue,
+    queues: HashMap<GameMode, Queue>,
 }
 
+impl Queues {
+    #[must_use]
+    pub fn new(modes: &[GameMode]) -> Self {
+        Self {
+            queues: modes.iter().map(|mode| (*mode, Queue::default())).collect(),
+        }
+    }
+
+    pub fn get_mut(&mut self, mode: &GameMode) -> Option<&mut Queue> {
+        self.queues.get_mut(mode)
+    }
+
+    /// Takes the player out of whichever queue they are in,
+    /// along with the rest of their group
+    pub fn remove(&mut self, session_id: Uuid) -> Option<Vec<Uuid>> {
+        self.queues
+            .values_mut()
+            .find_map(|queue| queue.remove(session_id))
+    }
+}
+
+/// How often queues are checked for a match, the allowed
+/// rating gap widens in between
+pub const MATCHMAKING_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Most running matches sent to a client looking for one to spectate
+pub const MAX_LISTED_GAMES: usize = 10;
+
+/// How far apart in rating queued players can be matched
+#[derive(Clone, Copy)]
+pub struct RatingGap {
+    // for players who just joined the queue
+    pub initial: f64,
+    // added for every second waited
+    pub growth: f64,
+}
+
+impl RatingGap {
+    #[must_use]
+    pub fn after(&self, waited: Duration) -> f64 {
+        self.initial + self.growth * waited.as_secs_f64()
+    }
+}
+
+/// Players who queued together, a party or a single player
+#[derive(Clone)]
+struct QueuedGroup {
+    players: Vec<Uuid>,
+    // average of the players
+    rating: f64,
+    since: Instant,
+}
+
+/// Teams taken out of the queue, along with the groups
+/// they were made of in case the match cannot start
+pub struct QueuedMatch {
+    pub teams: Vec<Vec<Uuid>>,
+    groups: Vec<QueuedGroup>,
+}
+
+/// Groups of players waiting for a match, a group always
+/// ends up on the same team
 #[derive(Default)]
 pub struct Queue {
-    players: VecDeque<Uuid>,
+    groups: VecDeque<QueuedGroup>,
 }
 
 impl Queue {
-    pub fn add(&mut self, session_id: Uuid) {
-        self.players.push_back(session_id)
+    pub fn add(&mut self, players: Vec<Uuid>, rating: f64) {
+        self.groups.push_back(QueuedGroup {
+            players,
+            rating,
+            since: Instant::now(),
+        })
     }
 
-    pub fn remove(&mut self, session_id: Uuid) {
-        self.players.retain(|&p| p != session_id)
+    /// Removes the whole group the player is in
+    pub fn remove(&mut self, session_id: Uuid) -> Option<Vec<Uuid>> {
+        let idx = self
+            .groups
+            .iter()
+            .position(|g| g.players.contains(&session_id))?;
+        self.groups.remove(idx).map(|g| g.players)
     }
 
+    /// Number of players, not groups
     pub fn len(&self) -> usize {
-        self.players.len()
+        self.groups.iter().map(|g| g.players.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    /// Looks for a match around each group in queue order, filling it with
+    /// the groups closest in rating to the anchoring group such that the
+    /// highest and lowest rated are no further apart than the gap it has
+    /// waited for. Groups that fit nowhere keep their place.
+    pub fn take_match(&mut self, mode: GameMode, gap: RatingGap) -> Option<QueuedMatch> {
+        if self.len() < mode.players() {
+            return None;
+        }
+
+        for (anchor_idx, anchor) in self.groups.iter().enumerate() {
+            let allowed = gap.after(anchor.since.elapsed());
+            let distance = |idx: usize| (self.groups[idx].rating - anchor.rating).abs();
+
+            // every match within the gap starts at one of these ratings,
+            // the range centred on the anchor is tried first
+            let mut lowest: Vec<f64> = self
+                .groups
+                .iter()
+                .map(|g| g.rating)
+                .filter(|&rating| rating <= anchor.rating && anchor.rating - rating <= allowed)
+                .collect();
+            lowest.sort_by(f64::total_cmp);
+            lowest.dedup();
+            let off_centre = |low: f64| (low + allowed / 2.0 - anchor.rating).abs();
+            lowest.sort_by(|&a, &b| off_centre(a).total_cmp(&off_centre(b)));
+
+            for low in lowest {
+                let mut candidates: Vec<usize> = (0..self.groups.len())
+                    .filter(|&idx| {
+                        idx != anchor_idx
+                            && (low..=low + allowed).contains(&self.groups[idx].rating)
+                    })
+                    .collect();
+                candidates.sort_by(|&a, &b| distance(a).total_cmp(&distance(b)));
+                candidates.insert(0, anchor_idx);
+
+                let Some((picked, teams)) = self.fill_teams(mode, &candidates) else {
+                    continue;
+                };
+                let teams = self.balance_teams(mode, &picked).unwrap_or(teams);
+
+                let mut picked = picked;
+                picked.sort_unstable_by(|a, b| b.cmp(a));
+                let groups = picked
+                    .into_iter()
+                    .filter_map(|idx| self.groups.remove(idx))
+                    .collect();
+                return Some(QueuedMatch { teams, groups });
+            }
+        }
+
+        None
+    }
+
+    /// Returns the groups of a match that did not start to the front of
+    /// the queue, keeping their waiting time, with only the players `keep`
+    /// still wants in
+    pub fn put_back(&mut self, matched: QueuedMatch, keep: impl Fn(&Uuid) -> bool) {
+        for mut group in matched.groups {
+            group.players.retain(|id| keep(id));
+            if !group.players.is_empty() {
+                self.groups.push_front(group);
+            }
+        }
+    }
+
+    // puts each candidate in the first team it fits, returning the
+    // groups used once every team is full
+    fn fill_teams(
+        &self,
+        mode: GameMode,
+        candidates: &[usize],
+    ) -> Option<(Vec<usize>, Vec<Vec<Uuid>>)> {
+        let mut teams: Vec<Vec<Uuid>> = vec![Vec::new(); mode.teams];
+        let mut picked = Vec::new();
+
+        for &idx in candidates {
+            let group = &self.groups[idx].players;
+            if let Some(team) = teams
+                .iter_mut()
+                .find(|team| team.len() + group.len() <= mode.team_size)
+            {
+                team.extend(group);
+                picked.push(idx);
+            }
+
+            if teams.iter().all(|team| team.len() == mode.team_size) {
+                return Some((picked, teams));
+            }
+        }
+
+        None
+    }
+
+    // spreads the picked groups so the teams are as even as possible,
+    // largest and highest rated groups first into the weakest team
+    fn balance_teams(&self, mode: GameMode, picked: &[usize]) -> Option<Vec<Vec<Uuid>>> {
+        let mut groups: Vec<&QueuedGroup> = picked.iter().map(|&idx| &self.groups[idx]).collect();
+        groups.sort_by(|a, b| {
+            b.players
+                .len()
+                .cmp(&a.players.len())
+                .then(b.rating.total_cmp(&a.rating))
+        });
+
+        let mut teams: Vec<(Vec<Uuid>, f64)> = vec![(Vec::new(), 0.0); mode.teams];
+        for group in groups {
+            let (team, total) = teams
+                .iter_mut()
+                .filter(|(team, _)| team.len() + group.players.len() <= mode.team_size)
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))?;
+            team.extend(&group.players);
+            *total += group.rating * group.players.len() as f64;
+        }
+
+        Some(teams.into_iter().map(|(team, _)| team).collect())
+    }
+}
+
+/// Players who queue together, led by the one who invited the rest
+#[derive(Clone)]
+pub struct Party {
+    pub leader: Uuid,
+    pub members: Vec<Uuid>,
+}
+
+#[derive(Default)]
+pub struct Parties {
+    parties: Vec<Party>,
+    // invitee to the player who invited them, only the latest invite is kept
+    invites: HashMap<Uuid, Uuid>,
+}
+
+impl Parties {
+    pub fn party_of(&self, session_id: Uuid) -> Option<&Party> {
+        self.parties
+            .iter()
+            .find(|p| p.members.contains(&session_id))
+    }
+
+    /// The players who queue along with `session_id`, including themselves
+    pub fn group_of(&self, session_id: Uuid) -> Vec<Uuid> {
+        match self.party_of(session_id) {
+            Some(party) => party.members.clone(),
+            None => vec![session_id],
+        }
     }
 
-    pub fn get_and_remove_players(&mut self, player_count: usize) -> Vec<Uuid> {
-        self.players.drain(..player_count).collect()
+    pub fn invite(&mut self, from: Uuid, to: Uuid) -> Result<(), PartyError> {
+        if self.party_of(from).is_some_and(|p| p.leader != from) {
+            return Err(PartyError::NotLeader);
+        }
+
+        self.invites.insert(to, from);
+        Ok(())
+    }
+
+    pub fn decline(&mut self, session_id: Uuid) -> Option<Uuid> {
+        self.invites.remove(&session_id)
+    }
+
+    /// Moves the invited player into the party of whoever invited them,
+    /// which is created if needed. Returns the party joined.
+    pub fn accept(&mut self, session_id: Uuid, max_size: usize) -> Result<Party, PartyError> {
+        let inviter = self
+            .invites
+            .remove(&session_id)
+            .ok_or(PartyError::NoInvite)?;
+
+        match self.party_of(inviter) {
+            Some(party) if party.leader != inviter => return Err(PartyError::NoInvite),
+            Some(party) if party.members.len() >= max_size => return Err(PartyError::PartyFull),
+            _ => {}
+        }
+
+        self.leave(session_id);
+        let idx = match self.parties.iter().position(|p| p.leader == inviter) {
+            Some(idx) => idx,
+            None => {
+                self.parties.push(Party {
+                    leader: inviter,
+                    members: vec![inviter],
+                });
+                self.parties.len() - 1
+            }
+        };
+
+        self.parties[idx].members.push(session_id);
+        Ok(self.parties[idx].clone())
+    }
+
+    /// Takes the player out of their party and returns who is left behind.
+    /// A party down to one player is disbanded, the longest standing
+    /// member takes over as leader otherwise.
+    pub fn leave(&mut self, session_id: Uuid) -> Vec<Uuid> {
+        let Some(idx) = self
+            .parties
+            .iter()
+            .position(|p| p.members.contains(&session_id))
+        else {
+            return Vec::new();
+        };
+
+        let party = &mut self.parties[idx];
+        party.members.retain(|&id| id != session_id);
+        if party.leader == session_id
+            && let Some(&next) = party.members.first()
+        {
+            party.leader = next;
+        }
+
+        let remaining = party.members.clone();
+        if remaining.len() < 2 {
+            self.parties.remove(idx);
+        }
+
+        remaining
+    }
+
+    /// Forgets the player along with any invite to or from them
+    pub fn remove(&mut self, session_id: Uuid) -> Vec<Uuid> {
+        self.invites
+            .retain(|to, from| *to != session_id && *from != session_id);
+        self.leave(session_id)
+    }
+}
+
+// no 0/O or 1/I to mix up when reading a code out loud
+const LOBBY_CODE_CHARS: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const LOBBY_CODE_LENGTH: usize = 5;
+
+/// Private lobbies by their join code
+#[derive(Default)]
+pub struct Lobbies {
+    lobbies: HashMap<String, Lobby>,
+}
+
+impl Lobbies {
+    /// Opens a lobby with `host` as its only member and returns its code
+    pub fn create(&mut self, mode: GameMode, host: Uuid) -> String {
+        let code = loop {
+            let code = random_lobby_code();
+            if !self.lobbies.contains_key(&code) {
+                break code;
+            }
+        };
+
+        self.lobbies
+            .insert(code.clone(), Lobby::new(code.clone(), mode, host));
+        code
+    }
+
+    pub fn get(&self, code: &str) -> Option<&Lobby> {
+        self.lobbies.get(code)
+    }
+
+    pub fn get_mut(&mut self, code: &str) -> Option<&mut Lobby> {
+        self.lobbies.get_mut(code)
+    }
+
+    /// Closes the lobby, e.g. when its match starts
+    pub fn close(&mut self, code: &str) -> Option<Lobby> {
+        self.lobbies.remove(code)
+    }
+
+    /// Takes the player out of their lobby, returning what is left of it.
+    /// The lobby is closed once the last member leaves.
+    pub fn remove(&mut self, session_id: Uuid) -> Option<Lobby> {
+        let code = self
+            .lobbies
+            .values()
+            .find(|l| l.contains(session_id))?
+            .code
+            .clone();
+
+        let lobby = self.lobbies.get_mut(&code)?;
+        lobby.leave(session_id);
+        if lobby.is_empty() {
+            self.lobbies.remove(&code);
+            return None;
+        }
+
+        Some(lobby.clone())
+    }
+}
+
+fn random_lobby_code() -> String {
+    let mut bits = Uuid::new_v4().as_u128();
+
+    (0..LOBBY_CODE_LENGTH)
+        .map(|_| {
+            let c = LOBBY_CODE_CHARS[(bits % LOBBY_CODE_CHARS.len() as u128) as usize];
+            bits /= LOBBY_CODE_CHARS.len() as u128;
+            c as char
+        })
+        .collect()
+}
+
+/// Players gathered by code who pick their own teams
+#[derive(Clone)]
+pub struct Lobby {
+    pub code: String,
+    pub mode: GameMode,
+    pub host: Uuid,
+    // in the order they picked the team
+    pub teams: Vec<Vec<Uuid>>,
+    pub unassigned: Vec<Uuid>,
+}
+
+impl Lobby {
+    #[must_use]
+    pub fn new(code: String, mode: GameMode, host: Uuid) -> Self {
+        Self {
+            code,
+            mode,
+            host,
+            teams: vec![Vec::new(); mode.teams],
+            unassigned: vec![host],
+        }
+    }
+
+    pub fn members(&self) -> impl Iterator<Item = &Uuid> {
+        self.teams.iter().flatten().chain(&self.unassigned)
+    }
+
+    #[must_use]
+    pub fn contains(&self, session_id: Uuid) -> bool {
+        self.members().any(|&id| id == session_id)
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.members().next().is_none()
+    }
+
+    pub fn join(&mut self, session_id: Uuid) -> Result<(), LobbyError> {
+        if self.contains(session_id) {
+            return Ok(());
+        }
+        if self.members().count() >= self.mode.players() {
+            return Err(LobbyError::LobbyFull);
+        }
+
+        self.unassigned.push(session_id);
+        Ok(())
+    }
+
+    /// Removes the player, the longest standing member takes over as host
+    pub fn leave(&mut self, session_id: Uuid) {
+        self.leave_team(session_id);
+
+        let next = self.members().next().copied();
+        if self.host == session_id
+            && let Some(next) = next
+        {
+            self.host = next;
+        }
+    }
+
+    /// Moves the player to the back of `team`
+    pub fn pick_team(&mut self, session_id: Uuid, team: usize) -> Result<(), LobbyError> {
+        let Some(picked) = self.teams.get(team) else {
+            return Err(LobbyError::InvalidTeam);
+        };
+        if picked.contains(&session_id) {
+            return Ok(());
+        }
+        if picked.len() >= self.mode.team_size {
+            return Err(LobbyError::TeamFull);
+        }
+
+        self.leave_team(session_id);
+        self.teams[team].push(session_id);
+        Ok(())
+    }
+
+    fn leave_team(&mut self, session_id: Uuid) {
+        for team in &mut self.teams {
+            team.retain(|&id| id != session_id);
+        }
+        self.unassigned.retain(|&id| id != session_id);
+    }
+
+    /// The teams to start the match with, once everyone has picked one
+    pub fn ready_teams(&self) -> Result<Vec<Vec<Uuid>>, LobbyError> {
+        if !self.unassigned.is_empty() || self.teams.iter().any(Vec::is_empty) {
+            return Err(LobbyError::NotReady);
+        }
+
+        Ok(self.teams.clone())
     }
 }
 
@@ -39,15 +507,24 @@ impl Queue {
 pub struct ClientSession {
     pub client_id: Uuid,
     pub player_name: String,
+    // played in the next match
+    pub character: Character,
     pub state: ClientState,
     pub addr: SocketAddr,
+    pub last_seen: Instant,
+    pub session_token: Uuid,
+    // profile in the store, outlives the session
+    pub account_id: Uuid,
 }
 
 #[derive(Debug)]
 pub enum ClientState {
     Menu,
     Queueing(GameMode),
+    InLobby(String),
     InGame,
+    /// Watching the match with this id
+    Spectating(Uuid),
 }
 
 pub struct GameSession {
@@ -62,6 +539,23 @@ pub struct GameHandle {
     pub game_id: Uuid,
     pub players: HashMap<Uuid, PlayerSlot>,
     pub input_tx: UnboundedSender<GameInput>,
+    pub connection_tx: UnboundedSender<ConnectionEvent>,
+    pub init_data: InitData,
+    // what players and spectators are sent
+    pub net_init_data: NetInitData,
+    // queued matches count towards ratings, private ones do not
+    pub rated: bool,
+}
+
+pub enum ConnectionEvent {
+    /// Connection lost, the player may still come back
+    Dropped(Uuid),
+    Resumed(Uuid, SocketAddr),
+    /// Gone for good
+    Left(Uuid),
+    /// Someone started watching, snapshots go to them too
+    SpectatorJoined(Uuid, SocketAddr),
+    SpectatorLeft(Uuid),
 }
 
 #[derive(Clone)]
@@ -69,10 +563,297 @@ pub struct PlayerSlot {
     pub team_id: usize,
     pub player_id: usize,
     pub client_id: Uuid,
+    pub account_id: Uuid,
 }
 
 pub struct GameInput {
     pub client_id: Uuid,
     pub client_tick: u64,
+    pub view_tick: u64,
+    pub snapshot_ack: Option<u64>,
     pub input: PlayerInput,
 }
+
+/// Most inputs held for a player, the oldest are dropped
+/// once a client runs this far ahead of the server
+pub const MAX_QUEUED_INPUTS: usize = 30;
+
+/// Inputs left waiting after a tick, enough to cover jitter. Anything
+/// beyond is folded into the current tick so a client that ticks a
+/// little faster than the server does not build up lag.
+pub const TARGET_QUEUED_INPUTS: usize = 2;
+
+/// Inputs of one player waiting to be simulated,
+/// one per server tick in the order the client made them
+#[derive(Default)]
+pub struct InputQueue {
+    inputs: VecDeque<GameInput>,
+    // newest client tick received
+    last_tick: Option<u64>,
+}
+
+impl InputQueue {
+    /// Ignores inputs that arrived late or twice
+    pub fn push(&mut self, input: GameInput) {
+        if self.last_tick.is_some_and(|last| input.client_tick <= last) {
+            return;
+        }
+
+        self.last_tick = Some(input.client_tick);
+        if self.inputs.len() == MAX_QUEUED_INPUTS {
+            self.inputs.pop_front();
+        }
+        self.inputs.push_back(input);
+    }
+
+    /// Input to simulate this tick, along with any that are
+    /// past the target depth
+    pub fn pop(&mut self) -> Option<GameInput> {
+        let mut input = self.inputs.pop_front()?;
+        while self.inputs.len() > TARGET_QUEUED_INPUTS {
+            let Some(next) = self.inputs.pop_front() else {
+                break;
+            };
+            input = GameInput {
+                input: input.input.followed_by(&next.input),
+                ..next
+            };
+        }
+
+        Some(input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXED_GAP: RatingGap = RatingGap {
+        initial: 100.0,
+        growth: 0.0,
+    };
+
+    fn queue(ratings: &[f64]) -> (Queue, Vec<Uuid>) {
+        let mut queue = Queue::default();
+        let players: Vec<Uuid> = ratings.iter().map(|_| Uuid::new_v4()).collect();
+        for (player, rating) in players.iter().zip(ratings) {
+            queue.add(vec![*player], *rating);
+        }
+        (queue, players)
+    }
+
+    #[test]
+    fn keeps_the_whole_match_within_the_gap() {
+        // everyone is within the gap of the first player, but the
+        // lowest and highest are twice the gap apart
+        let (mut queue, players) = queue(&[1100.0, 1000.0, 1200.0, 1190.0]);
+        assert!(queue.take_match(GameMode::new(2, 2), FIXED_GAP).is_none());
+
+        queue.add(vec![Uuid::new_v4()], 1150.0);
+        let matched = queue.take_match(GameMode::new(2, 2), FIXED_GAP).unwrap();
+        let picked: Vec<Uuid> = matched.teams.concat();
+        assert!(!picked.contains(&players[1]));
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn prefers_players_closest_to_the_first_in_queue() {
+        let (mut queue, players) = queue(&[1500.0, 1590.0, 1420.0, 1510.0]);
+
+        let matched = queue.take_match(GameMode::new(2, 1), FIXED_GAP).unwrap();
+        let picked: Vec<Uuid> = matched.teams.concat();
+        assert!(picked.contains(&players[0]) && picked.contains(&players[3]));
+        assert_eq!(queue.len(), 2);
+    }
+
+    #[test]
+    fn widens_the_gap_the_longer_a_player_waits() {
+        let gap = RatingGap {
+            initial: 100.0,
+            growth: 50.0,
+        };
+
+        assert_eq!(gap.after(Duration::ZERO), 100.0);
+        assert_eq!(gap.after(Duration::from_secs(4)), 300.0);
+    }
+
+    #[test]
+    fn balances_ratings_across_teams() {
+        let (mut queue, players) = queue(&[1500.0, 1530.0, 1560.0, 1590.0]);
+
+        let matched = queue.take_match(GameMode::new(2, 2), FIXED_GAP).unwrap();
+        // highest with lowest against the two in between
+        let with_top = matched
+            .teams
+            .iter()
+            .find(|team| team.contains(&players[3]))
+            .unwrap();
+        assert!(with_top.contains(&players[0]));
+    }
+
+    #[test]
+    fn keeps_a_party_on_one_team() {
+        let (mut queue, _) = queue(&[1500.0, 1510.0]);
+        let party = vec![Uuid::new_v4(), Uuid::new_v4()];
+        queue.add(party.clone(), 1505.0);
+
+        let matched = queue.take_match(GameMode::new(2, 2), FIXED_GAP).unwrap();
+        assert!(
+            matched
+                .teams
+                .iter()
+                .any(|team| party.iter().all(|id| team.contains(id)))
+        );
+    }
+
+    #[test]
+    fn skips_a_party_too_large_for_the_free_places() {
+        let (mut queue, players) = queue(&[1500.0]);
+        queue.add(vec![Uuid::new_v4(), Uuid::new_v4()], 1500.0);
+        queue.add(vec![Uuid::new_v4()], 1500.0);
+
+        let matched = queue.take_match(GameMode::new(2, 1), FIXED_GAP).unwrap();
+        assert!(matched.teams.concat().contains(&players[0]));
+        assert_eq!(queue.len(), 2);
+    }
+
+    #[test]
+    fn removes_the_whole_party_from_the_queue() {
+        let (mut queue, players) = queue(&[1500.0]);
+        let party = vec![Uuid::new_v4(), Uuid::new_v4()];
+        queue.add(party.clone(), 1500.0);
+
+        assert_eq!(queue.remove(party[1]), Some(party));
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.remove(players[0]), Some(vec![players[0]]));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn puts_back_those_still_queueing_at_the_front() {
+        let (mut queue, players) = queue(&[1500.0, 1500.0, 1500.0]);
+
+        let matched = queue.take_match(GameMode::new(2, 1), FIXED_GAP).unwrap();
+        let gone = players[0];
+        queue.put_back(matched, |id| *id != gone);
+        let late = Uuid::new_v4();
+        queue.add(vec![late], 1500.0);
+
+        assert_eq!(queue.len(), 3);
+        let rematched = queue.take_match(GameMode::new(2, 1), FIXED_GAP).unwrap();
+        assert!(rematched.teams.concat().contains(&players[1]));
+        assert_eq!(queue.remove(late), Some(vec![late]));
+    }
+
+    #[test]
+    fn forms_a_party_from_an_accepted_invite() {
+        let (leader, member) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut parties = Parties::default();
+
+        parties.invite(leader, member).unwrap();
+        let party = parties.accept(member, 2).unwrap();
+        assert_eq!(party.leader, leader);
+        assert_eq!(party.members, [leader, member]);
+        assert_eq!(parties.group_of(member), [leader, member]);
+    }
+
+    #[test]
+    fn only_the_leader_invites_and_parties_stay_within_size() {
+        let (leader, member, other) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let mut parties = Parties::default();
+        parties.invite(leader, member).unwrap();
+        parties.accept(member, 2).unwrap();
+
+        assert!(matches!(
+            parties.invite(member, other),
+            Err(PartyError::NotLeader)
+        ));
+        parties.invite(leader, other).unwrap();
+        assert!(matches!(
+            parties.accept(other, 2),
+            Err(PartyError::PartyFull)
+        ));
+        assert!(matches!(
+            parties.accept(other, 2),
+            Err(PartyError::NoInvite)
+        ));
+    }
+
+    #[test]
+    fn hands_the_party_over_when_the_leader_leaves() {
+        let players: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+        let mut parties = Parties::default();
+        for &member in &players[1..] {
+            parties.invite(players[0], member).unwrap();
+            parties.accept(member, 3).unwrap();
+        }
+
+        assert_eq!(parties.leave(players[0]), &players[1..]);
+        assert_eq!(parties.party_of(players[2]).unwrap().leader, players[1]);
+
+        // a party of one is no party
+        assert_eq!(parties.remove(players[1]), [players[2]]);
+        assert!(parties.party_of(players[2]).is_none());
+        assert_eq!(parties.group_of(players[2]), [players[2]]);
+    }
+
+    fn game_input(client_tick: u64, input: PlayerInput) -> GameInput {
+        GameInput {
+            client_id: Uuid::nil(),
+            client_tick,
+            view_tick: client_tick,
+            snapshot_ack: None,
+            input,
+        }
+    }
+
+    #[test]
+    fn catches_up_with_a_client_that_ticks_faster() {
+        let mut queue = InputQueue::default();
+        let mut client_tick = 0;
+
+        // three inputs arrive for every two server ticks
+        for _ in 0..100 {
+            for _ in 0..3 {
+                queue.push(game_input(client_tick, PlayerInput::default()));
+                client_tick += 1;
+            }
+            for _ in 0..2 {
+                queue.pop();
+                assert!(queue.inputs.len() <= TARGET_QUEUED_INPUTS);
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_presses_of_inputs_it_catches_up_on() {
+        let mut queue = InputQueue::default();
+        let mut jump = PlayerInput::default();
+        jump.set_jump(true);
+        let mut right = PlayerInput::default();
+        right.set_right(true);
+
+        queue.push(game_input(0, PlayerInput::default()));
+        queue.push(game_input(1, jump));
+        for tick in 2..2 + TARGET_QUEUED_INPUTS as u64 {
+            queue.push(game_input(tick, right.clone()));
+        }
+
+        let popped = queue.pop().unwrap();
+        assert!(popped.input.jump());
+        // the tick acknowledged is the newest one simulated
+        assert_eq!(popped.client_tick, 1);
+        assert_eq!(queue.inputs.len(), TARGET_QUEUED_INPUTS);
+    }
+
+    #[test]
+    fn ignores_late_and_repeated_inputs() {
+        let mut queue = InputQueue::default();
+        queue.push(game_input(5, PlayerInput::default()));
+        queue.push(game_input(5, PlayerInput::default()));
+        queue.push(game_input(3, PlayerInput::default()));
+
+        assert_eq!(queue.pop().map(|input| input.client_tick), Some(5));
+        assert!(queue.pop().is_none());
+    }
+}
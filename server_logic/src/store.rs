@@ -0,0 +1,242 @@
+use crate::rating::{Rating, rate_match};
+use anyhow::{Result, anyhow};
+use protocol::history::MatchRecord;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Most recent matches sent when a client asks for its history
+pub const HISTORY_LENGTH: usize = 20;
+
+/// Player account, created the first time a client without a known token says hello
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Profile {
+    // secret the client presents to log back in, never sent to anyone else
+    pub token: Uuid,
+    // last name the player used
+    pub name: String,
+    pub rating: Rating,
+    pub matches_played: u32,
+    pub wins: u32,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct MatchesFile {
+    matches: Vec<MatchRecord>,
+}
+
+/// Changes to the store's files, written with `write` on a thread
+/// that may block so the store itself is not held up by disk access
+#[must_use]
+pub struct StoreWrite {
+    profiles_path: PathBuf,
+    profiles: String,
+    matches_path: PathBuf,
+    // a finished match, as its own [[matches]] table
+    new_match: Option<String>,
+}
+
+impl StoreWrite {
+    pub fn write(self) -> Result<()> {
+        if let Some(dir) = self.profiles_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.profiles_path, self.profiles)
+            .map_err(|e| anyhow!("Failed to write '{}': {}", self.profiles_path.display(), e))?;
+
+        let Some(entry) = self.new_match else {
+            return Ok(());
+        };
+        if let Some(dir) = self.matches_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // appending a table keeps the file valid
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.matches_path)?;
+        writeln!(file, "{entry}")
+            .map_err(|e| anyhow!("Failed to write '{}': {}", self.matches_path.display(), e))
+    }
+}
+
+/// Player profiles and every finished match, kept in two TOML files.
+/// Profiles are rewritten on changes, matches are only ever appended.
+pub struct Store {
+    profiles_path: PathBuf,
+    matches_path: PathBuf,
+    profiles: HashMap<Uuid, Profile>,
+    // account id by token
+    tokens: HashMap<Uuid, Uuid>,
+    matches: Vec<MatchRecord>,
+}
+
+impl Store {
+    /// Reads both files, starting empty for any that does not exist yet
+    pub fn load(profiles_path: PathBuf, matches_path: PathBuf) -> Result<Self> {
+        let profiles: HashMap<Uuid, Profile> = match fs::read_to_string(&profiles_path) {
+            Ok(toml_str) => toml::from_str(&toml_str)
+                .map_err(|e| anyhow!("Failed to parse '{}': {}", profiles_path.display(), e))?,
+            Err(_) => HashMap::new(),
+        };
+        let matches = match fs::read_to_string(&matches_path) {
+            Ok(toml_str) => {
+                toml::from_str::<MatchesFile>(&toml_str)
+                    .map_err(|e| anyhow!("Failed to parse '{}': {}", matches_path.display(), e))?
+                    .matches
+            }
+            Err(_) => Vec::new(),
+        };
+
+        let tokens = profiles.iter().map(|(id, p)| (p.token, *id)).collect();
+
+        Ok(Self {
+            profiles_path,
+            matches_path,
+            profiles,
+            tokens,
+            matches,
+        })
+    }
+
+    /// The profiles as they are now, to be written to disk
+    pub fn save(&self) -> Result<StoreWrite> {
+        self.changes(None)
+    }
+
+    fn changes(&self, new_match: Option<&MatchRecord>) -> Result<StoreWrite> {
+        let new_match = new_match
+            .map(|record| {
+                toml::to_string(&MatchesFile {
+                    matches: vec![record.clone()],
+                })
+            })
+            .transpose()?;
+
+        Ok(StoreWrite {
+            profiles_path: self.profiles_path.clone(),
+            profiles: toml::to_string(&self.profiles)?,
+            matches_path: self.matches_path.clone(),
+            new_match,
+        })
+    }
+
+    /// Account `token` belongs to, if it is known
+    #[must_use]
+    pub fn account_id(&self, token: Option<&str>) -> Option<Uuid> {
+        token
+            .and_then(|t| Uuid::parse_str(t).ok())
+            .and_then(|t| self.tokens.get(&t).copied())
+    }
+
+    /// Account id and token of the profile `token` belongs to, a new profile
+    /// is created if the token is missing or unknown
+    pub fn login(&mut self, token: Option<&str>, name: &str) -> (Uuid, Uuid) {
+        if let Some(account_id) = self.account_id(token)
+            && let Some(profile) = self.profiles.get_mut(&account_id)
+        {
+            profile.name = name.to_string();
+            return (account_id, profile.token);
+        }
+
+        let account_id = Uuid::new_v4();
+        let token = Uuid::new_v4();
+        self.profiles.insert(
+            account_id,
+            Profile {
+                token,
+                name: name.to_string(),
+                rating: Rating::default(),
+                matches_played: 0,
+                wins: 0,
+            },
+        );
+        self.tokens.insert(token, account_id);
+
+        (account_id, token)
+    }
+
+    #[must_use]
+    pub fn profile(&self, account_id: Uuid) -> Option<&Profile> {
+        self.profiles.get(&account_id)
+    }
+
+    #[must_use]
+    pub fn rating(&self, account_id: Uuid) -> Rating {
+        self.profile(account_id)
+            .map(|p| p.rating)
+            .unwrap_or_default()
+    }
+
+    /// Adds a finished match, updating the profiles of everyone who played
+    /// and, for rated matches, their ratings. Returns the match with the
+    /// new ratings and the changes to write to disk.
+    pub fn record_match(&mut self, mut record: MatchRecord) -> Result<(MatchRecord, StoreWrite)> {
+        let account_ids: Vec<Option<Uuid>> = record
+            .players
+            .iter()
+            .map(|p| Uuid::parse_str(&p.account_id).ok())
+            .collect();
+
+        if record.rated {
+            let mut teams = vec![Vec::new(); record.mode.teams];
+            for (idx, player) in record.players.iter().enumerate() {
+                if let Some(team) = teams.get_mut(player.team) {
+                    team.push(idx);
+                }
+            }
+            let before: Vec<Vec<Rating>> = teams
+                .iter()
+                .map(|team| {
+                    team.iter()
+                        .map(|&idx| {
+                            account_ids[idx]
+                                .map(|id| self.rating(id))
+                                .unwrap_or_default()
+                        })
+                        .collect()
+                })
+                .collect();
+
+            let after = rate_match(&before, record.winner);
+            for (team, ratings) in teams.iter().zip(after) {
+                for (&idx, rating) in team.iter().zip(ratings) {
+                    record.players[idx].rating = Some(rating.rating);
+                    if let Some(profile) =
+                        account_ids[idx].and_then(|id| self.profiles.get_mut(&id))
+                    {
+                        profile.rating = rating;
+                    }
+                }
+            }
+        }
+
+        for (player, account_id) in record.players.iter().zip(&account_ids) {
+            if let Some(profile) = account_id.and_then(|id| self.profiles.get_mut(&id)) {
+                profile.matches_played += 1;
+                if record.winner == Some(player.team) {
+                    profile.wins += 1;
+                }
+            }
+        }
+
+        let write = self.changes(Some(&record))?;
+        self.matches.push(record.clone());
+        Ok((record, write))
+    }
+
+    /// Matches `account_id` played in, newest first
+    #[must_use]
+    pub fn history(&self, account_id: Uuid, limit: usize) -> Vec<&MatchRecord> {
+        let account_id = account_id.to_string();
+        self.matches
+            .iter()
+            .rev()
+            .filter(|m| m.player(&account_id).is_some())
+            .take(limit)
+            .collect()
+    }
+}
@@ -1,11 +1,11 @@
-use crate::constants::PLAYER_SIZE;
+use crate::balance::{AttackProperties, ProjectileProperties};
 use foundation::rect::Rect;
 use glam::Vec2;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use wincode::{SchemaRead, SchemaWrite};
 
-#[derive(Serialize, Deserialize, Clone, PartialEq, SchemaWrite, SchemaRead)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, SchemaWrite, SchemaRead)]
 pub enum AttackKind {
     Dash,
     Light,
@@ -13,52 +13,15 @@ pub enum AttackKind {
     Slam,
 }
 
-pub struct AttackProperties {
-    pub offset: f32,
-    pub size: f32,
-    pub duration: f32,
-    pub frame_count: usize,
-    pub stun: f32,
-    pub knockback_increase: f32,
-}
-
-impl AttackKind {
-    pub fn properties(&self) -> AttackProperties {
-        match self {
-            AttackKind::Dash => AttackProperties {
-                offset: 0.0,
-                size: PLAYER_SIZE,
-                duration: 0.3,
-                frame_count: 1,
-                stun: 0.5,
-                knockback_increase: 0.01,
-            },
-            AttackKind::Light => AttackProperties {
-                offset: 15.0,
-                size: PLAYER_SIZE + 30.0,
-                duration: 0.1,
-                frame_count: 4,
-                stun: 2.0,
-                knockback_increase: 0.01,
-            },
-            AttackKind::Normal => AttackProperties {
-                offset: 15.0,
-                size: PLAYER_SIZE + 30.0,
-                duration: 0.1,
-                frame_count: 4,
-                stun: 0.4,
-                knockback_increase: 0.015,
-            },
-            AttackKind::Slam => AttackProperties {
-                offset: 5.0,
-                size: PLAYER_SIZE + 10.0,
-                duration: 99.9,
-                frame_count: 1,
-                stun: 0.1,
-                knockback_increase: 0.02,
-            },
-        }
-    }
+/// Where an attack is in its frame data
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AttackPhase {
+    /// Winding up, nothing is hit yet
+    Startup,
+    /// The hitbox is out
+    Active,
+    /// The hitbox is gone and the attacker can't act yet
+    Recovery,
 }
 
 #[derive(Clone)]
@@ -67,12 +30,19 @@ pub struct Attack {
     pub size: f32,
     pub knockback: Vec2,
     pub kind: AttackKind,
-    pub duration: f32,
+    pub startup: f32,
+    pub active: f32,
+    pub recovery: f32,
     pub timer: f32,
     pub owner: Uuid,
     pub facing: Vec2,
     pub stun: f32,
     pub knockback_increase: f32,
+    // players this attack already landed on, each is hit once per move
+    pub targets_hit: Vec<Uuid>,
+    // fired off when startup ends instead of having a hitbox
+    pub projectile: Option<ProjectileProperties>,
+    pub fired: bool,
 
     // animation
     pub frame: usize,
@@ -81,38 +51,70 @@ pub struct Attack {
 
 impl Attack {
     #[must_use]
-    pub fn new(kind: AttackKind, owner: Uuid, facing: Vec2) -> Attack {
-        let properties = kind.properties();
+    pub fn new(
+        kind: AttackKind,
+        properties: &AttackProperties,
+        owner: Uuid,
+        facing: Vec2,
+    ) -> Attack {
         Attack {
             offset: properties.offset,
             size: properties.size,
-            knockback: facing * Self::base_knockback(&kind),
+            knockback: facing * properties.knockback,
             kind,
-            duration: properties.duration,
+            startup: properties.startup,
+            // the projectile is out while the attacker recovers
+            active: if properties.projectile.is_some() {
+                0.0
+            } else {
+                properties.active
+            },
+            recovery: properties.recovery,
             timer: 0.0,
             owner,
             facing,
             stun: properties.stun,
             knockback_increase: properties.knockback_increase,
+            targets_hit: Vec::new(),
+            projectile: properties.projectile,
+            fired: false,
             frame: 0,
             frame_count: properties.frame_count,
         }
     }
 
-    fn base_knockback(kind: &AttackKind) -> f32 {
-        match kind {
-            AttackKind::Dash => 0.0, // calculated at hit
-            AttackKind::Light => 450.0,
-            AttackKind::Normal => 450.0,
-            AttackKind::Slam => 600.0,
+    pub fn update(&mut self, dt: f32) {
+        self.timer += dt;
+
+        // frames are spread over the active window
+        let progress = if self.active > 0.0 {
+            (self.timer - self.startup).max(0.0) / self.active
+        } else {
+            0.0
+        };
+        self.frame = (progress * self.frame_count as f32).floor() as usize % self.frame_count;
+    }
+
+    #[must_use]
+    pub fn phase(&self) -> AttackPhase {
+        if self.timer < self.startup {
+            AttackPhase::Startup
+        } else if self.timer < self.startup + self.active {
+            AttackPhase::Active
+        } else {
+            AttackPhase::Recovery
         }
     }
 
-    pub fn update(&mut self, dt: f32) {
-        self.timer += dt;
+    /// Only active frames hit
+    #[must_use]
+    pub fn is_active(&self) -> bool {
+        self.phase() == AttackPhase::Active
+    }
 
-        self.frame = ((self.timer / self.duration) * self.frame_count as f32).floor() as usize
-            % self.frame_count;
+    #[must_use]
+    pub fn has_hit(&self, target: &Uuid) -> bool {
+        self.targets_hit.contains(target)
     }
 
     #[must_use]
@@ -142,22 +144,28 @@ impl Attack {
 
     #[must_use]
     pub fn is_expired(&self) -> bool {
-        self.timer >= self.duration
+        self.timer >= self.startup + self.active + self.recovery
     }
 
+    /// Hitbox around the owner's center, moved out in the aimed direction
     #[must_use]
-    pub fn get_rect(&self, player_pos: Vec2) -> Rect {
-        Rect::new(self.x(player_pos), self.y(player_pos), self.size, self.size)
+    pub fn get_rect(&self, owner_center: Vec2) -> Rect {
+        Rect::new(
+            self.x(owner_center),
+            self.y(owner_center),
+            self.size,
+            self.size,
+        )
     }
 
     #[must_use]
-    pub fn x(&self, player_pos: Vec2) -> f32 {
-        player_pos.x - self.offset + (self.offset * self.facing.x)
+    pub fn x(&self, owner_center: Vec2) -> f32 {
+        owner_center.x - self.size / 2.0 + (self.offset * self.facing.x)
     }
 
     #[must_use]
-    pub fn y(&self, player_pos: Vec2) -> f32 {
-        player_pos.y - self.offset + (self.offset * self.facing.y)
+    pub fn y(&self, owner_center: Vec2) -> f32 {
+        owner_center.y - self.size / 2.0 + (self.offset * self.facing.y)
     }
 
     #[must_use]
@@ -167,6 +175,6 @@ impl Attack {
 
     #[must_use]
     pub fn frame_count(&self) -> usize {
-        self.kind.properties().frame_count
+        self.frame_count
     }
 }
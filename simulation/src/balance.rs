@@ -0,0 +1,401 @@
+use crate::attack::AttackKind;
+use crate::character::Character;
+use crate::constants::PLAYER_SIZE;
+use serde::{Deserialize, Serialize};
+use wincode::{SchemaRead, SchemaWrite};
+
+/// The numbers characters, attacks and cooldowns play with. The server
+/// loads them from a balance file and sends them with the match, so both
+/// sides simulate the same values.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, SchemaWrite, SchemaRead)]
+pub struct Balance {
+    pub name: String,
+    pub characters: Characters,
+    pub cooldowns: Cooldowns,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, SchemaWrite, SchemaRead)]
+pub struct Characters {
+    pub standard: CharacterProperties,
+    pub heavy: CharacterProperties,
+    pub light: CharacterProperties,
+    pub ranged: CharacterProperties,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, SchemaWrite, SchemaRead)]
+pub struct CharacterProperties {
+    pub movement: Movement,
+    pub attacks: Attacks,
+}
+
+/// How a character moves and takes hits
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, SchemaWrite, SchemaRead)]
+pub struct Movement {
+    // width and height of the hitbox
+    pub size: f32,
+    pub run_speed: f32,
+    // fastest a slam goes
+    pub fall_speed: f32,
+    pub acceleration: f32,
+    pub gravity: f32,
+    pub jump_speed: f32,
+    // jumps without touching ground or a wall
+    pub air_jumps: u8,
+    pub dash_speed: f32,
+    // knockback taken is divided by this
+    pub weight: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, SchemaWrite, SchemaRead)]
+pub struct Attacks {
+    pub dash: AttackProperties,
+    pub light: AttackProperties,
+    pub normal: AttackProperties,
+    pub slam: AttackProperties,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, SchemaWrite, SchemaRead)]
+pub struct AttackProperties {
+    // how far the hitbox is moved from the attacker's center in the aimed direction
+    pub offset: f32,
+    pub size: f32,
+    // seconds before the hitbox comes out
+    pub startup: f32,
+    // seconds the hitbox is out, or how long a projectile flies
+    pub active: f32,
+    // seconds after that before the attacker can act again
+    pub recovery: f32,
+    // animation frames shown over the active window
+    pub frame_count: usize,
+    pub stun: f32,
+    pub knockback_increase: f32,
+    // speed the attack sends the target off with, the dash uses the attacker's
+    pub knockback: f32,
+    // fired off when startup ends instead of staying with the attacker
+    #[serde(default)]
+    pub projectile: Option<ProjectileProperties>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, SchemaWrite, SchemaRead)]
+pub struct ProjectileProperties {
+    pub speed: f32,
+    pub gravity: f32,
+}
+
+/// Seconds before an action can be used again
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, SchemaWrite, SchemaRead)]
+pub struct Cooldowns {
+    pub dash: f32,
+    pub normal: f32,
+    pub light: f32,
+    pub parry: f32,
+    // taken off the normal attack's cooldown when it lands
+    pub normal_hit: f32,
+}
+
+impl Balance {
+    #[must_use]
+    pub fn character(&self, character: Character) -> &CharacterProperties {
+        match character {
+            Character::Standard => &self.characters.standard,
+            Character::Heavy => &self.characters.heavy,
+            Character::Light => &self.characters.light,
+            Character::Ranged => &self.characters.ranged,
+        }
+    }
+}
+
+impl CharacterProperties {
+    /// Every attack with its name, for checking a balance file
+    #[must_use]
+    pub fn attacks(&self) -> [(&'static str, &AttackProperties); 4] {
+        [
+            ("dash", &self.attacks.dash),
+            ("light", &self.attacks.light),
+            ("normal", &self.attacks.normal),
+            ("slam", &self.attacks.slam),
+        ]
+    }
+
+    #[must_use]
+    pub fn attack(&self, kind: &AttackKind) -> &AttackProperties {
+        match kind {
+            AttackKind::Dash => &self.attacks.dash,
+            AttackKind::Light => &self.attacks.light,
+            AttackKind::Normal => &self.attacks.normal,
+            AttackKind::Slam => &self.attacks.slam,
+        }
+    }
+}
+
+impl Default for Balance {
+    fn default() -> Self {
+        Self {
+            name: String::from("default"),
+            characters: Characters {
+                standard: CharacterProperties {
+                    movement: Movement {
+                        size: PLAYER_SIZE,
+                        run_speed: 300.0,
+                        fall_speed: 600.0,
+                        acceleration: 5000.0,
+                        gravity: 1400.0,
+                        jump_speed: 500.0,
+                        air_jumps: 2,
+                        dash_speed: 1000.0,
+                        weight: 1.0,
+                    },
+                    attacks: Attacks {
+                        dash: AttackProperties {
+                            offset: 0.0,
+                            size: PLAYER_SIZE,
+                            startup: 0.0,
+                            active: 0.3,
+                            recovery: 0.0,
+                            frame_count: 1,
+                            stun: 0.5,
+                            knockback_increase: 0.01,
+                            knockback: 0.0,
+                            projectile: None,
+                        },
+                        light: AttackProperties {
+                            offset: 15.0,
+                            size: PLAYER_SIZE + 30.0,
+                            startup: 0.05,
+                            active: 0.1,
+                            recovery: 0.2,
+                            frame_count: 4,
+                            stun: 2.0,
+                            knockback_increase: 0.01,
+                            knockback: 450.0,
+                            projectile: None,
+                        },
+                        normal: AttackProperties {
+                            offset: 15.0,
+                            size: PLAYER_SIZE + 30.0,
+                            startup: 0.03,
+                            active: 0.1,
+                            recovery: 0.12,
+                            frame_count: 4,
+                            stun: 0.4,
+                            knockback_increase: 0.015,
+                            knockback: 450.0,
+                            projectile: None,
+                        },
+                        slam: AttackProperties {
+                            offset: 5.0,
+                            size: PLAYER_SIZE + 10.0,
+                            startup: 0.0,
+                            active: 99.9,
+                            recovery: 0.0,
+                            frame_count: 1,
+                            stun: 0.1,
+                            knockback_increase: 0.02,
+                            knockback: 600.0,
+                            projectile: None,
+                        },
+                    },
+                },
+                heavy: CharacterProperties {
+                    movement: Movement {
+                        size: 28.0,
+                        run_speed: 240.0,
+                        fall_speed: 700.0,
+                        acceleration: 4000.0,
+                        gravity: 1600.0,
+                        jump_speed: 520.0,
+                        air_jumps: 1,
+                        dash_speed: 850.0,
+                        weight: 1.4,
+                    },
+                    attacks: Attacks {
+                        dash: AttackProperties {
+                            offset: 0.0,
+                            size: 28.0,
+                            startup: 0.0,
+                            active: 0.3,
+                            recovery: 0.0,
+                            frame_count: 1,
+                            stun: 0.6,
+                            knockback_increase: 0.015,
+                            knockback: 0.0,
+                            projectile: None,
+                        },
+                        light: AttackProperties {
+                            offset: 19.0,
+                            size: 66.0,
+                            startup: 0.08,
+                            active: 0.12,
+                            recovery: 0.25,
+                            frame_count: 4,
+                            stun: 2.0,
+                            knockback_increase: 0.012,
+                            knockback: 520.0,
+                            projectile: None,
+                        },
+                        normal: AttackProperties {
+                            offset: 19.0,
+                            size: 66.0,
+                            startup: 0.06,
+                            active: 0.12,
+                            recovery: 0.18,
+                            frame_count: 4,
+                            stun: 0.45,
+                            knockback_increase: 0.02,
+                            knockback: 520.0,
+                            projectile: None,
+                        },
+                        slam: AttackProperties {
+                            offset: 5.0,
+                            size: 38.0,
+                            startup: 0.0,
+                            active: 99.9,
+                            recovery: 0.0,
+                            frame_count: 1,
+                            stun: 0.15,
+                            knockback_increase: 0.025,
+                            knockback: 700.0,
+                            projectile: None,
+                        },
+                    },
+                },
+                light: CharacterProperties {
+                    movement: Movement {
+                        size: 16.0,
+                        run_speed: 360.0,
+                        fall_speed: 560.0,
+                        acceleration: 6000.0,
+                        gravity: 1250.0,
+                        jump_speed: 480.0,
+                        air_jumps: 3,
+                        dash_speed: 1100.0,
+                        weight: 0.75,
+                    },
+                    attacks: Attacks {
+                        dash: AttackProperties {
+                            offset: 0.0,
+                            size: 16.0,
+                            startup: 0.0,
+                            active: 0.3,
+                            recovery: 0.0,
+                            frame_count: 1,
+                            stun: 0.45,
+                            knockback_increase: 0.01,
+                            knockback: 0.0,
+                            projectile: None,
+                        },
+                        light: AttackProperties {
+                            offset: 12.0,
+                            size: 40.0,
+                            startup: 0.03,
+                            active: 0.08,
+                            recovery: 0.15,
+                            frame_count: 4,
+                            stun: 1.8,
+                            knockback_increase: 0.008,
+                            knockback: 400.0,
+                            projectile: None,
+                        },
+                        normal: AttackProperties {
+                            offset: 12.0,
+                            size: 40.0,
+                            startup: 0.02,
+                            active: 0.08,
+                            recovery: 0.08,
+                            frame_count: 4,
+                            stun: 0.35,
+                            knockback_increase: 0.012,
+                            knockback: 400.0,
+                            projectile: None,
+                        },
+                        slam: AttackProperties {
+                            offset: 5.0,
+                            size: 26.0,
+                            startup: 0.0,
+                            active: 99.9,
+                            recovery: 0.0,
+                            frame_count: 1,
+                            stun: 0.1,
+                            knockback_increase: 0.015,
+                            knockback: 520.0,
+                            projectile: None,
+                        },
+                    },
+                },
+                ranged: CharacterProperties {
+                    movement: Movement {
+                        size: PLAYER_SIZE,
+                        run_speed: 290.0,
+                        fall_speed: 600.0,
+                        acceleration: 5000.0,
+                        gravity: 1400.0,
+                        jump_speed: 500.0,
+                        air_jumps: 2,
+                        dash_speed: 900.0,
+                        weight: 0.9,
+                    },
+                    attacks: Attacks {
+                        dash: AttackProperties {
+                            offset: 0.0,
+                            size: PLAYER_SIZE,
+                            startup: 0.0,
+                            active: 0.3,
+                            recovery: 0.0,
+                            frame_count: 1,
+                            stun: 0.5,
+                            knockback_increase: 0.01,
+                            knockback: 0.0,
+                            projectile: None,
+                        },
+                        light: AttackProperties {
+                            offset: 20.0,
+                            size: 14.0,
+                            startup: 0.1,
+                            active: 1.0,
+                            recovery: 0.25,
+                            frame_count: 1,
+                            stun: 1.2,
+                            knockback_increase: 0.01,
+                            knockback: 380.0,
+                            projectile: Some(ProjectileProperties {
+                                speed: 700.0,
+                                gravity: 0.0,
+                            }),
+                        },
+                        normal: AttackProperties {
+                            offset: 45.0,
+                            size: 36.0,
+                            startup: 0.04,
+                            active: 0.12,
+                            recovery: 0.12,
+                            frame_count: 4,
+                            stun: 0.3,
+                            knockback_increase: 0.012,
+                            knockback: 380.0,
+                            projectile: None,
+                        },
+                        slam: AttackProperties {
+                            offset: 5.0,
+                            size: PLAYER_SIZE + 10.0,
+                            startup: 0.0,
+                            active: 99.9,
+                            recovery: 0.0,
+                            frame_count: 1,
+                            stun: 0.1,
+                            knockback_increase: 0.02,
+                            knockback: 600.0,
+                            projectile: None,
+                        },
+                    },
+                },
+            },
+            cooldowns: Cooldowns {
+                dash: 3.0,
+                normal: 0.75,
+                light: 2.0,
+                parry: 4.0,
+                normal_hit: 0.25,
+            },
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::{
+    Player, balance::Balance, character::Character, game_state::GameState, map::Map,
+    simulation::SimulationCore,
+};
+use std::collections::HashMap;
+use std::fmt;
+use uuid::Uuid;
+
+/// Sets up a match directly, without going through the network
+#[derive(Clone)]
+pub struct MatchBuilder {
+    map: Map,
+    balance: Balance,
+    teams: Vec<Vec<Uuid>>,
+    // players left out play the standard character
+    characters: HashMap<Uuid, Character>,
+}
+
+#[derive(Debug)]
+pub enum MatchError {
+    /// The map has no spawn point for this player of this team
+    MissingSpawn {
+        team: usize,
+        player: usize,
+    },
+    DuplicatePlayer(Uuid),
+    /// Fewer than two teams have players
+    NotEnoughTeams,
+}
+
+impl fmt::Display for MatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchError::MissingSpawn { team, player } => write!(
+                f,
+                "Map has no spawn point for player {} of team {}",
+                player + 1,
+                team + 1
+            ),
+            MatchError::DuplicatePlayer(id) => write!(f, "Player {id} was added twice"),
+            MatchError::NotEnoughTeams => write!(f, "A match needs at least two teams"),
+        }
+    }
+}
+
+impl std::error::Error for MatchError {}
+
+impl MatchBuilder {
+    #[must_use]
+    pub fn new(map: Map) -> Self {
+        Self {
+            map,
+            balance: Balance::default(),
+            teams: Vec::new(),
+            characters: HashMap::new(),
+        }
+    }
+
+    /// Plays with `balance` instead of the default numbers
+    #[must_use]
+    pub fn balance(mut self, balance: Balance) -> Self {
+        self.balance = balance;
+        self
+    }
+
+    /// Adds a player to `team`, players spawn in the order they are added
+    #[must_use]
+    pub fn player(mut self, team: usize, player_id: Uuid) -> Self {
+        if self.teams.len() <= team {
+            self.teams.resize(team + 1, Vec::new());
+        }
+        self.teams[team].push(player_id);
+        self
+    }
+
+    /// Has the player play as `character`
+    #[must_use]
+    pub fn character(mut self, player_id: Uuid, character: Character) -> Self {
+        self.characters.insert(player_id, character);
+        self
+    }
+
+    /// Adds a whole team after the existing ones
+    #[must_use]
+    pub fn team(mut self, player_ids: impl IntoIterator<Item = Uuid>) -> Self {
+        self.teams.push(player_ids.into_iter().collect());
+        self
+    }
+
+    pub fn build_state(self) -> Result<GameState, MatchError> {
+        if self.teams.iter().filter(|team| !team.is_empty()).count() < 2 {
+            return Err(MatchError::NotEnoughTeams);
+        }
+
+        let mut players = HashMap::new();
+        for (team_idx, team) in self.teams.iter().enumerate() {
+            for (player_idx, player_id) in team.iter().enumerate() {
+                let spawn = self.map.spawn_position(team_idx, player_idx).ok_or(
+                    MatchError::MissingSpawn {
+                        team: team_idx,
+                        player: player_idx,
+                    },
+                )?;
+
+                let character = self.characters.get(player_id).copied().unwrap_or_default();
+                let player = Player::new(spawn, team_idx, character, &self.balance);
+
+                if players.insert(*player_id, player).is_some() {
+                    return Err(MatchError::DuplicatePlayer(*player_id));
+                }
+            }
+        }
+
+        Ok(GameState::new(players, self.teams, self.map, self.balance))
+    }
+
+    pub fn build(self) -> Result<SimulationCore, MatchError> {
+        Ok(SimulationCore::new(self.build_state()?))
+    }
+}
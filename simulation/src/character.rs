@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use wincode::{SchemaRead, SchemaWrite};
+
+/// Who a player picked to play as, what each one can do
+/// is set in the balance file
+#[derive(
+    Serialize,
+    Deserialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Default,
+    Debug,
+    SchemaWrite,
+    SchemaRead,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Character {
+    #[default]
+    Standard,
+    /// Big and slow, hits hard and is hard to knock away
+    Heavy,
+    /// Small and quick with an extra air jump, but easy to launch
+    Light,
+    /// Keeps opponents at a distance, the light attack is fired off
+    Ranged,
+}
+
+impl Character {
+    pub const ALL: [Character; 4] = [
+        Character::Standard,
+        Character::Heavy,
+        Character::Light,
+        Character::Ranged,
+    ];
+
+    /// The one after this in `ALL`, wrapping around
+    #[must_use]
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|c| *c == self).unwrap_or(0);
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+}
+
+impl fmt::Display for Character {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Character::Standard => "Standard",
+            Character::Heavy => "Heavy",
+            Character::Light => "Light",
+            Character::Ranged => "Ranged",
+        };
+        write!(f, "{name}")
+    }
+}
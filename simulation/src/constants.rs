@@ -16,18 +16,31 @@ pub const NAME_COLOR: Color = Color {
     a: 1.0,
 };
 
+// size of the standard character
 pub const PLAYER_SIZE: f32 = 20.0;
 
 // ticks per second
 pub const TICK_RATE: usize = 60;
 pub const FIXED_DT: f32 = 1.0 / TICK_RATE as f32;
 
-pub const MAX_SPEED: [f32; 2] = [300.0, 600.0];
-pub const ACCELERATION: f32 = 5000.0;
-pub const GRAVITY: f32 = 1400.0;
+// movement that differs per character is in the balance file
 pub const RESISTANCE: f32 = 1400.0;
 pub const WALL_SLIDE_SPEED: f32 = 0.0;
 
+// how far around a platform corner a ledge can be grabbed
+pub const LEDGE_GRAB_RANGE: f32 = 24.0;
+// longest a player can hang before letting go
+pub const LEDGE_HANG_TIME: f32 = 3.0;
+// hanging before a get-up option can be picked
+pub const LEDGE_GETUP_DELAY: f32 = 0.15;
+// intangible after the first grab since touching the ground
+pub const LEDGE_INTANGIBILITY: f32 = 1.0;
+pub const LEDGE_CLIMB_INTANGIBILITY: f32 = 0.3;
+pub const LEDGE_ROLL_INTANGIBILITY: f32 = 0.5;
+pub const LEDGE_ROLL_SPEED: f32 = 700.0;
+// no grabbing again right after letting go
+pub const LEDGE_REGRAB_COOLDOWN: f32 = 0.5;
+
 pub const RESPAWN_TIME: f32 = 2.5;
 
 pub const POST_GAME_TIMER: f32 = 5.0;
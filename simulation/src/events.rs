@@ -0,0 +1,30 @@
+use crate::attack::AttackKind;
+use crate::game_state::Outcome;
+use uuid::Uuid;
+
+/// Something that happened during an update, for bots, tests and
+/// anything else watching a match without rendering it
+#[derive(Clone, PartialEq, Debug)]
+pub enum GameEvent {
+    Hit {
+        attacker: Uuid,
+        target: Uuid,
+        kind: AttackKind,
+    },
+    /// Two dashes met, neither player was hit
+    DashClash {
+        attacker: Uuid,
+        target: Uuid,
+    },
+    /// `target` parried the attack of `attacker`
+    Parried {
+        attacker: Uuid,
+        target: Uuid,
+    },
+    /// Knocked out of the map, out of the match once no lives are left
+    Died {
+        player: Uuid,
+        lives_left: u8,
+    },
+    Ended(Outcome),
+}
@@ -1,38 +1,85 @@
-use crate::{Player, PlayerInput, constants::POST_GAME_TIMER, map::Map, player::HitResult};
+use crate::{
+    GameEvent, Player, PlayerInput, Projectile,
+    balance::Balance,
+    constants::POST_GAME_TIMER,
+    map::{Ledge, Map},
+    player::HitResult,
+};
 use foundation::rect::Rect;
 use std::collections::HashMap;
 use uuid::Uuid;
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Outcome {
+    /// Index of the last team with players left
+    Win(usize),
+    /// The last players were knocked out at the same time
+    Draw,
+}
+
 #[derive(Clone)]
 pub struct GameState {
     pub players: HashMap<Uuid, Player>,
-    pub teams: [Vec<Uuid>; 2],
+    pub teams: Vec<Vec<Uuid>>,
     pub map: Map,
-    pub winner: usize,
+    pub balance: Balance,
+    /// Attacks fired off and still flying
+    pub projectiles: Vec<Projectile>,
+    pub next_projectile_id: u32,
+    pub outcome: Option<Outcome>,
     pub post_game_timer: f32,
+    /// What happened during the last update
+    pub events: Vec<GameEvent>,
 }
 
 impl GameState {
-    pub fn new(players: HashMap<Uuid, Player>, teams: [Vec<Uuid>; 2]) -> Self {
+    pub fn new(
+        players: HashMap<Uuid, Player>,
+        teams: Vec<Vec<Uuid>>,
+        mut map: Map,
+        balance: Balance,
+    ) -> Self {
+        // place moving and timed platforms at their starting state
+        map.update(0.0);
+
         Self {
             players,
             teams,
-            map: Map::new(),
-            winner: 0,
+            map,
+            balance,
+            projectiles: Vec::new(),
+            next_projectile_id: 0,
+            outcome: None,
             post_game_timer: POST_GAME_TIMER,
+            events: Vec::new(),
         }
     }
 
-    pub fn update(&mut self, mut dt: f32) {
+    pub fn update(&mut self, dt: f32) {
+        self.update_with_views(dt, &HashMap::new());
+    }
+
+    /// Same as `update`, but attacks of a player listed in `views` are
+    /// checked against enemy hitboxes and invulnerability from that past
+    /// state, i.e. what the attacker saw on their screen when attacking.
+    pub fn update_with_views(&mut self, mut dt: f32, views: &HashMap<Uuid, &GameState>) {
+        self.events.clear();
         self.check_for_win();
 
         self.update_post_game_timer(dt);
 
-        if self.winner > 0 {
+        if self.outcome.is_some() {
             dt /= 2.0;
         }
 
-        let player_ids: Vec<_> = self.players.keys().cloned().collect();
+        // team order rather than hash order, so a replay steps players the same way
+        let player_ids: Vec<Uuid> = self
+            .teams
+            .iter()
+            .flatten()
+            .filter(|id| self.players.contains_key(id))
+            .copied()
+            .collect();
 
         let mut hits = Vec::new();
 
@@ -43,28 +90,56 @@ impl GameState {
             };
 
             let attacks = attacker.combat.attacks.clone();
+            let view = views.get(attacker_id);
 
-            for attack in attacks {
-                let atk_rect = attack.get_rect(attacker.physics.pos);
+            for (attack_idx, attack) in attacks.into_iter().enumerate() {
+                if !attack.is_active() {
+                    continue;
+                }
+
+                let atk_rect = attack.get_rect(attacker.physics.center());
 
                 for enemy_id in self.get_enemy_ids(attacker_id) {
+                    if attack.has_hit(&enemy_id) {
+                        continue;
+                    }
+
                     let enemy = match self.players.get(&enemy_id) {
                         Some(e) => e,
                         None => continue,
                     };
 
-                    if atk_rect.overlaps(&enemy.physics.get_rect()) {
-                        hits.push((attacker_id, enemy_id, attack.clone()));
+                    // the attacker could not have hit them while they were untouchable
+                    let seen = view.and_then(|gs| gs.players.get(&enemy_id));
+                    if seen.is_some_and(|past| past.status.invulnerable()) {
+                        continue;
+                    }
+
+                    let enemy_rect = seen.unwrap_or(enemy).physics.get_rect();
+
+                    if atk_rect.overlaps(&enemy_rect) {
+                        hits.push((attacker_id, attack_idx, enemy_id, attack.clone()));
                     }
                 }
             }
         }
 
-        for (_, target_id, attack) in &hits {
+        // a move reaches each target once, before hits start changing attacks
+        for (attacker_id, attack_idx, target_id, _) in &hits {
+            if let Some(attack) = self
+                .players
+                .get_mut(*attacker_id)
+                .and_then(|attacker| attacker.combat.attacks.get_mut(*attack_idx))
+            {
+                attack.targets_hit.push(*target_id);
+            }
+        }
+
+        for (_, _, target_id, attack) in &hits {
             let attacker_id = attack.owner();
 
-            let (attacker_pos, attacker_vel) = match self.players.get(&attacker_id) {
-                Some(attacker) => (attacker.physics.pos, attacker.physics.vel),
+            let (attacker_rect, attacker_vel) = match self.players.get(&attacker_id) {
+                Some(attacker) => (attacker.physics.get_rect(), attacker.physics.vel),
                 None => continue,
             };
 
@@ -74,82 +149,286 @@ impl GameState {
                     None => continue,
                 };
 
-                target.apply_hit(attack, attacker_pos, attacker_vel)
+                target.apply_hit(attack, &attacker_rect, attacker_vel)
             };
 
             match result {
                 HitResult::Hit => {
+                    let combo = self.players.get_mut(target_id).map_or(0, |target| {
+                        target.stats.hits_taken += 1;
+                        target.combat.combo
+                    });
                     if let Some(attacker) = self.players.get_mut(&attacker_id) {
-                        attacker.apply_hit_effects(attack);
+                        attacker.apply_hit_effects(attack, &self.balance);
+                        attacker.stats.hits_landed += 1;
+                        attacker.stats.best_combo = attacker.stats.best_combo.max(combo);
                     }
+                    self.events.push(GameEvent::Hit {
+                        attacker: attacker_id,
+                        target: *target_id,
+                        kind: attack.kind().clone(),
+                    });
                 }
 
                 HitResult::DashClash => {
                     if let Some(attacker) = self.players.get_mut(&attacker_id) {
                         attacker.apply_dash_clash_effects(attack);
                     }
+                    self.events.push(GameEvent::DashClash {
+                        attacker: attacker_id,
+                        target: *target_id,
+                    });
                 }
 
                 HitResult::Parried => {
+                    if let Some(target) = self.players.get_mut(target_id) {
+                        target.stats.parries += 1;
+                    }
                     if let Some(attacker) = self.players.get_mut(&attacker_id) {
                         attacker.apply_parry_penalty(attack);
                     }
+                    self.events.push(GameEvent::Parried {
+                        attacker: attacker_id,
+                        target: *target_id,
+                    });
                 }
 
                 HitResult::Ignored => {}
             }
         }
 
+        self.update_map(dt);
+
         for player_id in &player_ids {
-            let enemy_ids = self.get_enemy_ids(player_id);
-            let enemies: Vec<(Rect, bool)> = enemy_ids
-                .iter()
-                .filter_map(|enemy_id| self.players.get(enemy_id))
-                .map(|enemy| (enemy.physics.get_rect(), enemy.status.invulnerable()))
-                .collect();
-
-            let player = match self.players.get_mut(player_id) {
-                Some(p) if p.combat.is_alive() => p,
-                _ => continue,
-            };
+            let lives = self.players.get(player_id).map(|p| p.combat.lives);
+            self.update_player(player_id, dt);
 
-            player.update(self.map.get_rect(), *player_id, &enemies, dt);
+            if let Some(player) = self.players.get(player_id)
+                && lives.is_some_and(|lives| player.combat.lives < lives)
+            {
+                self.events.push(GameEvent::Died {
+                    player: *player_id,
+                    lives_left: player.combat.lives,
+                });
+            }
         }
+
+        self.update_projectiles(dt);
     }
 
-    fn get_enemy_ids(&self, player_id: &Uuid) -> Vec<Uuid> {
-        if self.teams[0].contains(player_id) {
-            self.teams[1].clone()
-        } else {
-            self.teams[0].clone()
+    /// Advances a single player without resolving any hits,
+    /// which is also what the client predicts its own player with
+    pub fn update_player(&mut self, player_id: &Uuid, dt: f32) {
+        let enemy_ids = self.get_enemy_ids(player_id);
+        let enemies: Vec<(Rect, bool)> = enemy_ids
+            .iter()
+            .filter_map(|enemy_id| self.players.get(enemy_id))
+            .map(|enemy| (enemy.physics.get_rect(), enemy.status.invulnerable()))
+            .collect();
+
+        // a ledge holds one player at a time
+        let ledges_taken: Vec<Ledge> = self
+            .players
+            .iter()
+            .filter(|(id, _)| *id != player_id)
+            .filter_map(|(_, p)| p.physics.ledge)
+            .collect();
+
+        let player = match self.players.get_mut(player_id) {
+            Some(p) if p.combat.is_alive() => p,
+            _ => return,
+        };
+
+        player.update(
+            &self.map,
+            &self.balance,
+            *player_id,
+            &enemies,
+            &ledges_taken,
+            dt,
+        );
+
+        for mut projectile in player.combat.fired.drain(..) {
+            projectile.id = self.next_projectile_id;
+            self.next_projectile_id = self.next_projectile_id.wrapping_add(1);
+            self.projectiles.push(projectile);
+        }
+    }
+
+    /// Moves every projectile and lands the ones that reach an enemy,
+    /// in the order they were fired
+    fn update_projectiles(&mut self, dt: f32) {
+        let mut projectiles = std::mem::take(&mut self.projectiles);
+        projectiles.retain_mut(|projectile| {
+            let old_pos = projectile.pos;
+            projectile.update(dt);
+            let rect = projectile.get_rect();
+
+            let blocked = self.map.active_platforms().any(|platform| {
+                !platform.is_one_way() && projectile.swept_overlaps(old_pos, &platform.rect)
+            });
+            if projectile.is_expired() || blocked || !self.map.blast_zone().contains(rect.x, rect.y)
+            {
+                return false;
+            }
+
+            let target_id = self
+                .get_enemy_ids(&projectile.owner)
+                .into_iter()
+                .find(|id| {
+                    self.players.get(id).is_some_and(|p| {
+                        p.combat.is_alive()
+                            && !p.status.respawning()
+                            && rect.overlaps(&p.physics.get_rect())
+                    })
+                });
+            match target_id {
+                Some(target_id) => self.land_projectile(projectile, target_id),
+                None => true,
+            }
+        });
+        self.projectiles = projectiles;
+    }
+
+    /// Whether the projectile keeps flying after reaching the target
+    fn land_projectile(&mut self, projectile: &Projectile, target_id: Uuid) -> bool {
+        let attacker_id = projectile.owner;
+        let result = match self.players.get_mut(&target_id) {
+            Some(target) => target.apply_projectile_hit(projectile),
+            None => return true,
+        };
+
+        match result {
+            HitResult::Hit => {
+                let combo = self.players.get_mut(&target_id).map_or(0, |target| {
+                    target.stats.hits_taken += 1;
+                    target.combat.combo
+                });
+                if let Some(attacker) = self.players.get_mut(&attacker_id) {
+                    attacker.stats.hits_landed += 1;
+                    attacker.stats.best_combo = attacker.stats.best_combo.max(combo);
+                }
+                self.events.push(GameEvent::Hit {
+                    attacker: attacker_id,
+                    target: target_id,
+                    kind: projectile.kind.clone(),
+                });
+                false
+            }
+
+            HitResult::Parried => {
+                if let Some(target) = self.players.get_mut(&target_id) {
+                    target.stats.parries += 1;
+                }
+                self.events.push(GameEvent::Parried {
+                    attacker: attacker_id,
+                    target: target_id,
+                });
+                false
+            }
+
+            HitResult::DashClash | HitResult::Ignored => true,
         }
     }
 
+    fn update_map(&mut self, dt: f32) {
+        // find who is standing where before the platforms move
+        let standing: Vec<(Uuid, usize)> = self
+            .players
+            .iter()
+            .filter(|(_, p)| p.combat.is_alive() && !p.status.respawning())
+            .filter_map(|(id, p)| Some((*id, p.physics.standing_platform(&self.map)?)))
+            .collect();
+
+        self.map.update(dt);
+
+        for (player_id, platform_idx) in standing {
+            self.carry(&player_id, platform_idx, dt);
+        }
+    }
+
+    /// Moves the player along with the platform they stand on by its
+    /// current velocity, for clients that predict without moving platforms
+    pub fn carry_player(&mut self, player_id: &Uuid, dt: f32) {
+        let standing = self
+            .players
+            .get(player_id)
+            .filter(|p| p.combat.is_alive() && !p.status.respawning())
+            .and_then(|p| p.physics.standing_platform(&self.map));
+
+        if let Some(platform_idx) = standing {
+            self.carry(player_id, platform_idx, dt);
+        }
+    }
+
+    // carries a player along with the platform they stand on
+    fn carry(&mut self, player_id: &Uuid, platform_idx: usize, dt: f32) {
+        let platform = &self.map.platforms()[platform_idx];
+        if !platform.active {
+            return;
+        }
+
+        if let Some(player) = self.players.get_mut(player_id) {
+            player.physics.pos += platform.velocity() * dt;
+        }
+    }
+
+    /// Everyone not on the player's team
+    fn get_enemy_ids(&self, player_id: &Uuid) -> Vec<Uuid> {
+        self.teams
+            .iter()
+            .filter(|team| !team.contains(player_id))
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    #[must_use]
+    pub fn team_of(&self, player_id: &Uuid) -> Option<usize> {
+        self.teams.iter().position(|team| team.contains(player_id))
+    }
+
     fn update_post_game_timer(&mut self, dt: f32) {
-        if self.winner != 0 {
+        if self.outcome.is_some() {
             self.post_game_timer -= dt;
         }
     }
 
     pub fn check_for_win(&mut self) {
-        if self.winner != 0 {
+        if self.outcome.is_some() {
             return;
         }
 
-        let alive = [
-            self.teams[0]
-                .iter()
-                .any(|id| self.players[id].combat.is_alive()),
-            self.teams[1]
-                .iter()
-                .any(|id| self.players[id].combat.is_alive()),
-        ];
+        let mut alive_teams = self.teams.iter().enumerate().filter(|(_, team)| {
+            team.iter()
+                .any(|id| self.players.get(id).is_some_and(|p| p.combat.is_alive()))
+        });
 
-        self.winner = match alive {
-            [true, false] => 1,
-            [false, true] => 2,
-            _ => 0,
+        self.outcome = match (alive_teams.next(), alive_teams.next()) {
+            (None, _) => Some(Outcome::Draw),
+            (Some((team_idx, _)), None) => Some(Outcome::Win(team_idx)),
+            _ => None,
         };
+        if let Some(outcome) = self.outcome {
+            self.events.push(GameEvent::Ended(outcome));
+        }
+    }
+
+    /// A disconnected player stands still until they reconnect
+    pub fn set_disconnected(&mut self, player_id: &Uuid, disconnected: bool) {
+        if let Some(player) = self.players.get_mut(player_id) {
+            player.status.disconnected = disconnected;
+            player.input = PlayerInput::default();
+        }
+    }
+
+    /// Removes a player that left mid-match by taking their remaining lives,
+    /// the match continues as long as their team has someone left
+    pub fn forfeit_player(&mut self, player_id: &Uuid) {
+        self.set_disconnected(player_id, true);
+        if let Some(player) = self.players.get_mut(player_id) {
+            player.combat.lives = 0;
+        }
     }
 
     pub fn apply_input(&mut self, player: &Uuid, input: PlayerInput) {
@@ -159,4 +438,48 @@ impl GameState {
     pub fn is_game_over(&self) -> bool {
         self.post_game_timer <= 0.0
     }
+
+    /// Hash of the state that decides how the match plays out,
+    /// replays compare it to notice a re-simulation drifting off
+    #[must_use]
+    pub fn checksum(&self) -> u64 {
+        let mut hash = FNV_OFFSET;
+        for player in self
+            .teams
+            .iter()
+            .flatten()
+            .filter_map(|id| self.players.get(id))
+        {
+            for value in [
+                player.physics.pos.x,
+                player.physics.pos.y,
+                player.physics.vel.x,
+                player.physics.vel.y,
+                player.combat.knockback_multiplier,
+            ] {
+                fnv_feed(&mut hash, value.to_bits().into());
+            }
+            fnv_feed(&mut hash, player.combat.lives.into());
+            fnv_feed(&mut hash, player.combat.combo.into());
+        }
+        for projectile in &self.projectiles {
+            fnv_feed(&mut hash, projectile.pos.x.to_bits().into());
+            fnv_feed(&mut hash, projectile.pos.y.to_bits().into());
+        }
+        for platform in self.map.platforms() {
+            fnv_feed(&mut hash, platform.rect.x.to_bits().into());
+            fnv_feed(&mut hash, platform.rect.y.to_bits().into());
+        }
+        hash
+    }
+}
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+fn fnv_feed(hash: &mut u64, value: u64) {
+    for byte in value.to_le_bytes() {
+        *hash ^= u64::from(byte);
+        *hash = hash.wrapping_mul(FNV_PRIME);
+    }
 }
@@ -0,0 +1,51 @@
+use crate::game_state::GameState;
+use std::collections::VecDeque;
+
+/// Recent game states kept by the server so a client's
+/// attacks can be checked against what that client saw.
+/// Replays keep the same history to re-simulate matches exactly.
+#[derive(Clone)]
+pub struct StateHistory {
+    states: VecDeque<(u64, GameState)>,
+    max_rewind: u64,
+}
+
+impl StateHistory {
+    #[must_use]
+    pub fn new(max_rewind: u64) -> Self {
+        Self {
+            states: VecDeque::new(),
+            max_rewind,
+        }
+    }
+
+    /// Keeps a copy of the state after `tick`, nothing when the window is 0
+    pub fn push(&mut self, tick: u64, gs: &GameState) {
+        if self.max_rewind == 0 {
+            return;
+        }
+
+        while self
+            .states
+            .front()
+            .is_some_and(|(t, _)| *t + self.max_rewind < tick)
+        {
+            self.states.pop_front();
+        }
+
+        self.states.push_back((tick, gs.clone()));
+    }
+
+    /// State at `view_tick`, clamped to the rewind window ending at `current_tick`,
+    /// none when lag compensation is disabled
+    #[must_use]
+    pub fn view(&self, view_tick: u64, current_tick: u64) -> Option<&GameState> {
+        let tick = view_tick.clamp(current_tick.saturating_sub(self.max_rewind), current_tick);
+
+        self.states
+            .iter()
+            .rev()
+            .find(|(t, _)| *t <= tick)
+            .map(|(_, gs)| gs)
+    }
+}
@@ -1,14 +1,26 @@
 pub mod attack;
+pub mod balance;
+pub mod builder;
+pub mod character;
 pub mod constants;
+pub mod events;
 pub mod game_state;
+pub mod lag_compensation;
 pub mod map;
 pub mod player;
+pub mod projectile;
 pub mod simulation;
 pub mod utils;
 
+pub use builder::MatchBuilder;
+pub use character::Character;
+pub use events::GameEvent;
+pub use player::Action;
 pub use player::Player;
 pub use player::PlayerCombat;
 pub use player::PlayerCooldowns;
 pub use player::PlayerInput;
 pub use player::PlayerPhysics;
+pub use player::PlayerStats;
 pub use player::PlayerStatus;
+pub use projectile::Projectile;
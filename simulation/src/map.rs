@@ -7,11 +7,181 @@ use crate::{
 };
 use foundation::color::Color;
 use foundation::rect::Rect;
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use wincode::{SchemaRead, SchemaWrite};
 
-#[derive(Clone)]
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default, SchemaWrite, SchemaRead)]
+#[serde(rename_all = "snake_case")]
+pub enum PlatformKind {
+    /// Collides on all four sides
+    #[default]
+    Solid,
+    /// Only collides from above and can be dropped through
+    OneWay,
+}
+
+/// Closed loop of top-left positions a platform travels along
+#[derive(Serialize, Deserialize, Clone, SchemaWrite, SchemaRead)]
+pub struct PlatformPath {
+    pub waypoints: Vec<[f32; 2]>,
+    // pixels per second
+    pub speed: f32,
+}
+
+impl PlatformPath {
+    #[must_use]
+    pub fn position_at(&self, time: f32) -> Option<Vec2> {
+        let first = Vec2::from(*self.waypoints.first()?);
+        let segments: Vec<(Vec2, Vec2)> = self
+            .waypoints
+            .iter()
+            .zip(self.waypoints.iter().cycle().skip(1))
+            .map(|(a, b)| (Vec2::from(*a), Vec2::from(*b)))
+            .collect();
+
+        let total: f32 = segments.iter().map(|(a, b)| a.distance(*b)).sum();
+        if total <= 0.0 {
+            return Some(first);
+        }
+
+        let mut distance = (time * self.speed).rem_euclid(total);
+        for (a, b) in segments {
+            let len = a.distance(b);
+            if distance <= len {
+                return Some(a.lerp(b, distance / len));
+            }
+            distance -= len;
+        }
+
+        Some(first)
+    }
+}
+
+/// Repeating cycle of `active` seconds present followed by `inactive` seconds gone
+#[derive(Serialize, Deserialize, Clone, SchemaWrite, SchemaRead)]
+pub struct PlatformSchedule {
+    pub active: f32,
+    pub inactive: f32,
+    #[serde(default)]
+    pub offset: f32,
+}
+
+impl PlatformSchedule {
+    #[must_use]
+    pub fn is_active_at(&self, time: f32) -> bool {
+        let period = self.active + self.inactive;
+        if period <= 0.0 {
+            return true;
+        }
+
+        (time + self.offset).rem_euclid(period) < self.active
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, SchemaWrite, SchemaRead)]
+pub struct Platform {
+    pub rect: Rect,
+    #[serde(default)]
+    pub kind: PlatformKind,
+    pub path: Option<PlatformPath>,
+    pub schedule: Option<PlatformSchedule>,
+
+    // runtime state
+    #[serde(skip)]
+    pub vel: [f32; 2],
+    #[serde(skip, default = "default_active")]
+    pub active: bool,
+}
+
+impl Platform {
+    #[must_use]
+    pub fn new(rect: Rect, kind: PlatformKind) -> Self {
+        Self {
+            rect,
+            kind,
+            path: None,
+            schedule: None,
+            vel: [0.0, 0.0],
+            active: true,
+        }
+    }
+
+    pub fn update(&mut self, time: f32, dt: f32) {
+        if let Some(pos) = self.path.as_ref().and_then(|p| p.position_at(time)) {
+            if dt > 0.0 {
+                self.vel = [(pos.x - self.rect.x) / dt, (pos.y - self.rect.y) / dt];
+            }
+            self.rect.x = pos.x;
+            self.rect.y = pos.y;
+        }
+
+        if let Some(schedule) = &self.schedule {
+            self.active = schedule.is_active_at(time);
+        }
+    }
+
+    #[must_use]
+    pub fn is_one_way(&self) -> bool {
+        self.kind == PlatformKind::OneWay
+    }
+
+    /// Whether the platform changes during a match and has to be networked
+    #[must_use]
+    pub fn is_dynamic(&self) -> bool {
+        self.path.is_some() || self.schedule.is_some()
+    }
+
+    #[must_use]
+    pub fn velocity(&self) -> Vec2 {
+        Vec2::from(self.vel)
+    }
+}
+
+/// Which end of a platform a ledge is on
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, SchemaWrite, SchemaRead)]
+pub enum LedgeSide {
+    Left,
+    Right,
+}
+
+/// A top corner of a solid platform, players hang from it beside the platform
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, SchemaWrite, SchemaRead)]
+pub struct Ledge {
+    pub platform: usize,
+    pub side: LedgeSide,
+}
+
+impl LedgeSide {
+    /// Horizontal direction from the ledge onto its platform
+    #[must_use]
+    pub fn inward(self) -> f32 {
+        match self {
+            LedgeSide::Left => 1.0,
+            LedgeSide::Right => -1.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, SchemaWrite, SchemaRead)]
+pub struct SpawnPoint {
+    pub name: String,
+    pub team: usize,
+    pub pos: [f32; 2],
+}
+
+#[derive(Serialize, Deserialize, Clone, SchemaWrite, SchemaRead)]
 pub struct Map {
-    rect: Rect,
+    name: String,
+    platforms: Vec<Platform>,
+    spawn_points: Vec<SpawnPoint>,
+    blast_zone: Rect,
+    #[serde(default = "default_color")]
     color: Color,
+
+    // seconds since the match started, drives moving and timed platforms
+    #[serde(skip)]
+    time: f32,
 }
 
 impl Default for Map {
@@ -24,19 +194,136 @@ impl Map {
     #[must_use]
     pub fn new() -> Map {
         Map {
-            rect: Rect::new(
-                (VIRTUAL_WIDTH - 400.0) / 2.0,
-                (VIRTUAL_HEIGHT - 30.0) / 2.0,
-                400.0,
-                30.0
-            ),
+            name: String::from("Default"),
+            platforms: vec![Platform::new(
+                Rect::new(
+                    (VIRTUAL_WIDTH - 400.0) / 2.0,
+                    (VIRTUAL_HEIGHT - 30.0) / 2.0,
+                    400.0,
+                    30.0
+                ),
+                PlatformKind::Solid,
+            )],
+            spawn_points: vec![
+                SpawnPoint { name: String::from("left"), team: 0, pos: [820.0, 470.0] },
+                SpawnPoint { name: String::from("left_inner"), team: 0, pos: [870.0, 470.0] },
+                SpawnPoint { name: String::from("right"), team: 1, pos: [1160.0, 470.0] },
+                SpawnPoint { name: String::from("right_inner"), team: 1, pos: [1110.0, 470.0] },
+                SpawnPoint { name: String::from("left_center"), team: 0, pos: [920.0, 470.0] },
+                SpawnPoint { name: String::from("right_center"), team: 1, pos: [1060.0, 470.0] },
+                SpawnPoint { name: String::from("middle_left"), team: 2, pos: [965.0, 380.0] },
+                SpawnPoint { name: String::from("middle_right"), team: 2, pos: [1015.0, 380.0] },
+                SpawnPoint { name: String::from("top_left"), team: 3, pos: [940.0, 300.0] },
+                SpawnPoint { name: String::from("top_right"), team: 3, pos: [1040.0, 300.0] },
+            ],
+            blast_zone: Rect::new(0.0, -VIRTUAL_HEIGHT, VIRTUAL_WIDTH, VIRTUAL_HEIGHT * 2.0),
             color: MAP_COLOR,
+            time: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+
+        for platform in &mut self.platforms {
+            platform.update(self.time, dt);
+        }
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str { &self.name }
+
+    #[must_use]
+    pub fn platforms(&self) -> &[Platform] { &self.platforms }
+
+    #[must_use]
+    pub fn platforms_mut(&mut self) -> &mut [Platform] { &mut self.platforms }
+
+    /// Platforms that can currently be collided with
+    pub fn active_platforms(&self) -> impl Iterator<Item = &Platform> {
+        self.platforms.iter().filter(|p| p.active)
+    }
+
+    /// Ledges of every active solid platform, with the corner they sit on
+    pub fn ledges(&self) -> impl Iterator<Item = (Ledge, Vec2)> + '_ {
+        (0..self.platforms.len())
+            .flat_map(|platform| {
+                [LedgeSide::Left, LedgeSide::Right].map(|side| Ledge { platform, side })
+            })
+            .filter_map(|ledge| Some((ledge, self.ledge_corner(ledge)?)))
+    }
+
+    /// Corner of `ledge`, `None` while its platform can't be held on to
+    #[must_use]
+    pub fn ledge_corner(&self, ledge: Ledge) -> Option<Vec2> {
+        let platform = self.platforms.get(ledge.platform)?;
+        if !platform.active || platform.is_one_way() {
+            return None;
         }
+
+        let x = match ledge.side {
+            LedgeSide::Left => platform.rect.x,
+            LedgeSide::Right => platform.rect.x + platform.rect.w,
+        };
+        Some(Vec2::new(x, platform.rect.y))
+    }
+
+    #[must_use]
+    pub fn spawn_points(&self) -> &[SpawnPoint] { &self.spawn_points }
+
+    #[must_use]
+    pub fn spawn_point(&self, name: &str) -> Option<&SpawnPoint> {
+        self.spawn_points.iter().find(|s| s.name == name)
+    }
+
+    /// Position of the `player_idx`-th spawn point belonging to `team_idx`,
+    /// in the order they are listed in the map file.
+    #[must_use]
+    pub fn spawn_position(&self, team_idx: usize, player_idx: usize) -> Option<[f32; 2]> {
+        self.spawn_points
+            .iter()
+            .filter(|s| s.team == team_idx)
+            .nth(player_idx)
+            .map(|s| s.pos)
     }
 
     #[must_use]
-    pub fn get_rect(&self) -> &Rect { &self.rect }
+    pub fn blast_zone(&self) -> &Rect { &self.blast_zone }
 
     #[must_use]
     pub fn get_color(&self) -> Color { self.color.clone() }
+
+    /// Smallest rect containing every platform
+    #[must_use]
+    pub fn bounds(&self) -> Rect {
+        let mut platforms = self.platforms.iter().map(|p| p.rect);
+        let Some(first) = platforms.next() else {
+            return self.blast_zone;
+        };
+
+        platforms.fold(first, |acc, r| {
+            let x = acc.x.min(r.x);
+            let y = acc.y.min(r.y);
+            Rect::new(
+                x,
+                y,
+                (acc.x + acc.w).max(r.x + r.w) - x,
+                (acc.y + acc.h).max(r.y + r.h) - y,
+            )
+        })
+    }
+
+    #[must_use]
+    pub fn center(&self) -> Vec2 {
+        let bounds = self.bounds();
+        Vec2::new(bounds.x + bounds.w / 2.0, bounds.y + bounds.h / 2.0)
+    }
+}
+
+fn default_color() -> Color {
+    MAP_COLOR
+}
+
+fn default_active() -> bool {
+    true
 }
@@ -1,7 +1,9 @@
 use uuid::Uuid;
 
 use super::PlayerPhysics;
-use crate::attack::{Attack, AttackKind};
+use crate::attack::{Attack, AttackKind, AttackPhase};
+use crate::balance::{AttackProperties, CharacterProperties};
+use crate::projectile::Projectile;
 use crate::utils::tick_timers;
 
 #[derive(Clone)]
@@ -11,6 +13,8 @@ pub struct PlayerCombat {
     pub combo_timer: f32,
     pub knockback_multiplier: f32,
     pub attacks: Vec<Attack>,
+    // fired since the last update, until the game state takes them
+    pub fired: Vec<Projectile>,
 }
 
 impl Default for PlayerCombat {
@@ -21,6 +25,7 @@ impl Default for PlayerCombat {
             combo_timer: 0.0,
             knockback_multiplier: 1.0,
             attacks: Vec::default(),
+            fired: Vec::default(),
         }
     }
 }
@@ -60,9 +65,60 @@ impl PlayerCombat {
         self.combo_timer = 1.0;
     }
 
-    pub fn spawn_attack(&mut self, kind: AttackKind, physics: &PlayerPhysics, player_id: Uuid) {
+    pub fn spawn_attack(
+        &mut self,
+        kind: AttackKind,
+        properties: &AttackProperties,
+        physics: &PlayerPhysics,
+        player_id: Uuid,
+    ) {
+        // a held slam is one attack that lands again every tick
+        if kind == AttackKind::Slam
+            && let Some(slam) = self.attacks.iter_mut().find(|a| a.kind == AttackKind::Slam)
+        {
+            slam.targets_hit.clear();
+            return;
+        }
+
         self.attacks
-            .push(Attack::new(kind, player_id, physics.facing));
+            .push(Attack::new(kind, properties, player_id, physics.facing));
+    }
+
+    /// Fires the projectile of every attack that just finished its startup
+    pub fn fire_projectiles(
+        &mut self,
+        character: &CharacterProperties,
+        physics: &PlayerPhysics,
+        player_id: Uuid,
+    ) {
+        for attack in &mut self.attacks {
+            let Some(projectile) = &attack.projectile else {
+                continue;
+            };
+            if attack.fired || attack.phase() == AttackPhase::Startup {
+                continue;
+            }
+
+            attack.fired = true;
+            self.fired.push(Projectile::new(
+                attack.kind.clone(),
+                character.attack(&attack.kind),
+                projectile,
+                player_id,
+                physics.center(),
+                physics.aim(),
+            ));
+        }
+    }
+
+    /// In the middle of a light or normal attack, or winding up or recovering
+    /// from a dash or slam, which locks out other actions
+    #[must_use]
+    pub fn is_busy(&self) -> bool {
+        self.attacks.iter().any(|atk| {
+            matches!(atk.kind(), AttackKind::Light | AttackKind::Normal)
+                || atk.phase() != AttackPhase::Active
+        })
     }
 
     #[must_use]
@@ -1,3 +1,4 @@
+use crate::balance::Cooldowns;
 use crate::utils::tick_timers;
 
 #[derive(Clone)]
@@ -29,24 +30,24 @@ impl PlayerCooldowns {
         ], dt);
     }
 
-    pub fn activate_dash(&mut self) {
-        self.dash = 3.0;
+    pub fn activate_dash(&mut self, cooldowns: &Cooldowns) {
+        self.dash = cooldowns.dash;
     }
 
-    pub fn activate_normal(&mut self) {
-        self.normal = 0.75;
+    pub fn activate_normal(&mut self, cooldowns: &Cooldowns) {
+        self.normal = cooldowns.normal;
     }
 
-    pub fn activate_light(&mut self) {
-        self.light = 2.0;
+    pub fn activate_light(&mut self, cooldowns: &Cooldowns) {
+        self.light = cooldowns.light;
     }
 
-    pub fn activate_parry(&mut self) {
-        self.parry = 4.0;
+    pub fn activate_parry(&mut self, cooldowns: &Cooldowns) {
+        self.parry = cooldowns.parry;
     }
 
-    pub fn normal_hit(&mut self) {
-        self.normal -= 0.25;
+    pub fn normal_hit(&mut self, cooldowns: &Cooldowns) {
+        self.normal -= cooldowns.normal_hit;
     }
 
     #[must_use]
@@ -1,6 +1,13 @@
 use super::PlayerInput;
-use super::{PlayerCombat, PlayerCooldowns, PlayerPhysics, PlayerStatus};
-use crate::constants::PLAYER_SIZE;
+use super::{PlayerCombat, PlayerCooldowns, PlayerPhysics, PlayerStats, PlayerStatus};
+use crate::balance::Balance;
+use crate::character::Character;
+use crate::constants::{
+    LEDGE_CLIMB_INTANGIBILITY, LEDGE_GETUP_DELAY, LEDGE_HANG_TIME, LEDGE_INTANGIBILITY,
+    LEDGE_REGRAB_COOLDOWN, LEDGE_ROLL_INTANGIBILITY, LEDGE_ROLL_SPEED,
+};
+use crate::map::{Ledge, Map};
+use crate::projectile::Projectile;
 use crate::{
     attack::{Attack, AttackKind},
     utils::get_combo_multiplier,
@@ -18,30 +25,45 @@ pub enum HitResult {
 
 #[derive(Clone)]
 pub struct Player {
+    pub character: Character,
     pub combat: PlayerCombat,
     pub cooldowns: PlayerCooldowns,
     pub physics: PlayerPhysics,
     pub status: PlayerStatus,
     pub input: PlayerInput,
+    pub stats: PlayerStats,
 }
 
 impl Player {
     #[must_use]
-    pub fn new(start_pos: [f32; 2], team_idx: usize) -> Self {
+    pub fn new(
+        start_pos: [f32; 2],
+        team_idx: usize,
+        character: Character,
+        balance: &Balance,
+    ) -> Self {
         Self {
+            character,
             combat: PlayerCombat::default(),
             cooldowns: PlayerCooldowns::default(),
-            physics: PlayerPhysics::new(start_pos.into(), team_idx),
+            physics: PlayerPhysics::new(
+                start_pos.into(),
+                team_idx,
+                balance.character(character).movement,
+            ),
             status: PlayerStatus::default(),
             input: PlayerInput::new(),
+            stats: PlayerStats::default(),
         }
     }
 
     pub fn update(
         &mut self,
-        map: &Rect,
+        map: &Map,
+        balance: &Balance,
         player_id: Uuid,
         enemies: &[(Rect, bool)], // hitbox, invulnerable
+        ledges_taken: &[Ledge],   // held by other players
         dt: f32,
     ) {
         self.tick(dt, map, enemies);
@@ -55,19 +77,29 @@ impl Player {
             self.status.touch_platform();
         }
 
-        if !self.status.stunned() && self.combat.is_alive() {
-            self.apply_input(map, player_id, dt);
+        if self.physics.ledge.is_some() {
+            self.update_ledge(map, balance, player_id);
+        } else if let Some(ledge) = self.find_ledge(map, ledges_taken) {
+            self.grab_ledge(ledge, map);
+        } else if !self.status.stunned() && self.combat.is_alive() {
+            self.apply_input(map, balance, player_id, dt);
         }
 
-        if self.physics.should_lose_life() {
+        self.combat
+            .fire_projectiles(balance.character(self.character), &self.physics, player_id);
+
+        if self.physics.should_lose_life(map) {
             self.lose_life();
         }
     }
 
-    pub fn apply_input(&mut self, map: &Rect, player_id: Uuid, dt: f32) {
+    pub fn apply_input(&mut self, map: &Map, balance: &Balance, player_id: Uuid, dt: f32) {
+        let character = balance.character(self.character);
         let mut kind: Option<AttackKind> = None;
+        // an attack has to play out before anything else can start
+        let can_act = !self.combat.is_busy();
 
-        if self.input.slam() && self.status.can_slam {
+        if self.input.slam() && self.status.can_slam && (can_act || self.combat.is_slamming()) {
             self.physics.slam(dt);
             kind = Some(AttackKind::Slam);
         } else {
@@ -75,41 +107,130 @@ impl Player {
             self.combat.remove_slams();
         }
 
-        if self.input.light() && self.cooldowns.can_light() {
+        if self.input.light() && self.cooldowns.can_light() && can_act {
             kind = Some(AttackKind::Light);
-            self.cooldowns.activate_light();
+            self.cooldowns.activate_light(&balance.cooldowns);
         }
 
-        if self.input.normal() && self.cooldowns.can_normal() {
+        if self.input.normal() && self.cooldowns.can_normal() && can_act {
             kind = Some(AttackKind::Normal);
-            self.cooldowns.activate_normal();
+            self.cooldowns.activate_normal(&balance.cooldowns);
         }
 
-        if self.input.dash() && self.cooldowns.can_dash() && !self.status.parrying() {
+        if self.input.dash() && self.cooldowns.can_dash() && !self.status.parrying() && can_act {
             self.physics.dash();
             kind = Some(AttackKind::Dash);
-            self.cooldowns.activate_dash();
+            self.cooldowns.activate_dash(&balance.cooldowns);
         }
 
         if self.input.parry()
+            && can_act
             && self.physics.is_on_platform(map)
             && self.cooldowns.can_parry()
             && !self.combat.is_dashing()
             && !self.combat.is_slamming()
         {
             self.status.activate_parry();
-            self.cooldowns.activate_parry();
+            self.cooldowns.activate_parry(&balance.cooldowns);
         }
 
         if let Some(kind) = kind {
-            self.combat.spawn_attack(kind, &self.physics, player_id);
+            let properties = character.attack(&kind);
+            self.combat
+                .spawn_attack(kind, properties, &self.physics, player_id);
+        }
+    }
+
+    fn find_ledge(&self, map: &Map, ledges_taken: &[Ledge]) -> Option<Ledge> {
+        let can_grab = self.combat.is_alive()
+            && !self.status.stunned()
+            && !self.combat.is_busy()
+            && self.status.ledge_cooldown == 0.0
+            && !self.physics.is_on_platform(map);
+
+        can_grab
+            .then(|| self.physics.find_ledge(map, &self.input, ledges_taken))
+            .flatten()
+    }
+
+    /// Only the first grab since touching the ground makes the player
+    /// intangible, so hanging can't be stalled on
+    fn grab_ledge(&mut self, ledge: Ledge, map: &Map) {
+        self.physics.grab_ledge(ledge, map);
+        self.combat.remove_dashes();
+        self.combat.remove_slams();
+        self.status.hang = LEDGE_HANG_TIME;
+        if self.status.ledge_intangibility {
+            self.status.ledge_intangibility = false;
+            self.status.make_intangible(LEDGE_INTANGIBILITY);
+        }
+    }
+
+    fn let_go_of_ledge(&mut self) {
+        self.physics.ledge = None;
+        self.status.hang = 0.0;
+        self.status.ledge_cooldown = LEDGE_REGRAB_COOLDOWN;
+    }
+
+    fn climb_ledge(&mut self, map: &Map) {
+        self.physics.climb_ledge(map);
+        self.status.hang = 0.0;
+    }
+
+    /// Get-up options while hanging: jump, roll with a ready dash, attack, climb by
+    /// holding toward the platform or up, or let go by holding away or down
+    fn update_ledge(&mut self, map: &Map, balance: &Balance, player_id: Uuid) {
+        let Some(ledge) = self.physics.ledge else {
+            return;
+        };
+
+        if self.status.hang == 0.0 {
+            self.let_go_of_ledge();
+            return;
+        }
+        if self.status.hang > LEDGE_HANG_TIME - LEDGE_GETUP_DELAY {
+            return;
+        }
+
+        let inward = ledge.side.inward();
+        let (toward, away) = if inward > 0.0 {
+            (self.input.right(), self.input.left())
+        } else {
+            (self.input.left(), self.input.right())
+        };
+
+        if self.input.jump() && !self.physics.has_jumped {
+            self.physics.has_jumped = true;
+            self.let_go_of_ledge();
+            self.physics.vel.y = -self.physics.movement.jump_speed;
+        } else if self.input.dash() && self.cooldowns.can_dash() {
+            // rolling uses up the dash
+            self.climb_ledge(map);
+            self.physics.vel.x = inward * LEDGE_ROLL_SPEED;
+            self.status.make_intangible(LEDGE_ROLL_INTANGIBILITY);
+            self.cooldowns.activate_dash(&balance.cooldowns);
+        } else if self.input.light() || self.input.normal() {
+            // the character's normal attack, aimed onto the platform
+            self.climb_ledge(map);
+            self.physics.facing = Vec2::new(inward, 0.0);
+            let properties = balance
+                .character(self.character)
+                .attack(&AttackKind::Normal);
+            self.combat
+                .spawn_attack(AttackKind::Normal, properties, &self.physics, player_id);
+            self.cooldowns.activate_normal(&balance.cooldowns);
+        } else if toward || self.input.up() {
+            self.climb_ledge(map);
+            self.status.make_intangible(LEDGE_CLIMB_INTANGIBILITY);
+        } else if away || self.input.slam() {
+            self.let_go_of_ledge();
         }
     }
 
     fn tick(
         &mut self,
         dt: f32,
-        map: &Rect,
+        map: &Map,
         enemies: &[(Rect, bool)], // hitbox, invulnerable
     ) {
         self.combat.tick(dt);
@@ -123,9 +244,13 @@ impl Player {
         self.combat.lose_life();
         self.physics.reset();
         self.status.lose_life();
+        self.stats.falls += 1;
     }
 
-    pub fn apply_hit(&mut self, atk: &Attack, attacker_pos: Vec2, attacker_vel: Vec2) -> HitResult {
+    pub fn apply_hit(&mut self, atk: &Attack, attacker: &Rect, attacker_vel: Vec2) -> HitResult {
+        // heavier characters are sent off slower
+        let weight = self.physics.movement.weight;
+
         if self.status.invulnerable() {
             return HitResult::Ignored;
         }
@@ -148,7 +273,7 @@ impl Player {
                     self.combat.knockback_multiplier += atk.knockback_increase();
                     return HitResult::DashClash;
                 } else {
-                    self.physics.vel = attacker_vel * self.combat.knockback_multiplier;
+                    self.physics.vel = attacker_vel * self.combat.knockback_multiplier / weight;
                 }
             }
             AttackKind::Light => {
@@ -162,7 +287,8 @@ impl Player {
                     self.physics.vel = atk.facing.normalize_or_zero()
                         * 600.0
                         * self.combat.knockback_multiplier
-                        * get_combo_multiplier(self.combat.combo);
+                        * get_combo_multiplier(self.combat.combo)
+                        / weight;
 
                     // apply knockback multiplier boost for combo
                     self.combat.knockback_multiplier +=
@@ -177,23 +303,41 @@ impl Player {
             }
             AttackKind::Slam => {
                 // attacker has to be above victim for slam
-                if attacker_pos.y + PLAYER_SIZE < self.physics.pos.y {
+                if attacker.y + attacker.h < self.physics.pos.y {
                     // knockback is only vertical
                     self.physics
-                        .get_slammed(atk.knockback[1] * self.combat.knockback_multiplier);
+                        .get_slammed(atk.knockback[1] * self.combat.knockback_multiplier / weight);
                 } else {
                     return HitResult::Ignored;
                 }
             }
             AttackKind::Normal => {
-                self.physics.vel = atk.facing * 450.0;
+                self.physics.vel = atk.knockback / weight;
             }
         }
-        self.apply_generic_attack_traits(atk);
+        self.apply_generic_attack_traits(atk.stun(), atk.knockback_increase());
         HitResult::Hit
     }
 
-    pub fn apply_hit_effects(&mut self, attack: &Attack) {
+    pub fn apply_projectile_hit(&mut self, projectile: &Projectile) -> HitResult {
+        if self.status.invulnerable() {
+            return HitResult::Ignored;
+        }
+
+        if self.status.parrying() {
+            self.cooldowns.dash = 0.0;
+            self.combat.combo = 0;
+
+            return HitResult::Parried;
+        }
+
+        self.physics.vel = projectile.knockback() * self.combat.knockback_multiplier
+            / self.physics.movement.weight;
+        self.apply_generic_attack_traits(projectile.stun, projectile.knockback_increase);
+        HitResult::Hit
+    }
+
+    pub fn apply_hit_effects(&mut self, attack: &Attack, balance: &Balance) {
         match attack.kind() {
             AttackKind::Dash => {
                 self.physics.vel *= -0.5;
@@ -205,7 +349,7 @@ impl Player {
                 self.combat.remove_slams();
             }
             AttackKind::Normal => {
-                self.cooldowns.normal_hit();
+                self.cooldowns.normal_hit(&balance.cooldowns);
             }
         }
     }
@@ -224,12 +368,12 @@ impl Player {
         self.physics.set_parried_vel();
     }
 
-    fn apply_generic_attack_traits(&mut self, atk: &Attack) {
+    fn apply_generic_attack_traits(&mut self, stun: f32, knockback_increase: f32) {
         self.combat.remove_dashes();
         self.combat.remove_slams();
 
-        self.status.stun(atk.stun());
-        self.combat.knockback_multiplier += atk.knockback_increase();
+        self.status.stun(stun);
+        self.combat.knockback_multiplier += knockback_increase;
         self.status.invulnerable_timer = 0.3;
 
         self.combat.increase_combo();
@@ -1,8 +1,53 @@
-use ggez::input::keyboard::KeyCode;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::fmt;
 use wincode::{SchemaRead, SchemaWrite};
 
+/// Everything a player can do, clients decide which keys do what
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Jump,
+    Up,
+    Left,
+    Right,
+    Slam,
+    Dash,
+    Light,
+    Normal,
+    Parry,
+}
+
+impl Action {
+    pub const ALL: [Action; 9] = [
+        Action::Jump,
+        Action::Up,
+        Action::Left,
+        Action::Right,
+        Action::Slam,
+        Action::Dash,
+        Action::Light,
+        Action::Normal,
+        Action::Parry,
+    ];
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Action::Jump => "jump",
+            Action::Up => "up",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Slam => "slam",
+            Action::Dash => "dash",
+            Action::Light => "light",
+            Action::Normal => "normal",
+            Action::Parry => "parry",
+        };
+        write!(f, "{name}")
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug, SchemaRead, SchemaWrite)]
 pub struct PlayerInput {
     jump: bool,
@@ -32,18 +77,6 @@ impl PlayerInput {
         }
     }
 
-    pub fn update(&mut self, pressed: &HashSet<KeyCode>) {
-        self.jump = pressed.contains(&KeyCode::Space);
-        self.up = pressed.contains(&KeyCode::W);
-        self.left = pressed.contains(&KeyCode::A);
-        self.right = pressed.contains(&KeyCode::D);
-        self.slam = pressed.contains(&KeyCode::S);
-        self.dash = pressed.contains(&KeyCode::H);
-        self.normal = pressed.contains(&KeyCode::J);
-        self.light = pressed.contains(&KeyCode::K);
-        self.parry = pressed.contains(&KeyCode::L) || pressed.contains(&KeyCode::LShift);
-    }
-
     #[must_use]
     pub fn jump(&self) -> bool {
         self.jump
@@ -89,17 +122,77 @@ impl PlayerInput {
         self.parry
     }
 
+    #[must_use]
+    pub fn is_held(&self, action: Action) -> bool {
+        match action {
+            Action::Jump => self.jump,
+            Action::Up => self.up,
+            Action::Left => self.left,
+            Action::Right => self.right,
+            Action::Slam => self.slam,
+            Action::Dash => self.dash,
+            Action::Light => self.light,
+            Action::Normal => self.normal,
+            Action::Parry => self.parry,
+        }
+    }
+
+    /// Folds `later` into this input as if both happened in one tick,
+    /// directions are taken from `later` but no button press is lost
+    #[must_use]
+    pub fn followed_by(&self, later: &PlayerInput) -> PlayerInput {
+        PlayerInput {
+            jump: self.jump || later.jump,
+            up: later.up,
+            left: later.left,
+            right: later.right,
+            slam: self.slam || later.slam,
+            dash: self.dash || later.dash,
+            light: self.light || later.light,
+            normal: self.normal || later.normal,
+            parry: self.parry || later.parry,
+        }
+    }
+
     // SETTERS
+    pub fn set(&mut self, action: Action, value: bool) {
+        match action {
+            Action::Jump => self.jump = value,
+            Action::Up => self.up = value,
+            Action::Left => self.left = value,
+            Action::Right => self.right = value,
+            Action::Slam => self.slam = value,
+            Action::Dash => self.dash = value,
+            Action::Light => self.light = value,
+            Action::Normal => self.normal = value,
+            Action::Parry => self.parry = value,
+        }
+    }
     pub fn set_jump(&mut self, value: bool) {
+        self.jump = value
+    }
+    pub fn set_up(&mut self, value: bool) {
         self.up = value
     }
+    pub fn set_left(&mut self, value: bool) {
+        self.left = value
+    }
+    pub fn set_right(&mut self, value: bool) {
+        self.right = value
+    }
     pub fn set_slam(&mut self, value: bool) {
         self.slam = value
     }
+    pub fn set_dash(&mut self, value: bool) {
+        self.dash = value
+    }
     pub fn set_light(&mut self, value: bool) {
         self.light = value
     }
     pub fn set_normal(&mut self, value: bool) {
         self.normal = value
     }
+    pub fn set_parry(&mut self, value: bool) {
+        self.parry = value
+    }
 }
@@ -3,12 +3,14 @@ mod cooldowns;
 mod core;
 mod input;
 mod physics;
+mod stats;
 mod status;
 
 pub use combat::PlayerCombat;
 pub use cooldowns::PlayerCooldowns;
 pub use core::HitResult;
 pub use core::Player;
-pub use input::PlayerInput;
+pub use input::{Action, PlayerInput};
 pub use physics::PlayerPhysics;
+pub use stats::PlayerStats;
 pub use status::PlayerStatus;
@@ -1,8 +1,7 @@
 use super::{PlayerCombat, PlayerInput, PlayerStatus};
-use crate::constants::{
-    ACCELERATION, GRAVITY, MAX_SPEED, PLAYER_SIZE, RESISTANCE, VIRTUAL_HEIGHT, VIRTUAL_WIDTH,
-    WALL_SLIDE_SPEED,
-};
+use crate::balance::Movement;
+use crate::constants::{LEDGE_GRAB_RANGE, RESISTANCE, WALL_SLIDE_SPEED};
+use crate::map::{Ledge, LedgeSide, Map, Platform};
 use foundation::math_helpers::approach;
 use foundation::rect::Rect;
 use glam::Vec2;
@@ -14,20 +13,31 @@ pub struct PlayerPhysics {
     pub vel: Vec2,
     pub facing: Vec2,
     pub team_idx: usize,
+    // from the character played
+    pub movement: Movement,
     pub double_jumps: u8,
     pub has_jumped: bool,
+    pub has_pressed_down: bool,
+    // falling through one-way platforms
+    pub drop_through: bool,
+    // the ledge the player hangs from
+    pub ledge: Option<Ledge>,
 }
 
 impl PlayerPhysics {
-    pub fn new(start_pos: Vec2, team_idx: usize) -> Self {
+    pub fn new(start_pos: Vec2, team_idx: usize, movement: Movement) -> Self {
         Self {
             start_pos,
             pos: start_pos,
             vel: Vec2::new(0.0, 0.0),
             facing: get_facing_from_team(team_idx),
             team_idx,
-            double_jumps: 2,
+            movement,
+            double_jumps: movement.air_jumps,
             has_jumped: false,
+            has_pressed_down: false,
+            drop_through: false,
+            ledge: None,
         }
     }
 
@@ -37,7 +47,7 @@ impl PlayerPhysics {
         combat: &PlayerCombat,
         input: &PlayerInput,
         status: &PlayerStatus,
-        map: &Rect,
+        map: &Map,
         enemies: &[(Rect, bool)], // hitbox, invulnerable
     ) {
         if status.respawning() {
@@ -45,30 +55,48 @@ impl PlayerPhysics {
         }
 
         self.update_facing(input);
-        if !status.stunned() {
+        if self.hang(input, status, map) {
+            return;
+        }
+
+        // no jumping out of an attack that is still playing out
+        if !status.stunned() && !combat.is_busy() {
             self.apply_movement_input(input, map);
         }
         self.update_position(dt, combat, map, enemies);
         self.check_platform_collision(dt, input, status, map);
+        self.update_drop_through(map);
     }
 
     fn update_position(
         &mut self,
         dt: f32,
         combat: &PlayerCombat,
-        map: &Rect,
+        map: &Map,
         enemies: &[(Rect, bool)], // hitbox, invulnerable
     ) {
-        if self.facing.x != 0.0 && self.vel.x.abs() < MAX_SPEED[0] {
-            self.vel.x += ACCELERATION * dt * self.facing.x;
+        if self.facing.x != 0.0 && self.vel.x.abs() < self.movement.run_speed {
+            self.vel.x += self.movement.acceleration * dt * self.facing.x;
         }
 
         let old_pos = self.pos;
 
         self.pos += self.vel * dt;
 
-        // sweep test to prevent downward tunneling through platform
-        if let Some(corrected_y) = self.sweep_down(old_pos.y, self.pos.y, map) {
+        // sweep test to prevent downward tunneling through platforms,
+        // landing on the first one crossed
+        let landing = map
+            .active_platforms()
+            .filter(|platform| !(platform.is_one_way() && self.drop_through))
+            .filter_map(|platform| {
+                if platform.is_one_way() {
+                    self.sweep_onto_one_way(old_pos.y, self.pos.y, &platform.rect)
+                } else {
+                    self.sweep_down(old_pos.y, self.pos.y, &platform.rect)
+                }
+            })
+            .reduce(f32::min);
+        if let Some(corrected_y) = landing {
             // snap onto platform
             self.pos.y = corrected_y;
             self.vel.y = 0.0;
@@ -92,16 +120,18 @@ impl PlayerPhysics {
     }
 
     fn sweep_down(&self, old_y: f32, new_y: f32, object: &Rect) -> Option<f32> {
-        if self.get_rect().x + PLAYER_SIZE > object.x && self.get_rect().x < object.x + object.w {
+        if self.get_rect().x + self.movement.size > object.x
+            && self.get_rect().x < object.x + object.w
+        {
             // only downward motion matters for slam
             if new_y > old_y {
-                let old_bottom = old_y + PLAYER_SIZE;
-                let new_bottom = new_y + PLAYER_SIZE;
+                let old_bottom = old_y + self.movement.size;
+                let new_bottom = new_y + self.movement.size;
 
                 // if player bottom crossed the
                 // object's top between frames:
                 if old_bottom <= object.y && new_bottom >= object.y {
-                    return Some(object.y - PLAYER_SIZE);
+                    return Some(object.y - self.movement.size);
                 }
             }
         }
@@ -109,11 +139,54 @@ impl PlayerPhysics {
         None
     }
 
-    pub fn should_lose_life(&self) -> bool {
-        self.pos[1] > VIRTUAL_HEIGHT || self.pos[0] > VIRTUAL_WIDTH || self.pos[0] < 0.0
+    /// Lands a falling player whose bottom reaches the top of a one-way
+    /// platform this tick, also when a jump peaked with the feet inside it
+    fn sweep_onto_one_way(&self, old_y: f32, new_y: f32, platform: &Rect) -> Option<f32> {
+        let rect = self.get_rect();
+        let horizontal_overlap = rect.x + rect.w > platform.x && rect.x < platform.x + platform.w;
+        let old_bottom = old_y + self.movement.size;
+        let new_bottom = new_y + self.movement.size;
+
+        // feet that started below the platform pass under it
+        if horizontal_overlap
+            && new_y > old_y
+            && old_bottom < platform.y + platform.h
+            && new_bottom >= platform.y
+        {
+            return Some(platform.y - self.movement.size);
+        }
+
+        None
+    }
+
+    pub fn should_lose_life(&self, map: &Map) -> bool {
+        !map.blast_zone().contains(self.pos.x, self.pos.y)
     }
 
-    pub fn is_on_platform(&self, platform: &Rect) -> bool {
+    pub fn is_on_platform(&self, map: &Map) -> bool {
+        self.standing_platform(map).is_some()
+    }
+
+    /// Index of the platform the player is standing on
+    pub fn standing_platform(&self, map: &Map) -> Option<usize> {
+        map.platforms().iter().position(|platform| {
+            platform.active
+                && !(platform.is_one_way() && self.drop_through)
+                && self.stands_on(platform)
+        })
+    }
+
+    fn is_on_one_way_platform(&self, map: &Map) -> bool {
+        map.active_platforms()
+            .any(|platform| platform.is_one_way() && self.stands_on(platform))
+    }
+
+    /// A one-way platform only holds a player who isn't moving up through it
+    fn stands_on(&self, platform: &Platform) -> bool {
+        (!platform.is_one_way() || self.vel.y >= 0.0) && self.is_on_rect(&platform.rect)
+    }
+
+    fn is_on_rect(&self, platform: &Rect) -> bool {
         let player = self.get_rect();
 
         let player_bottom = player.y + player.h;
@@ -135,36 +208,46 @@ impl PlayerPhysics {
         dt: f32,
         input: &PlayerInput,
         status: &PlayerStatus,
-        map: &Rect,
+        map: &Map,
     ) {
         let mut rect = self.get_rect();
         let mut on_wall_right = false;
         let mut on_wall_left = false;
 
-        if rect.overlaps(map) {
-            let overlap_x1 = map.x + map.w - rect.x;
-            let overlap_x2 = rect.x + rect.w - map.x;
-            let overlap_y1 = map.y + map.h - rect.y;
-            let overlap_y2 = rect.y + rect.h - map.y;
+        for platform in map.active_platforms() {
+            // one-way platforms are only landed on through the downward sweep
+            if platform.is_one_way() {
+                continue;
+            }
+
+            let platform = &platform.rect;
+            if !rect.overlaps(platform) {
+                continue;
+            }
+
+            let overlap_x1 = platform.x + platform.w - rect.x;
+            let overlap_x2 = rect.x + rect.w - platform.x;
+            let overlap_y1 = platform.y + platform.h - rect.y;
+            let overlap_y2 = rect.y + rect.h - platform.y;
 
             let resolve_x = overlap_x1.min(overlap_x2);
             let resolve_y = overlap_y1.min(overlap_y2);
 
             if resolve_x < resolve_y {
-                if rect.x < map.x {
-                    rect.x = map.x - rect.w;
+                if rect.x < platform.x {
+                    rect.x = platform.x - rect.w;
                     on_wall_right = true;
                 } else {
-                    rect.x = map.x + map.w;
+                    rect.x = platform.x + platform.w;
                     on_wall_left = true;
                 }
-                self.double_jumps = 2;
-            } else if rect.y < map.y {
-                rect.y = map.y - rect.h;
+                self.double_jumps = self.movement.air_jumps;
+            } else if rect.y < platform.y {
+                rect.y = platform.y - rect.h;
                 self.vel.y = 0.0;
-                self.double_jumps = 2;
+                self.double_jumps = self.movement.air_jumps;
             } else {
-                rect.y = map.y + map.h;
+                rect.y = platform.y + platform.h;
                 if self.vel.y < 0.0 {
                     self.vel.y = 0.0;
                 }
@@ -177,19 +260,121 @@ impl PlayerPhysics {
         let on_platform = self.is_on_platform(map);
 
         if on_platform {
-            self.double_jumps = 2;
+            self.double_jumps = self.movement.air_jumps;
         }
 
         if holding_wall && !on_platform && !status.stunned() {
             self.vel.y = WALL_SLIDE_SPEED;
         } else {
-            self.vel.y += GRAVITY * dt;
+            self.vel.y += self.movement.gravity * dt;
         }
 
         self.pos.x = rect.x;
         self.pos.y = rect.y;
     }
 
+    /// Holds the player at their ledge, which may have moved or vanished.
+    /// Being hit knocks them off it.
+    fn hang(&mut self, input: &PlayerInput, status: &PlayerStatus, map: &Map) -> bool {
+        let Some(ledge) = self.ledge else {
+            return false;
+        };
+        let corner = match map.ledge_corner(ledge) {
+            Some(corner) if !status.stunned() => corner,
+            _ => {
+                self.ledge = None;
+                return false;
+            }
+        };
+
+        self.pos = self.hang_position(ledge.side, corner);
+        self.vel = Vec2::ZERO;
+        if !input.jump() {
+            self.has_jumped = false;
+        }
+        true
+    }
+
+    /// Beside the platform with the top at the corner
+    fn hang_position(&self, side: LedgeSide, corner: Vec2) -> Vec2 {
+        match side {
+            LedgeSide::Left => Vec2::new(corner.x - self.movement.size, corner.y),
+            LedgeSide::Right => corner,
+        }
+    }
+
+    /// A free ledge the falling player is close enough to grab, unless they
+    /// hold down or away from it
+    #[must_use]
+    pub fn find_ledge(&self, map: &Map, input: &PlayerInput, taken: &[Ledge]) -> Option<Ledge> {
+        if self.vel.y < 0.0 || input.slam() {
+            return None;
+        }
+
+        let rect = self.get_rect();
+        let center = self.center();
+
+        map.ledges()
+            .filter(|(ledge, _)| !taken.contains(ledge))
+            .find(|(ledge, corner)| {
+                let (beside, away) = match ledge.side {
+                    LedgeSide::Left => (center.x < corner.x, input.left()),
+                    LedgeSide::Right => (center.x > corner.x, input.right()),
+                };
+                let volume = Rect::new(
+                    match ledge.side {
+                        LedgeSide::Left => corner.x - LEDGE_GRAB_RANGE,
+                        LedgeSide::Right => corner.x,
+                    },
+                    corner.y - LEDGE_GRAB_RANGE / 2.0,
+                    LEDGE_GRAB_RANGE,
+                    LEDGE_GRAB_RANGE,
+                );
+
+                // another platform may cover the corner
+                let hang_pos = self.hang_position(ledge.side, *corner);
+                let hang_rect = Rect::new(
+                    hang_pos.x,
+                    hang_pos.y,
+                    self.movement.size,
+                    self.movement.size,
+                );
+                let covered = map
+                    .active_platforms()
+                    .any(|platform| !platform.is_one_way() && platform.rect.overlaps(&hang_rect));
+
+                beside && !away && rect.overlaps(&volume) && !covered
+            })
+            .map(|(ledge, _)| ledge)
+    }
+
+    pub fn grab_ledge(&mut self, ledge: Ledge, map: &Map) {
+        if let Some(corner) = map.ledge_corner(ledge) {
+            self.pos = self.hang_position(ledge.side, corner);
+        }
+        self.ledge = Some(ledge);
+        self.vel = Vec2::ZERO;
+        self.double_jumps = self.movement.air_jumps;
+        self.drop_through = false;
+    }
+
+    /// Onto the platform the player hangs from, at its edge
+    pub fn climb_ledge(&mut self, map: &Map) {
+        let Some(ledge) = self.ledge.take() else {
+            return;
+        };
+        let Some(corner) = map.ledge_corner(ledge) else {
+            return;
+        };
+
+        let x = match ledge.side {
+            LedgeSide::Left => corner.x,
+            LedgeSide::Right => corner.x - self.movement.size,
+        };
+        self.pos = Vec2::new(x, corner.y - self.movement.size);
+        self.vel = Vec2::ZERO;
+    }
+
     fn update_facing(&mut self, input: &PlayerInput) {
         self.facing = Vec2::new(0.0, 0.0);
         if input.left() {
@@ -206,28 +391,50 @@ impl PlayerPhysics {
         }
     }
 
-    fn apply_movement_input(&mut self, input: &PlayerInput, map: &Rect) {
+    fn apply_movement_input(&mut self, input: &PlayerInput, map: &Map) {
         if input.jump() && !self.has_jumped {
             self.has_jumped = true;
-            if self.is_on_platform(map) || self.double_jumps > 0 {
-                self.vel.y = -500.0;
-                if !self.is_on_platform(map) {
+            let on_platform = self.is_on_platform(map);
+            if on_platform || self.double_jumps > 0 {
+                self.vel.y = -self.movement.jump_speed;
+                if !on_platform {
                     self.double_jumps -= 1;
                 }
             }
         } else if !input.jump() {
             self.has_jumped = false;
         }
+
+        if input.slam() && !self.has_pressed_down {
+            self.has_pressed_down = true;
+            if self.is_on_one_way_platform(map) {
+                self.drop_through = true;
+            }
+        } else if !input.slam() {
+            self.has_pressed_down = false;
+        }
+    }
+
+    fn update_drop_through(&mut self, map: &Map) {
+        if !self.drop_through {
+            return;
+        }
+
+        // keep falling through until clear of every one-way platform
+        let rect = self.get_rect();
+        self.drop_through = map
+            .active_platforms()
+            .any(|platform| platform.is_one_way() && rect.overlaps(&platform.rect));
     }
 
     pub fn slam(&mut self, dt: f32) {
-        if self.vel.y < MAX_SPEED[1] {
-            self.vel.y += ACCELERATION * dt;
+        if self.vel.y < self.movement.fall_speed {
+            self.vel.y += self.movement.acceleration * dt;
         }
     }
 
     pub fn dash(&mut self) {
-        self.vel = self.facing.normalize_or_zero() * 1000.0;
+        self.vel = self.facing.normalize_or_zero() * self.movement.dash_speed;
     }
 
     pub fn apply_knockback(&mut self, force: Vec2, multiplier: f32) {
@@ -251,15 +458,107 @@ impl PlayerPhysics {
         self.pos = self.start_pos;
         self.vel = Vec2::new(0.0, 0.0);
         self.facing = get_facing_from_team(self.team_idx);
-        self.double_jumps = 2;
+        self.double_jumps = self.movement.air_jumps;
+        self.drop_through = false;
+        self.ledge = None;
+    }
+
+    /// Direction held, or the way the player moves when nothing is held
+    #[must_use]
+    pub fn aim(&self) -> Vec2 {
+        if self.facing == Vec2::ZERO {
+            Vec2::new(self.vel.x.signum(), 0.0)
+        } else {
+            self.facing.normalize()
+        }
+    }
+
+    #[must_use]
+    pub fn center(&self) -> Vec2 {
+        self.pos + self.movement.size / 2.0
     }
 
     #[must_use]
     pub fn get_rect(&self) -> Rect {
-        Rect::new(self.pos.x, self.pos.y, PLAYER_SIZE, PLAYER_SIZE)
+        Rect::new(
+            self.pos.x,
+            self.pos.y,
+            self.movement.size,
+            self.movement.size,
+        )
     }
 }
 
+// even teams start on the left, odd teams on the right
 fn get_facing_from_team(team_idx: usize) -> Vec2 {
-    Vec2::new(if team_idx == 0 { 1.0 } else { -1.0 }, 0.0)
+    Vec2::new(
+        if team_idx.is_multiple_of(2) {
+            1.0
+        } else {
+            -1.0
+        },
+        0.0,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::balance::Balance;
+    use crate::character::Character;
+    use crate::map::PlatformKind;
+
+    fn one_way_map() -> Map {
+        let mut map = Map::new();
+        map.platforms_mut()[0].kind = PlatformKind::OneWay;
+        map
+    }
+
+    /// A player whose feet are just below the top of the platform
+    fn player_rising_through(map: &Map) -> PlayerPhysics {
+        let movement = Balance::default().character(Character::Standard).movement;
+        let top = map.platforms()[0].rect;
+        let start = Vec2::new(top.x + 100.0, top.y - movement.size + 3.0);
+        let mut player = PlayerPhysics::new(start, 0, movement);
+        player.vel.y = -movement.jump_speed;
+        player.double_jumps = 1;
+        player
+    }
+
+    #[test]
+    fn rising_through_a_one_way_platform_is_not_standing() {
+        let map = one_way_map();
+        let mut player = player_rising_through(&map);
+        assert!(!player.is_on_platform(&map));
+
+        player.vel.y = 0.0;
+        assert!(player.is_on_platform(&map));
+    }
+
+    #[test]
+    fn jumping_through_a_one_way_platform_uses_an_air_jump() {
+        let map = one_way_map();
+        let mut player = player_rising_through(&map);
+        player.has_jumped = false;
+
+        let mut input = PlayerInput::new();
+        input.set_jump(true);
+        player.apply_movement_input(&input, &map);
+
+        assert_eq!(player.double_jumps, 0);
+    }
+
+    #[test]
+    fn jumping_off_a_one_way_platform_keeps_air_jumps() {
+        let map = one_way_map();
+        let mut player = player_rising_through(&map);
+        player.vel.y = 0.0;
+
+        let mut input = PlayerInput::new();
+        input.set_jump(true);
+        player.apply_movement_input(&input, &map);
+
+        assert!(player.vel.y < 0.0);
+        assert_eq!(player.double_jumps, 1);
+    }
 }
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+use wincode::{SchemaRead, SchemaWrite};
+
+/// Tally of what a player did during a match, kept for the match history
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug, SchemaWrite, SchemaRead)]
+pub struct PlayerStats {
+    pub hits_landed: u32,
+    pub hits_taken: u32,
+    pub parries: u32,
+    pub falls: u32,
+    // longest combo dealt to a single enemy
+    pub best_combo: u32,
+}
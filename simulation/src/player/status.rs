@@ -8,6 +8,12 @@ pub struct PlayerStatus {
     pub invulnerable_timer: f32,
     pub parry: f32,
     pub can_slam: bool,
+    pub disconnected: bool,
+    // hanging time left on a ledge
+    pub hang: f32,
+    pub ledge_cooldown: f32,
+    // the next ledge grab makes the player intangible
+    pub ledge_intangibility: bool,
 }
 
 impl Default for  PlayerStatus {
@@ -18,6 +24,10 @@ impl Default for  PlayerStatus {
             invulnerable_timer: 0.0,
             parry: 0.0,
             can_slam: true,
+            disconnected: false,
+            hang: 0.0,
+            ledge_cooldown: 0.0,
+            ledge_intangibility: true,
         }
     }
 }
@@ -29,11 +39,14 @@ impl PlayerStatus {
             &mut self.respawn_timer,
             &mut self.invulnerable_timer,
             &mut self.parry,
+            &mut self.hang,
+            &mut self.ledge_cooldown,
         ], dt);
     }
 
     pub fn touch_platform(&mut self) {
         self.can_slam = false;
+        self.ledge_intangibility = true;
     }
 
     pub fn stun(&mut self, stun: f32) {
@@ -48,6 +61,13 @@ impl PlayerStatus {
         self.respawn_timer = RESPAWN_TIME;
         self.stunned = RESPAWN_TIME;
         self.invulnerable_timer = RESPAWN_TIME + 0.5;
+        self.hang = 0.0;
+        self.ledge_intangibility = true;
+    }
+
+    /// Invulnerable for at least `time` from now
+    pub fn make_intangible(&mut self, time: f32) {
+        self.invulnerable_timer = self.invulnerable_timer.max(time);
     }
 
     #[must_use]
@@ -0,0 +1,158 @@
+use crate::attack::AttackKind;
+use crate::balance::{AttackProperties, ProjectileProperties};
+use foundation::rect::Rect;
+use glam::Vec2;
+use uuid::Uuid;
+
+/// An attack that leaves its owner and travels on its own, until it hits
+/// an enemy or a solid platform, leaves the blast zone or runs out of time
+#[derive(Clone)]
+pub struct Projectile {
+    // unique within the match, so snapshots can tell projectiles apart,
+    // given out by the game state
+    pub id: u32,
+    pub owner: Uuid,
+    // the attack that fired it
+    pub kind: AttackKind,
+    // top left corner
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub timer: f32,
+    pub lifetime: f32,
+    pub size: f32,
+    pub gravity: f32,
+    pub stun: f32,
+    pub knockback_increase: f32,
+    pub knockback: f32,
+}
+
+impl Projectile {
+    /// Fired from the owner's center in the aimed direction, starting as far
+    /// out as the attack's `offset` and flying for its active time
+    #[must_use]
+    pub fn new(
+        kind: AttackKind,
+        attack: &AttackProperties,
+        projectile: &ProjectileProperties,
+        owner: Uuid,
+        owner_center: Vec2,
+        aim: Vec2,
+    ) -> Self {
+        Self {
+            id: 0,
+            owner,
+            kind,
+            pos: owner_center - attack.size / 2.0 + aim * attack.offset,
+            vel: aim * projectile.speed,
+            timer: 0.0,
+            lifetime: attack.active,
+            size: attack.size,
+            gravity: projectile.gravity,
+            stun: attack.stun,
+            knockback_increase: attack.knockback_increase,
+            knockback: attack.knockback,
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.timer += dt;
+        self.vel.y += self.gravity * dt;
+        self.pos += self.vel * dt;
+    }
+
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        self.timer >= self.lifetime
+    }
+
+    /// Knockback for whoever it hits, in the direction it travels
+    #[must_use]
+    pub fn knockback(&self) -> Vec2 {
+        self.vel.normalize_or_zero() * self.knockback
+    }
+
+    #[must_use]
+    pub fn get_rect(&self) -> Rect {
+        Rect::new(self.pos.x, self.pos.y, self.size, self.size)
+    }
+
+    /// Whether the projectile passed through `rect` on its way here from
+    /// `old_pos`, so a fast one cannot skip over a thin platform
+    #[must_use]
+    pub fn swept_overlaps(&self, old_pos: Vec2, rect: &Rect) -> bool {
+        // the path of the top left corner against the rect grown by our size
+        let min = Vec2::new(rect.x - self.size, rect.y - self.size);
+        let max = Vec2::new(rect.x + rect.w, rect.y + rect.h);
+        let delta = self.pos - old_pos;
+
+        let (mut enter, mut exit) = (0.0_f32, 1.0_f32);
+        for axis in 0..2 {
+            if delta[axis] == 0.0 {
+                if old_pos[axis] <= min[axis] || old_pos[axis] >= max[axis] {
+                    return false;
+                }
+            } else {
+                let to_min = (min[axis] - old_pos[axis]) / delta[axis];
+                let to_max = (max[axis] - old_pos[axis]) / delta[axis];
+                enter = enter.max(to_min.min(to_max));
+                exit = exit.min(to_min.max(to_max));
+            }
+        }
+
+        enter < exit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn projectile(pos: Vec2) -> Projectile {
+        Projectile {
+            id: 0,
+            owner: Uuid::nil(),
+            kind: AttackKind::Light,
+            pos,
+            vel: Vec2::ZERO,
+            timer: 0.0,
+            lifetime: 1.0,
+            size: 10.0,
+            gravity: 0.0,
+            stun: 0.0,
+            knockback_increase: 0.0,
+            knockback: 0.0,
+        }
+    }
+
+    #[test]
+    fn catches_a_thin_platform_passed_within_one_tick() {
+        let wall = Rect::new(100.0, 0.0, 5.0, 100.0);
+
+        let passed = projectile(Vec2::new(150.0, 50.0));
+        assert!(!passed.get_rect().overlaps(&wall));
+        assert!(passed.swept_overlaps(Vec2::new(50.0, 50.0), &wall));
+
+        let below = projectile(Vec2::new(150.0, 120.0));
+        assert!(!below.swept_overlaps(Vec2::new(50.0, 120.0), &wall));
+    }
+
+    #[test]
+    fn matches_overlap_when_standing_still() {
+        let platform = Rect::new(0.0, 100.0, 200.0, 20.0);
+
+        let inside = projectile(Vec2::new(50.0, 95.0));
+        assert!(inside.swept_overlaps(inside.pos, &platform));
+
+        let touching = projectile(Vec2::new(50.0, 90.0));
+        assert!(!touching.swept_overlaps(touching.pos, &platform));
+    }
+
+    #[test]
+    fn misses_a_platform_the_diagonal_path_goes_around() {
+        let platform = Rect::new(100.0, 100.0, 20.0, 20.0);
+
+        // the box around the path covers the platform, the path does not
+        let diagonal = projectile(Vec2::new(200.0, 200.0));
+        assert!(!diagonal.swept_overlaps(Vec2::new(0.0, 100.0), &platform));
+    }
+}
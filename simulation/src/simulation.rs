@@ -1,16 +1,61 @@
 use crate::game_state::GameState;
+use crate::{GameEvent, PlayerInput};
+use std::collections::HashMap;
+use uuid::Uuid;
 
 pub struct SimulationCore {
     game_state: GameState,
+    // everything that happened since the last `take_events`
+    events: Vec<GameEvent>,
 }
 
 impl SimulationCore {
     pub fn new(game_state: GameState) -> Self {
-        Self { game_state }
+        Self {
+            game_state,
+            events: Vec::new(),
+        }
     }
 
     pub fn step(&mut self, dt: f32) {
         self.game_state.update(dt);
+        self.events.extend_from_slice(&self.game_state.events);
+    }
+
+    /// Steps with lag compensation, see `GameState::update_with_views`
+    pub fn step_with_views(&mut self, dt: f32, views: &HashMap<Uuid, &GameState>) {
+        self.game_state.update_with_views(dt, views);
+        self.events.extend_from_slice(&self.game_state.events);
+    }
+
+    /// Steps only `player_id` with `input`, used for client-side prediction
+    pub fn predict(&mut self, player_id: &Uuid, input: PlayerInput, dt: f32) {
+        if !self.game_state.players.contains_key(player_id) {
+            return;
+        }
+
+        self.game_state.apply_input(player_id, input);
+        // platforms stay where the last snapshot put them, but the
+        // server carries the player along, so the prediction does too
+        self.game_state.carry_player(player_id, dt);
+        self.game_state.update_player(player_id, dt);
+    }
+
+    /// Input the player holds from the next step on, unknown players are ignored
+    pub fn set_input(&mut self, player_id: &Uuid, input: PlayerInput) {
+        if let Some(player) = self.game_state.players.get_mut(player_id) {
+            player.input = input;
+        }
+    }
+
+    #[must_use]
+    pub fn input(&self, player_id: &Uuid) -> Option<&PlayerInput> {
+        self.game_state.players.get(player_id).map(|p| &p.input)
+    }
+
+    /// Events from every step since the last call, oldest first
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
     }
 
     pub fn game_state(&self) -> &GameState {
@@ -21,3 +66,184 @@ impl SimulationCore {
         &mut self.game_state
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{FIXED_DT, LEDGE_GETUP_DELAY, LEDGE_REGRAB_COOLDOWN, PLAYER_SIZE};
+    use crate::map::{Ledge, LedgeSide, Map};
+    use crate::{MatchBuilder, Player};
+    use glam::Vec2;
+
+    const LEFT_LEDGE: Ledge = Ledge {
+        platform: 0,
+        side: LedgeSide::Left,
+    };
+
+    /// A match on the default map with everyone spawned and landed
+    fn landed_match() -> (SimulationCore, Uuid, Uuid) {
+        let (player, enemy) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut sim = MatchBuilder::new(Map::default())
+            .player(0, player)
+            .player(1, enemy)
+            .build()
+            .unwrap();
+        for _ in 0..240 {
+            sim.step(FIXED_DT);
+        }
+        (sim, player, enemy)
+    }
+
+    fn player_mut<'a>(sim: &'a mut SimulationCore, id: &Uuid) -> &'a mut Player {
+        sim.game_state_mut().players.get_mut(id).unwrap()
+    }
+
+    fn ledge(sim: &SimulationCore, id: &Uuid) -> Option<Ledge> {
+        sim.game_state().players[id].physics.ledge
+    }
+
+    /// Puts the player just off the left ledge, within grabbing range
+    fn drop_beside_ledge(sim: &mut SimulationCore, id: &Uuid) {
+        let corner = sim.game_state().map.ledge_corner(LEFT_LEDGE).unwrap();
+        let player = player_mut(sim, id);
+        player.physics.pos = corner + Vec2::new(-PLAYER_SIZE - 4.0, -10.0);
+        player.physics.vel = Vec2::ZERO;
+        player.status.invulnerable_timer = 0.0;
+    }
+
+    fn grab_ledge(sim: &mut SimulationCore, id: &Uuid) {
+        drop_beside_ledge(sim, id);
+        sim.step(FIXED_DT);
+        assert_eq!(ledge(sim, id), Some(LEFT_LEDGE));
+    }
+
+    fn hold(sim: &mut SimulationCore, id: &Uuid, set: fn(&mut PlayerInput, bool)) {
+        let mut input = PlayerInput::default();
+        set(&mut input, true);
+        sim.set_input(id, input);
+    }
+
+    fn step_seconds(sim: &mut SimulationCore, seconds: f32) {
+        for _ in 0..(seconds / FIXED_DT).ceil() as usize {
+            sim.step(FIXED_DT);
+        }
+    }
+
+    #[test]
+    fn predicts_a_player_carried_by_a_moving_platform() {
+        let (player, enemy) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut server = MatchBuilder::new(Map::default())
+            .player(0, player)
+            .player(1, enemy)
+            .build()
+            .unwrap();
+        // let everyone spawn and land before the platform starts moving
+        for _ in 0..240 {
+            server.step(FIXED_DT);
+        }
+        server.game_state_mut().map.platforms_mut()[0].vel = [120.0, 0.0];
+        let mut client = SimulationCore::new(server.game_state().clone());
+
+        let start = client.game_state().players[&player].physics.pos;
+        for _ in 0..30 {
+            client.predict(&player, PlayerInput::default(), FIXED_DT);
+        }
+        let moved = client.game_state().players[&player].physics.pos - start;
+
+        assert!((moved.x - 120.0 * FIXED_DT * 30.0).abs() < 1.0);
+        assert!(moved.y.abs() < 1.0);
+    }
+
+    #[test]
+    fn waits_out_the_get_up_delay_before_climbing() {
+        let (mut sim, player, _) = landed_match();
+        grab_ledge(&mut sim, &player);
+        hold(&mut sim, &player, PlayerInput::set_right);
+
+        step_seconds(&mut sim, LEDGE_GETUP_DELAY / 2.0);
+        assert_eq!(ledge(&sim, &player), Some(LEFT_LEDGE));
+
+        step_seconds(&mut sim, LEDGE_GETUP_DELAY);
+        assert_eq!(ledge(&sim, &player), None);
+        let map = &sim.game_state().map;
+        assert!(
+            sim.game_state().players[&player]
+                .physics
+                .is_on_platform(map)
+        );
+    }
+
+    #[test]
+    fn only_the_first_grab_since_landing_is_intangible() {
+        let (mut sim, player, _) = landed_match();
+        grab_ledge(&mut sim, &player);
+        assert!(sim.game_state().players[&player].status.invulnerable());
+
+        // let go, then grab again without touching the ground
+        step_seconds(&mut sim, LEDGE_GETUP_DELAY);
+        hold(&mut sim, &player, PlayerInput::set_slam);
+        sim.step(FIXED_DT);
+        assert_eq!(ledge(&sim, &player), None);
+        sim.set_input(&player, PlayerInput::default());
+        step_seconds(&mut sim, LEDGE_REGRAB_COOLDOWN);
+        grab_ledge(&mut sim, &player);
+        assert!(!sim.game_state().players[&player].status.invulnerable());
+
+        // landing makes the next grab intangible again
+        hold(&mut sim, &player, PlayerInput::set_right);
+        step_seconds(&mut sim, LEDGE_GETUP_DELAY * 2.0);
+        sim.set_input(&player, PlayerInput::default());
+        sim.step(FIXED_DT);
+        grab_ledge(&mut sim, &player);
+        assert!(sim.game_state().players[&player].status.invulnerable());
+    }
+
+    #[test]
+    fn a_held_ledge_cannot_be_grabbed() {
+        let (mut sim, player, enemy) = landed_match();
+        grab_ledge(&mut sim, &enemy);
+
+        drop_beside_ledge(&mut sim, &player);
+        sim.step(FIXED_DT);
+        assert_eq!(ledge(&sim, &player), None);
+        assert_eq!(ledge(&sim, &enemy), Some(LEFT_LEDGE));
+    }
+
+    #[test]
+    fn lets_go_when_stunned() {
+        let (mut sim, player, _) = landed_match();
+        grab_ledge(&mut sim, &player);
+
+        player_mut(&mut sim, &player).status.stun(0.5);
+        sim.step(FIXED_DT);
+        assert_eq!(ledge(&sim, &player), None);
+    }
+
+    #[test]
+    fn lets_go_when_the_platform_vanishes() {
+        let (mut sim, player, _) = landed_match();
+        grab_ledge(&mut sim, &player);
+
+        sim.game_state_mut().map.platforms_mut()[0].active = false;
+        sim.step(FIXED_DT);
+        assert_eq!(ledge(&sim, &player), None);
+    }
+
+    #[test]
+    fn waits_out_the_regrab_cooldown() {
+        let (mut sim, player, _) = landed_match();
+        grab_ledge(&mut sim, &player);
+        step_seconds(&mut sim, LEDGE_GETUP_DELAY);
+        hold(&mut sim, &player, PlayerInput::set_slam);
+        sim.step(FIXED_DT);
+        assert_eq!(ledge(&sim, &player), None);
+        sim.set_input(&player, PlayerInput::default());
+
+        drop_beside_ledge(&mut sim, &player);
+        sim.step(FIXED_DT);
+        assert_eq!(ledge(&sim, &player), None);
+
+        step_seconds(&mut sim, LEDGE_REGRAB_COOLDOWN);
+        grab_ledge(&mut sim, &player);
+    }
+}